@@ -1,4 +1,6 @@
+mod number;
 mod scanner;
 mod token;
-pub use scanner::Scanner;
+pub use number::format_number;
+pub use scanner::{Scanner, ScannerError, ScannerErrorKind};
 pub use token::{Token, TokenType};
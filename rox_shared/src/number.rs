@@ -0,0 +1,25 @@
+/// Format a number the way Lox prints it, shared by
+/// both interpreters so the same program produces the
+/// same output. Whole numbers print without a
+/// trailing `.0`
+pub fn format_number(n: f64) -> String {
+    if n == 0.0 {
+        // avoid printing `-0`
+        return String::from("0");
+    }
+    format!("{}", n)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn numbers() {
+        assert_eq!(format_number(3.0), "3");
+        assert_eq!(format_number(0.5), "0.5");
+        assert_eq!(format_number(-0.0), "0");
+        assert_eq!(format_number(-1.25), "-1.25");
+        assert_eq!(format_number(1e21), "1000000000000000000000");
+    }
+}
@@ -1,28 +1,95 @@
 use super::token::{Token, TokenType};
+use std::io::Read;
 
-type ScannerResult = Result<Token, String>;
-type InvertedResult = Result<Option<Token>, String>;
+type ScannerResult = Result<Token, ScannerError>;
+type InvertedResult = Result<Option<Token>, ScannerError>;
+
+/// What kind of mistake stopped the scanner
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScannerErrorKind {
+    /// The source couldn't be read
+    Io,
+    UnknownToken,
+    /// The source ended before a string's closing `"`
+    UnterminatedString,
+    MalformedNumber,
+    NumberOutOfRange,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScannerError {
+    pub kind: ScannerErrorKind,
+    message: String,
+}
+
+impl ScannerError {
+    pub fn new(kind: ScannerErrorKind, message: String) -> Self {
+        Self { kind, message }
+    }
+}
+
+impl std::fmt::Display for ScannerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.message.fmt(f)
+    }
+}
+
+impl std::error::Error for ScannerError {}
 pub struct Scanner {
     source: Vec<char>,
     start: usize,
     current: usize,
     lookahead: Option<Token>,
     pub line: usize,
+    /// The column of the next character to be consumed
+    pub column: usize,
+    /// The column the token currently being scanned started at
+    token_column: usize,
+    /// The count of unclosed `{`s inside of each `${`
+    /// string interpolation we are currently in
+    interpolations: Vec<usize>,
 }
 
 impl Scanner {
-    pub fn new(source: String) -> Result<Self, String> {
+    pub fn new(source: String) -> Result<Self, ScannerError> {
         let mut ret = Self {
             source: source.chars().collect(),
             start: 0,
             current: 0,
             lookahead: None,
             line: 1,
+            column: 1,
+            token_column: 1,
+            interpolations: Vec::new(),
         };
+        ret.skip_shebang();
         let _ = ret.scan_token()?;
 
         Ok(ret)
     }
+    /// Read all of `reader` and scan it, failing to read
+    /// or reading something that isn't UTF-8 is an error
+    pub fn from_reader(mut reader: impl Read) -> Result<Self, ScannerError> {
+        let mut source = String::new();
+        reader
+            .read_to_string(&mut source)
+            .map_err(|e| ScannerError::new(ScannerErrorKind::Io, format!("IO Error: {}", e)))?;
+        Self::new(source)
+    }
+    /// Skip a `#!` line at the very start of the source so
+    /// scripts can be run as executables, the newline ending
+    /// it is left to be scanned like any other
+    fn skip_shebang(&mut self) {
+        if self.source.starts_with(&['#', '!']) {
+            while self.peek() != '\n' && !self.is_at_end() {
+                self.advance();
+            }
+        }
+    }
+    /// The column the most recently scanned token started at
+    pub fn token_column(&self) -> usize {
+        self.token_column
+    }
     pub fn lookahead(&self) -> &Option<Token> {
         &self.lookahead
     }
@@ -33,8 +100,8 @@ impl Scanner {
             false
         }
     }
-    pub fn scan_tokens(&mut self) -> Result<Vec<Token>, String> {
-        let ret = self.collect::<Result<Vec<Token>, String>>()?;
+    pub fn scan_tokens(&mut self) -> Result<Vec<Token>, ScannerError> {
+        let ret = self.collect::<Result<Vec<Token>, ScannerError>>()?;
         Ok(ret)
     }
 
@@ -51,23 +118,75 @@ impl Scanner {
 
     pub fn scan_token(&mut self) -> InvertedResult {
         if self.is_at_end() {
-            if self.lookahead.is_none() {
+            if self.done() && self.lookahead.is_some() {
                 return Ok(None);
-            } else {
-                return Ok(::std::mem::replace(&mut self.lookahead, None));
             }
+            self.token_column = self.column;
+            let eof = Token::new(
+                TokenType::Eof,
+                String::new(),
+                self.line,
+                self.column,
+                self.current,
+                self.current,
+            );
+            return Ok(self.lookahead.replace(eof));
         }
+        self.token_column = self.column;
         let next = match self.advance() {
             Some('(') => self.add_token(TokenType::LeftParen),
             Some(')') => self.add_token(TokenType::RightParen),
-            Some('{') => self.add_token(TokenType::LeftBrace),
-            Some('}') => self.add_token(TokenType::RightBrace),
+            Some('{') => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth += 1;
+                }
+                self.add_token(TokenType::LeftBrace)
+            }
+            Some('}') => {
+                if self.end_interpolation() {
+                    self.string_from(self.current)?
+                } else {
+                    self.add_token(TokenType::RightBrace)
+                }
+            }
             Some(',') => self.add_token(TokenType::Comma),
             Some('.') => self.add_token(TokenType::Dot),
-            Some('-') => self.add_token(TokenType::Minus),
-            Some('+') => self.add_token(TokenType::Plus),
+            Some('?') => {
+                if self.match_next('.') {
+                    self.add_token(TokenType::QuestionDot)
+                } else {
+                    self.unknown_token('?')?
+                }
+            }
+            Some('-') => {
+                let token = if self.match_next('=') {
+                    TokenType::MinusEqual
+                } else if self.match_next('-') {
+                    TokenType::MinusMinus
+                } else {
+                    TokenType::Minus
+                };
+                self.add_token(token)
+            }
+            Some('+') => {
+                let token = if self.match_next('=') {
+                    TokenType::PlusEqual
+                } else if self.match_next('+') {
+                    TokenType::PlusPlus
+                } else {
+                    TokenType::Plus
+                };
+                self.add_token(token)
+            }
             Some(';') => self.add_token(TokenType::Semicolon),
-            Some('*') => self.add_token(TokenType::Star),
+            Some('*') => {
+                let token = if self.match_next('=') {
+                    TokenType::StarEqual
+                } else {
+                    TokenType::Star
+                };
+                self.add_token(token)
+            }
             Some('!') => {
                 let token = if self.match_next('=') {
                     TokenType::BangEqual
@@ -87,6 +206,8 @@ impl Scanner {
             Some('<') => {
                 let token = if self.match_next('=') {
                     TokenType::LessEqual
+                } else if self.match_next('<') {
+                    TokenType::LessLess
                 } else {
                     TokenType::Less
                 };
@@ -95,34 +216,38 @@ impl Scanner {
             Some('>') => {
                 let token = if self.match_next('=') {
                     TokenType::GreaterEqual
+                } else if self.match_next('>') {
+                    TokenType::GreaterGreater
                 } else {
                     TokenType::Greater
                 };
                 self.add_token(token)
             }
+            Some('&') => self.add_token(TokenType::Ampersand),
+            Some('|') => self.add_token(TokenType::Pipe),
+            Some('^') => self.add_token(TokenType::Caret),
             Some('/') => {
                 if self.match_next('/') {
                     while self.peek() != '\n' && !self.is_at_end() {
                         self.advance();
                     }
+                    self.start = self.current;
                     return self.scan_token();
+                } else if self.match_next('=') {
+                    self.add_token(TokenType::SlashEqual)
                 } else {
                     self.add_token(TokenType::Slash)
                 }
             }
-            Some(' ') | Some('\r') | Some('\t') => {
-                self.start += 1;
+            Some(' ') | Some('\r') | Some('\t') | Some('\n') => {
+                self.start = self.current;
                 return self.scan_token();
             }
-            Some('\n') => {
-                self.line += 1;
-                return self.scan_token();
-            }
-            Some('"') => self.string()?,
+            Some('"') => self.string_from(self.current)?,
             Some(c) => {
-                if c.is_digit(10) {
+                if c.is_ascii_digit() {
                     self.number()?
-                } else if c.is_alphabetic() {
+                } else if c.is_alphabetic() || c == '_' {
                     self.identifier()?
                 } else {
                     self.unknown_token(c)?
@@ -130,17 +255,46 @@ impl Scanner {
             }
             None => self.unknown_token('\0')?,
         };
-        let ret = ::std::mem::replace(&mut self.lookahead, Some(next));
+        let ret = self.lookahead.replace(next);
         Ok(ret)
     }
 
-    fn unknown_token(&self, c: char) -> Result<Token, String> {
-        Err(format!("unknown token found {:?}", c))
+    /// Account for a `}`, returning true if it closes
+    /// the innermost `${` string interpolation
+    fn end_interpolation(&mut self) -> bool {
+        match self.interpolations.last_mut() {
+            Some(0) => {
+                self.interpolations.pop();
+                true
+            }
+            Some(depth) => {
+                *depth -= 1;
+                false
+            }
+            None => false,
+        }
+    }
+
+    fn unknown_token(&self, c: char) -> ScannerResult {
+        Err(ScannerError::new(
+            ScannerErrorKind::UnknownToken,
+            format!(
+                "unknown token found {:?} at column {}",
+                c, self.token_column
+            ),
+        ))
     }
 
     pub fn advance(&mut self) -> Option<char> {
         self.current += 1;
-        self.source.get(self.current - 1).copied()
+        let next = self.source.get(self.current - 1).copied();
+        if next == Some('\n') {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        next
     }
 
     pub fn add_token(&mut self, kind: TokenType) -> Token {
@@ -153,7 +307,14 @@ impl Scanner {
             .collect::<String>()
             .trim()
             .to_string();
-        Token::new(kind, text, self.line, self.start, self.current)
+        Token::new(
+            kind,
+            text,
+            self.line,
+            self.token_column,
+            self.start,
+            self.current,
+        )
     }
     fn match_next(&mut self, e: char) -> bool {
         if self.is_at_end() {
@@ -177,48 +338,155 @@ impl Scanner {
         }
     }
 
-    pub fn string(&mut self) -> ScannerResult {
+    /// Scan the contents of a string starting at `content_start`
+    /// up to either the closing `"`, producing a `String` token, or
+    /// the next `${`, producing an `Interpolation` token
+    pub fn string_from(&mut self, content_start: usize) -> ScannerResult {
         while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
+            if self.peek() == '$' && self.peek_next() == '{' {
+                let text = self.source[content_start..self.current]
+                    .iter()
+                    .collect::<String>();
+                let _ = self.advance();
+                let _ = self.advance();
+                self.interpolations.push(0);
+                return Ok(self.add_literal(TokenType::Interpolation(text)));
             }
             let _ = self.advance();
         }
         if self.is_at_end() {
-            Err("Unterminated string literal".to_string())
+            Err(ScannerError::new(
+                ScannerErrorKind::UnterminatedString,
+                format!(
+                    "Unterminated string literal starting at column {}",
+                    self.token_column
+                ),
+            ))
         } else {
             let _ = self.advance();
-            let text = self.source[self.start + 1..self.current - 1]
+            let text = self.source[content_start..self.current - 1]
                 .iter()
-                .collect::<String>()
-                .trim()
-                .to_string();
+                .collect::<String>();
             Ok(self.add_literal(TokenType::String(text)))
         }
     }
 
     pub fn number(&mut self) -> ScannerResult {
-        while self.peek().is_digit(10) {
+        if self.source[self.start] == '0' {
+            let radix = match self.peek() {
+                'x' | 'X' => Some(16),
+                'b' | 'B' => Some(2),
+                _ => None,
+            };
+            if let Some(radix) = radix {
+                return self.radix_number(radix);
+            }
+        }
+        self.digits(10)?;
+        if self.peek() == '.' && self.peek_next().is_ascii_digit() {
             let _ = self.advance();
+            self.digits(10)?;
         }
-        if self.peek() == '.' && self.peek_next().is_digit(10) {
+        if self.peek() == 'e' || self.peek() == 'E' {
             let _ = self.advance();
-            while self.peek().is_digit(10) {
+            if self.peek() == '+' || self.peek() == '-' {
                 let _ = self.advance();
             }
+            if !self.peek().is_ascii_digit() {
+                let text = self.source[self.start..self.current]
+                    .iter()
+                    .collect::<String>();
+                return Err(ScannerError::new(
+                    ScannerErrorKind::MalformedNumber,
+                    format!(
+                        "Malformed number literal {} at column {}",
+                        text, self.token_column
+                    ),
+                ));
+            }
+            self.digits(10)?;
         }
         let text = self.source[self.start..self.current]
             .iter()
             .collect::<String>();
-        let value = text
-            .trim()
-            .parse()
-            .map_err(|e| format!("Unable to parse number {} {}", text, e))?;
+        let value: f64 = text.replace('_', "").parse().map_err(|e| {
+            ScannerError::new(
+                ScannerErrorKind::MalformedNumber,
+                format!("Unable to parse number {} {}", text, e),
+            )
+        })?;
+        if !value.is_finite() {
+            return Err(ScannerError::new(
+                ScannerErrorKind::NumberOutOfRange,
+                format!(
+                    "numeric literal out of range {} at column {}",
+                    text, self.token_column
+                ),
+            ));
+        }
         Ok(self.add_literal(TokenType::Number(value)))
     }
 
+    /// Consume a run of digits, allowing single `_`s
+    /// between them as separators. A `_` that isn't
+    /// followed by a digit is an error
+    fn digits(&mut self, radix: u32) -> Result<(), ScannerError> {
+        while self.peek().is_digit(radix)
+            || (self.peek() == '_' && self.peek_next().is_digit(radix))
+        {
+            let _ = self.advance();
+        }
+        if self.peek() == '_' {
+            let _ = self.advance();
+            let text = self.source[self.start..self.current]
+                .iter()
+                .collect::<String>();
+            return Err(ScannerError::new(
+                ScannerErrorKind::MalformedNumber,
+                format!(
+                    "Malformed number literal {} at column {}",
+                    text, self.token_column
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Scan the digits of a `0x` or `0b` prefixed literal
+    fn radix_number(&mut self, radix: u32) -> ScannerResult {
+        let _ = self.advance();
+        let digits_start = self.current;
+        if self.peek().is_digit(radix) {
+            self.digits(radix)?;
+        }
+        let text = self.source[self.start..self.current]
+            .iter()
+            .collect::<String>();
+        if self.current == digits_start {
+            return Err(ScannerError::new(
+                ScannerErrorKind::MalformedNumber,
+                format!(
+                    "Malformed number literal {} at column {}",
+                    text, self.token_column
+                ),
+            ));
+        }
+        // every digit is valid by now, so this only fails
+        // when there are more than fit in a u64
+        let value = u64::from_str_radix(&text[2..].replace('_', ""), radix).map_err(|_| {
+            ScannerError::new(
+                ScannerErrorKind::NumberOutOfRange,
+                format!(
+                    "numeric literal out of range {} at column {}",
+                    text, self.token_column
+                ),
+            )
+        })?;
+        Ok(self.add_literal(TokenType::Number(value as f64)))
+    }
+
     pub fn identifier(&mut self) -> ScannerResult {
-        while self.peek().is_alphanumeric() {
+        while self.peek().is_alphanumeric() || self.peek() == '_' {
             let _ = self.advance();
         }
         let text = self.source[self.start..self.current]
@@ -250,7 +518,7 @@ impl Scanner {
         if self.current + 1 >= self.source.len() {
             '\0'
         } else {
-            self.source[self.current]
+            self.source[self.current + 1]
         }
     }
 }
@@ -259,10 +527,336 @@ impl Iterator for Scanner {
     type Item = ScannerResult;
     fn next(&mut self) -> Option<Self::Item> {
         self.start = self.current;
-        if self.is_at_end() {
+        if self.done() {
             None
         } else {
             self.scan_token().transpose()
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn token_columns() {
+        let mut scanner = Scanner::new("var answer = 42;\n  print answer;\n".to_string()).unwrap();
+        let tokens = scanner.scan_tokens().unwrap();
+        let columns: Vec<(usize, usize)> = tokens.iter().map(|t| (t.line, t.column)).collect();
+        assert_eq!(
+            columns,
+            vec![
+                (1, 1),
+                (1, 5),
+                (1, 12),
+                (1, 14),
+                (1, 16),
+                (2, 3),
+                (2, 9),
+                (2, 15)
+            ]
+        );
+    }
+
+    #[test]
+    fn out_of_range_number() {
+        let digits = "9".repeat(400);
+        let err = Scanner::new(format!("var a = 1;\nvar big = {};", digits))
+            .unwrap()
+            .scan_tokens()
+            .unwrap_err();
+        assert_eq!(err.kind, ScannerErrorKind::NumberOutOfRange);
+        assert_eq!(
+            err.to_string(),
+            format!("numeric literal out of range {} at column 11", digits)
+        );
+        let tokens = Scanner::new("1e308 1e-400".to_string())
+            .unwrap()
+            .scan_tokens()
+            .unwrap();
+        assert_eq!(tokens[0].kind, TokenType::Number(1e308));
+        assert_eq!(tokens[1].kind, TokenType::Number(0.0));
+        assert!(Scanner::new("1e309".to_string()).is_err());
+    }
+
+    #[test]
+    fn bitwise_operators() {
+        let tokens = Scanner::new("a << b >> c <= d & e | f ^ g".to_string())
+            .unwrap()
+            .scan_tokens()
+            .unwrap();
+        let ops: Vec<TokenType> = tokens
+            .into_iter()
+            .map(|t| t.kind)
+            .filter(|k| !matches!(k, TokenType::Identifier(_)))
+            .collect();
+        assert_eq!(
+            ops,
+            vec![
+                TokenType::LessLess,
+                TokenType::GreaterGreater,
+                TokenType::LessEqual,
+                TokenType::Ampersand,
+                TokenType::Pipe,
+                TokenType::Caret,
+            ]
+        );
+    }
+
+    #[test]
+    fn from_reader() {
+        let lox = "var s = \"héllo ${1 + 2}\";\nprint s;\n";
+        let tokens = Scanner::from_reader(std::io::Cursor::new(lox))
+            .unwrap()
+            .scan_tokens()
+            .unwrap();
+        let expected = Scanner::new(lox.to_string())
+            .unwrap()
+            .scan_tokens()
+            .unwrap();
+        assert_eq!(tokens, expected);
+        let err = Scanner::from_reader(std::io::Cursor::new(vec![0xff, 0xfe])).err();
+        assert_eq!(err.unwrap().kind, ScannerErrorKind::Io);
+    }
+
+    #[test]
+    fn shebang() {
+        let tokens = Scanner::new("#!/usr/bin/env rox\nprint 1;".to_string())
+            .unwrap()
+            .scan_tokens()
+            .unwrap();
+        let kinds: Vec<&TokenType> = tokens.iter().map(|t| &t.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                &TokenType::Print,
+                &TokenType::Number(1.0),
+                &TokenType::Semicolon
+            ]
+        );
+        assert_eq!((tokens[0].line, tokens[0].column), (2, 1));
+        assert!(Scanner::new("#!only a shebang".to_string()).unwrap().done());
+        assert!(Scanner::new(" #!/usr/bin/env rox".to_string()).is_err());
+        assert!(Scanner::new("#/usr/bin/env rox".to_string()).is_err());
+    }
+
+    #[test]
+    fn no_trailing_whitespace() {
+        let mut scanner = Scanner::new("print 1;".to_string()).unwrap();
+        let tokens = scanner.scan_tokens().unwrap();
+        assert_eq!(tokens.len(), 3);
+        assert!(scanner.done());
+        assert_eq!(
+            scanner.lookahead().as_ref().map(|t| t.kind.clone()),
+            Some(TokenType::Eof)
+        );
+    }
+
+    #[test]
+    fn bad_char_column() {
+        let mut scanner = Scanner::new("var a = 1;\nvar b = 2 # 3;\n".to_string()).unwrap();
+        let err = scanner.scan_tokens().unwrap_err();
+        assert_eq!(scanner.line, 2);
+        assert_eq!(scanner.token_column(), 11);
+        assert_eq!(err.kind, ScannerErrorKind::UnknownToken);
+        assert!(err.to_string().contains("column 11"), "{}", err);
+    }
+
+    #[test]
+    fn tokens_after_newlines() {
+        let mut scanner = Scanner::new("a\n  1 // note\nb".to_string()).unwrap();
+        let tokens = scanner.scan_tokens().unwrap();
+        let found: Vec<(&str, (usize, usize))> =
+            tokens.iter().map(|t| (t.lexeme.as_str(), t.span)).collect();
+        assert_eq!(found, vec![("a", (0, 1)), ("1", (4, 5)), ("b", (14, 15))]);
+    }
+
+    #[test]
+    fn radix_numbers() {
+        assert_eq!(
+            kinds("0xFF 0b1010 0x0 0987.321 0"),
+            vec![
+                TokenType::Number(255.0),
+                TokenType::Number(10.0),
+                TokenType::Number(0.0),
+                TokenType::Number(987.321),
+                TokenType::Number(0.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_radix_number() {
+        let mut scanner = Scanner::new("var a = 0x;\n".to_string()).unwrap();
+        let err = scanner.scan_tokens().unwrap_err();
+        assert_eq!(err.kind, ScannerErrorKind::MalformedNumber);
+        assert!(
+            err.to_string().starts_with("Malformed number literal 0x"),
+            "{}",
+            err
+        );
+        let mut scanner = Scanner::new("var a = 0b2;\n".to_string()).unwrap();
+        assert!(scanner.scan_tokens().is_err());
+    }
+
+    #[test]
+    fn out_of_range_radix_number() {
+        assert_eq!(
+            kinds("0xFFFF_FFFF_FFFF_FFFF"),
+            vec![TokenType::Number(u64::MAX as f64)]
+        );
+        let binary = format!("0b1{}", "0".repeat(64));
+        for lox in &["0x1_0000_0000_0000_0000", binary.as_str()] {
+            let err = Scanner::new(format!("var a = {};\n", lox))
+                .and_then(|mut scanner| scanner.scan_tokens())
+                .unwrap_err();
+            assert_eq!(err.kind, ScannerErrorKind::NumberOutOfRange, "{}", lox);
+            assert_eq!(
+                err.to_string(),
+                format!("numeric literal out of range {} at column 9", lox)
+            );
+        }
+    }
+
+    #[test]
+    fn compound_assignment() {
+        assert_eq!(
+            kinds("+= -= *= /= + - * / // comment"),
+            vec![
+                TokenType::PlusEqual,
+                TokenType::MinusEqual,
+                TokenType::StarEqual,
+                TokenType::SlashEqual,
+                TokenType::Plus,
+                TokenType::Minus,
+                TokenType::Star,
+                TokenType::Slash,
+            ]
+        );
+    }
+
+    #[test]
+    fn increment_decrement() {
+        assert_eq!(
+            kinds("i++ i-- - -1 +++"),
+            vec![
+                TokenType::Identifier("i".to_string()),
+                TokenType::PlusPlus,
+                TokenType::Identifier("i".to_string()),
+                TokenType::MinusMinus,
+                TokenType::Minus,
+                TokenType::Minus,
+                TokenType::Number(1.0),
+                TokenType::PlusPlus,
+                TokenType::Plus,
+            ]
+        );
+    }
+
+    #[test]
+    fn exponent_numbers() {
+        assert_eq!(
+            kinds("1e3 1.5E+2 2.5e-3"),
+            vec![
+                TokenType::Number(1000.0),
+                TokenType::Number(150.0),
+                TokenType::Number(0.0025),
+            ]
+        );
+    }
+
+    #[test]
+    fn digit_separators() {
+        assert_eq!(
+            kinds("1_000 0xFF_FF 1_0.2_5 1e1_0"),
+            vec![
+                TokenType::Number(1000.0),
+                TokenType::Number(65535.0),
+                TokenType::Number(10.25),
+                TokenType::Number(1e10),
+            ]
+        );
+        for lox in &["1_", "1__0", "1_.5", "0x_1", "1e_1", "1_e1"] {
+            let err = Scanner::new(format!("{}\n", lox))
+                .and_then(|mut scanner| scanner.scan_tokens())
+                .unwrap_err();
+            assert!(
+                err.kind == ScannerErrorKind::MalformedNumber,
+                "{}: {}",
+                lox,
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn malformed_exponent() {
+        let mut scanner = Scanner::new("var a = 1e;\n".to_string()).unwrap();
+        let err = scanner.scan_tokens().unwrap_err();
+        assert!(
+            err.to_string().starts_with("Malformed number literal 1e"),
+            "{}",
+            err
+        );
+        let mut scanner = Scanner::new("var a = 1e+;\n".to_string()).unwrap();
+        assert!(scanner.scan_tokens().is_err());
+    }
+
+    fn kinds(lox: &str) -> Vec<TokenType> {
+        let mut scanner = Scanner::new(format!("{}\n", lox)).unwrap();
+        scanner
+            .scan_tokens()
+            .unwrap()
+            .into_iter()
+            .map(|t| t.kind)
+            .collect()
+    }
+
+    #[test]
+    fn interpolation() {
+        assert_eq!(
+            kinds(r#""total: ${a + b}!""#),
+            vec![
+                TokenType::Interpolation("total: ".to_string()),
+                TokenType::Identifier("a".to_string()),
+                TokenType::Plus,
+                TokenType::Identifier("b".to_string()),
+                TokenType::String("!".to_string()),
+            ]
+        );
+        assert_eq!(
+            kinds(r#""${a} and ${b}""#),
+            vec![
+                TokenType::Interpolation(String::new()),
+                TokenType::Identifier("a".to_string()),
+                TokenType::Interpolation(" and ".to_string()),
+                TokenType::Identifier("b".to_string()),
+                TokenType::String(String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn interpolation_nested_braces() {
+        assert_eq!(
+            kinds(r#""a ${ { } } b""#),
+            vec![
+                TokenType::Interpolation("a ".to_string()),
+                TokenType::LeftBrace,
+                TokenType::RightBrace,
+                TokenType::String(" b".to_string()),
+            ]
+        );
+        assert_eq!(
+            kinds(r#""a ${"b ${c}"} d""#),
+            vec![
+                TokenType::Interpolation("a ".to_string()),
+                TokenType::Interpolation("b ".to_string()),
+                TokenType::Identifier("c".to_string()),
+                TokenType::String(String::new()),
+                TokenType::String(" d".to_string()),
+            ]
+        );
+    }
+}
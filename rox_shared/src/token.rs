@@ -1,17 +1,26 @@
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     pub kind: TokenType,
     pub lexeme: String,
-    line: usize,
+    pub line: usize,
+    pub column: usize,
     pub span: (usize, usize),
 }
 
 impl Token {
-    pub fn new(kind: TokenType, lexeme: String, line: usize, start: usize, end: usize) -> Self {
+    pub fn new(
+        kind: TokenType,
+        lexeme: String,
+        line: usize,
+        column: usize,
+        start: usize,
+        end: usize,
+    ) -> Self {
         Self {
             kind,
             lexeme,
             line,
+            column,
             span: (start, end),
         }
     }
@@ -25,11 +34,21 @@ pub enum TokenType {
     RightBrace,
     Comma,
     Dot,
+    /// `?.`, a property access that is skipped on `nil`
+    QuestionDot,
     Minus,
     Plus,
     Semicolon,
     Slash,
     Star,
+    PlusEqual,
+    MinusEqual,
+    StarEqual,
+    SlashEqual,
+    /// `++`, a postfix increment
+    PlusPlus,
+    /// `--`, a postfix decrement
+    MinusMinus,
 
     Bang,
     BangEqual,
@@ -39,9 +58,16 @@ pub enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    Ampersand,
+    Pipe,
+    Caret,
+    LessLess,
+    GreaterGreater,
 
     Identifier(String),
     String(String),
+    /// The part of a string literal leading up to a `${`
+    Interpolation(String),
     Number(f64),
 
     And,
@@ -63,3 +89,63 @@ pub enum TokenType {
 
     Eof,
 }
+
+impl ::std::fmt::Display for TokenType {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        let s = match self {
+            TokenType::LeftParen => "'('",
+            TokenType::RightParen => "')'",
+            TokenType::LeftBrace => "'{'",
+            TokenType::RightBrace => "'}'",
+            TokenType::Comma => "','",
+            TokenType::Dot => "'.'",
+            TokenType::QuestionDot => "'?.'",
+            TokenType::Minus => "'-'",
+            TokenType::Plus => "'+'",
+            TokenType::Semicolon => "';'",
+            TokenType::Slash => "'/'",
+            TokenType::Star => "'*'",
+            TokenType::PlusEqual => "'+='",
+            TokenType::MinusEqual => "'-='",
+            TokenType::StarEqual => "'*='",
+            TokenType::SlashEqual => "'/='",
+            TokenType::PlusPlus => "'++'",
+            TokenType::MinusMinus => "'--'",
+            TokenType::Bang => "'!'",
+            TokenType::BangEqual => "'!='",
+            TokenType::Equal => "'='",
+            TokenType::EqualEqual => "'=='",
+            TokenType::Greater => "'>'",
+            TokenType::GreaterEqual => "'>='",
+            TokenType::Less => "'<'",
+            TokenType::LessEqual => "'<='",
+            TokenType::Ampersand => "'&'",
+            TokenType::Pipe => "'|'",
+            TokenType::Caret => "'^'",
+            TokenType::LessLess => "'<<'",
+            TokenType::GreaterGreater => "'>>'",
+            TokenType::Identifier(_) => "identifier",
+            TokenType::String(_) => "string",
+            TokenType::Interpolation(_) => "interpolated string",
+            TokenType::Number(_) => "number",
+            TokenType::And => "'and'",
+            TokenType::Class => "'class'",
+            TokenType::Else => "'else'",
+            TokenType::False => "'false'",
+            TokenType::Fun => "'fun'",
+            TokenType::For => "'for'",
+            TokenType::If => "'if'",
+            TokenType::Nil => "'nil'",
+            TokenType::Or => "'or'",
+            TokenType::Print => "'print'",
+            TokenType::Return => "'return'",
+            TokenType::Super => "'super'",
+            TokenType::This => "'this'",
+            TokenType::True => "'true'",
+            TokenType::Var => "'var'",
+            TokenType::While => "'while'",
+            TokenType::Eof => "end of input",
+        };
+        f.write_str(s)
+    }
+}
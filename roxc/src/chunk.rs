@@ -1,12 +1,21 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    hash::BuildHasherDefault,
+};
+use hashers::fnv::FNV1aHasher64;
 use crate::op::OpCode;
 use crate::value::Value;
-use crate::Obj;
-#[derive(Default, Debug)]
+use crate::{Error, Obj, Result};
+#[derive(Default, Debug, PartialEq)]
 pub struct Chunk<'a> {
     pub code: Vec<OpCode>,
     pub values: Vec<Value>,
     pub heap: Vec<Obj<'a>>,
     pub lines: RunList,
+    /// The heap index of every string, keyed by its
+    /// contents so each string is only stored once
+    strings: HashMap<Cow<'a, str>, usize, BuildHasherDefault<FNV1aHasher64>>,
 }
 
 impl<'a> Chunk<'a> {
@@ -14,16 +23,121 @@ impl<'a> Chunk<'a> {
         self.code.push(byte);
         self.lines.push(line);
     }
+    /// Add a constant, reusing the index of an equal
+    /// constant if one is already in the pool
     pub fn add_constant(&mut self, value: Value) -> usize {
+        // numbers are matched by their bits, `==` would
+        // hand -0.0 the slot of an earlier 0.0
+        let same = |v: &Value| match (v, &value) {
+            (Value::Number(lhs), Value::Number(rhs)) => lhs.to_bits() == rhs.to_bits(),
+            _ => *v == value,
+        };
+        if let Some(idx) = self.values.iter().position(same) {
+            return idx;
+        }
         self.values.push(value);
         self.values.len() - 1
     }
     
+    /// Add an object to the heap, strings are interned so
+    /// an equal string reuses the index already on the heap.
+    /// Tables are mutable so each one always gets its own slot
     pub fn add_obj(&mut self, obj: Obj<'a>) -> usize {
+        if let Obj::String(s) = &obj {
+            if let Some(idx) = self.strings.get(s) {
+                return *idx;
+            }
+            self.strings.insert(s.clone(), self.heap.len());
+        }
         self.heap.push(obj);
         self.heap.len() - 1
     }
 
+    /// The heap index of the interned string `s`, if
+    /// it has been added
+    pub fn interned(&self, s: &str) -> Option<usize> {
+        self.strings.get(s).copied()
+    }
+
+    /// Drop any constants and heap objects that no
+    /// instruction can reach, remapping the indices in
+    /// `code` and `values` to match
+    pub fn prune_unused(&mut self) {
+        let mut value_map = vec![None; self.values.len()];
+        let mut values = Vec::new();
+        for op in &mut self.code {
+            if let OpCode::Constant { idx }
+            | OpCode::DefineGlobal { idx }
+            | OpCode::GetGlobal { idx }
+            | OpCode::SetGlobal { idx } = op
+            {
+                let new_idx = match value_map[*idx] {
+                    Some(new_idx) => new_idx,
+                    None => {
+                        values.push(self.values[*idx]);
+                        value_map[*idx] = Some(values.len() - 1);
+                        values.len() - 1
+                    }
+                };
+                *idx = new_idx;
+            }
+        }
+        let mut heap_map = vec![None; self.heap.len()];
+        let mut pending: Vec<usize> = values.iter().filter_map(Self::obj_idx).collect();
+        let mut kept = Vec::new();
+        while let Some(idx) = pending.pop() {
+            if heap_map[idx].is_some() {
+                continue;
+            }
+            heap_map[idx] = Some(kept.len());
+            kept.push(idx);
+            if let Obj::HashTable { entries } = &self.heap[idx] {
+                pending.extend(entries.values().filter_map(Self::obj_idx));
+            }
+        }
+        kept.sort_unstable();
+        for (new_idx, old_idx) in kept.iter().enumerate() {
+            heap_map[*old_idx] = Some(new_idx);
+        }
+        let mut heap = Vec::with_capacity(kept.len());
+        for old_idx in kept {
+            let mut obj = std::mem::replace(&mut self.heap[old_idx], Obj::string(""));
+            if let Obj::HashTable { entries } = &mut obj {
+                for value in entries.values_mut() {
+                    Self::remap_obj(value, &heap_map);
+                }
+            }
+            heap.push(obj);
+        }
+        for value in &mut values {
+            Self::remap_obj(value, &heap_map);
+        }
+        self.strings.clear();
+        for (idx, obj) in heap.iter().enumerate() {
+            if let Obj::String(s) = obj {
+                self.strings.insert(s.clone(), idx);
+            }
+        }
+        self.values = values;
+        self.heap = heap;
+    }
+
+    fn obj_idx(value: &Value) -> Option<usize> {
+        if let Value::Obj { idx } = value {
+            Some(*idx)
+        } else {
+            None
+        }
+    }
+
+    fn remap_obj(value: &mut Value, heap_map: &[Option<usize>]) {
+        if let Value::Obj { idx } = value {
+            if let Some(new_idx) = heap_map[*idx] {
+                *idx = new_idx;
+            }
+        }
+    }
+
     pub fn print_obj(&self, idx: usize) {
         match &self.heap[idx] {
             Obj::String(s) => {
@@ -35,11 +149,89 @@ impl<'a> Chunk<'a> {
         }
     }
 
-    pub fn dissassemble_all(&self, name: &str) {
-        println!("== {} ==", name);
-        for (i, code) in self.code.iter().enumerate() {
-            self.dissassemble_inst(i, code)
+    /// Walk the instructions in this chunk as
+    /// `(offset, line, opcode)` without printing them
+    pub fn instructions(&self) -> impl Iterator<Item = (usize, usize, &OpCode)> + '_ {
+        self.code
+            .iter()
+            .enumerate()
+            .map(move |(i, code)| (i, self.lines.get_unchecked(i), code))
+    }
+
+    /// Encode this chunk in the `.rox` bytecode format, the
+    /// magic bytes and version followed by the code, values,
+    /// heap and lines. Every number is little endian and every
+    /// index or length is a `u64`
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        write_len(&mut out, self.code.len());
+        for op in &self.code {
+            let (tag, operand) = match op {
+                OpCode::Constant { idx } => (0, Some(*idx)),
+                OpCode::Return => (1, None),
+                OpCode::Negate => (2, None),
+                OpCode::Add => (3, None),
+                OpCode::Sub => (4, None),
+                OpCode::Mul => (5, None),
+                OpCode::Div => (6, None),
+                OpCode::True => (7, None),
+                OpCode::False => (8, None),
+                OpCode::Nil => (9, None),
+                OpCode::Not => (10, None),
+                OpCode::Eq => (11, None),
+                OpCode::Gtr => (12, None),
+                OpCode::Less => (13, None),
+                OpCode::Table => (14, None),
+                OpCode::TableEntry => (15, None),
+                OpCode::GetIndex => (16, None),
+                OpCode::SetIndex => (17, None),
+                OpCode::Pop => (18, None),
+                OpCode::Jump { offset } => (19, Some(*offset)),
+                OpCode::JumpIfFalse { offset } => (20, Some(*offset)),
+                OpCode::Loop { offset } => (21, Some(*offset)),
+                OpCode::Print => (22, None),
+                OpCode::DefineGlobal { idx } => (23, Some(*idx)),
+                OpCode::GetGlobal { idx } => (24, Some(*idx)),
+                OpCode::SetGlobal { idx } => (25, Some(*idx)),
+            };
+            out.push(tag);
+            if let Some(operand) = operand {
+                write_len(&mut out, operand);
+            }
+        }
+        write_len(&mut out, self.values.len());
+        for value in &self.values {
+            write_value(&mut out, value);
+        }
+        write_len(&mut out, self.heap.len());
+        for obj in &self.heap {
+            match obj {
+                Obj::String(s) => {
+                    out.push(0);
+                    write_str(&mut out, s);
+                }
+                Obj::HashTable { entries } => {
+                    out.push(1);
+                    write_len(&mut out, entries.len());
+                    for (key, value) in entries {
+                        write_str(&mut out, key);
+                        write_value(&mut out, value);
+                    }
+                }
+            }
+        }
+        write_len(&mut out, self.lines.values.len());
+        for run in &self.lines.values {
+            write_len(&mut out, run.value);
+            write_len(&mut out, run.len);
         }
+        out
+    }
+
+    pub fn dissassemble_all(&self, name: &str) {
+        print!("{}", self.disassemble(name));
     }
 
     pub fn dissassemble_inst_idx(&self, i: usize) {
@@ -47,38 +239,298 @@ impl<'a> Chunk<'a> {
     }
 
     pub fn dissassemble_inst(&self, i: usize, code: &OpCode) {
-        print!("{:04} ", i);
-        self.dissassemblly_line(i);
+        print!("{}", self.disassemble_inst(i, code));
+    }
+
+    /// Render the full listing for this chunk, one
+    /// instruction per line
+    pub fn disassemble(&self, name: &str) -> String {
+        let mut ret = format!("== {} ==\n", name);
+        for (i, code) in self.code.iter().enumerate() {
+            ret.push_str(&self.disassemble_inst(i, code));
+        }
+        ret
+    }
+
+    fn disassemble_inst(&self, i: usize, code: &OpCode) -> String {
+        let mut ret = format!("{:04} ", i);
+        ret.push_str(&self.dissassemblly_line(i));
         match code {
             OpCode::Constant { idx } => {
-                println!("{: <16} {:?}", format!("{:}", code), &self.values[*idx])
+                ret.push_str(&format!("{: <16} {:?}\n", format!("{:}", code), &self.values[*idx]))
+            }
+            OpCode::DefineGlobal { idx } | OpCode::GetGlobal { idx } | OpCode::SetGlobal { idx } => {
+                let name = match self.values[*idx] {
+                    Value::Obj { idx } => match &self.heap[idx] {
+                        Obj::String(s) => s.to_string(),
+                        _ => String::new(),
+                    },
+                    _ => String::new(),
+                };
+                ret.push_str(&format!("{: <16} {}\n", format!("{:}", code), name))
+            }
+            OpCode::Jump { offset } | OpCode::JumpIfFalse { offset } => {
+                ret.push_str(&format!("{: <16} {:04} -> {:04}\n", format!("{:}", code), i, i + 1 + offset))
+            }
+            OpCode::Loop { offset } => {
+                ret.push_str(&format!("{: <16} {:04} -> {:04}\n", format!("{:}", code), i, (i + 1).wrapping_sub(*offset)))
             }
-            _ => println!("{}", code),
+            _ => ret.push_str(&format!("{}\n", code)),
         }
+        ret
     }
 
-    fn dissassemblly_line(&self, idx: usize) {
+    fn dissassemblly_line(&self, idx: usize) -> String {
         if idx == 0 {
-            print!("{:04} ", self.lines.get_unchecked(0));
+            format!("{:04} ", self.lines.get_unchecked(0))
         } else {
             let prev = self.lines.get_unchecked(idx - 1);
             let curr = self.lines.get_unchecked(idx);
             if prev == curr {
-                print!("   | ");
+                "   | ".to_string()
             } else {
-                print!("{:04}", curr);
+                format!("{:04} ", curr)
+            }
+        }
+    }
+}
+
+impl Chunk<'static> {
+    /// Decode a chunk written by `Chunk::serialize`, bytes
+    /// from a different version of the format, or that
+    /// don't describe a valid chunk, are an `Error::Runtime`
+    pub fn deserialize(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader { bytes, pos: 0 };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(Error::Runtime("not a rox bytecode file".to_string()));
+        }
+        let version = reader.u8()?;
+        if version != VERSION {
+            return Err(Error::Runtime(format!(
+                "unsupported bytecode version {}, expected {}",
+                version, VERSION
+            )));
+        }
+        let mut chunk = Chunk::default();
+        for _ in 0..reader.len()? {
+            let op = match reader.u8()? {
+                0 => OpCode::Constant { idx: reader.len()? },
+                1 => OpCode::Return,
+                2 => OpCode::Negate,
+                3 => OpCode::Add,
+                4 => OpCode::Sub,
+                5 => OpCode::Mul,
+                6 => OpCode::Div,
+                7 => OpCode::True,
+                8 => OpCode::False,
+                9 => OpCode::Nil,
+                10 => OpCode::Not,
+                11 => OpCode::Eq,
+                12 => OpCode::Gtr,
+                13 => OpCode::Less,
+                14 => OpCode::Table,
+                15 => OpCode::TableEntry,
+                16 => OpCode::GetIndex,
+                17 => OpCode::SetIndex,
+                18 => OpCode::Pop,
+                19 => OpCode::Jump { offset: reader.len()? },
+                20 => OpCode::JumpIfFalse { offset: reader.len()? },
+                21 => OpCode::Loop { offset: reader.len()? },
+                22 => OpCode::Print,
+                23 => OpCode::DefineGlobal { idx: reader.len()? },
+                24 => OpCode::GetGlobal { idx: reader.len()? },
+                25 => OpCode::SetGlobal { idx: reader.len()? },
+                tag => return Err(reader.error(&format!("unknown opcode {}", tag))),
+            };
+            chunk.code.push(op);
+        }
+        for _ in 0..reader.len()? {
+            let value = reader.value()?;
+            chunk.values.push(value);
+        }
+        for _ in 0..reader.len()? {
+            let obj = match reader.u8()? {
+                0 => Obj::String(Cow::Owned(reader.string()?)),
+                1 => {
+                    let mut entries = crate::value::HashTable::default();
+                    for _ in 0..reader.len()? {
+                        let key = reader.string()?;
+                        let value = reader.value()?;
+                        entries.insert(Cow::Owned(key), value);
+                    }
+                    Obj::hash_table(entries)
+                }
+                tag => return Err(reader.error(&format!("unknown object {}", tag))),
+            };
+            if let Obj::String(s) = &obj {
+                // strings compare by heap index, so a second
+                // copy would never equal the first
+                if chunk.strings.insert(s.clone(), chunk.heap.len()).is_some() {
+                    return Err(reader.error(&format!("duplicate string {:?}", s)));
+                }
+            }
+            chunk.heap.push(obj);
+        }
+        let mut lines: usize = 0;
+        for _ in 0..reader.len()? {
+            let value = reader.len()?;
+            let len = reader.len()?;
+            lines = match lines.checked_add(len) {
+                Some(lines) => lines,
+                None => return Err(reader.error("too many lines")),
+            };
+            chunk.lines.values.push(RunLength { value, len });
+        }
+        if reader.pos != bytes.len() {
+            return Err(reader.error("trailing bytes"));
+        }
+        if lines != chunk.code.len() {
+            return Err(reader.error("line count doesn't match the code"));
+        }
+        chunk.validate()?;
+        Ok(chunk)
+    }
+}
+
+impl<'a> Chunk<'a> {
+    /// Make sure every index in the chunk points at
+    /// something, so a loaded chunk can't panic the vm
+    fn validate(&self) -> Result<()> {
+        let invalid = |msg: String| Err(Error::Runtime(format!("invalid bytecode: {}", msg)));
+        for (i, op) in self.code.iter().enumerate() {
+            match op {
+                OpCode::Constant { idx }
+                | OpCode::DefineGlobal { idx }
+                | OpCode::GetGlobal { idx }
+                | OpCode::SetGlobal { idx }
+                    if *idx >= self.values.len() =>
+                {
+                    return invalid(format!("constant {} at {} is out of range", idx, i));
+                }
+                OpCode::Jump { offset } | OpCode::JumpIfFalse { offset }
+                    if (i + 1)
+                        .checked_add(*offset)
+                        .is_none_or(|target| target > self.code.len()) =>
+                {
+                    return invalid(format!("jump at {} is out of range", i));
+                }
+                OpCode::Loop { offset } if *offset > i + 1 => {
+                    return invalid(format!("loop at {} is out of range", i));
+                }
+                _ => {}
+            }
+        }
+        let tables = self.heap.iter().flat_map(|obj| match obj {
+            Obj::HashTable { entries } => Some(entries.values()),
+            Obj::String(_) => None,
+        });
+        for value in self.values.iter().chain(tables.flatten()) {
+            if let Value::Obj { idx } = value {
+                if *idx >= self.heap.len() {
+                    return invalid(format!("object {} is out of range", idx));
+                }
             }
         }
+        Ok(())
+    }
+}
+
+/// The first bytes of every serialized chunk
+const MAGIC: &[u8] = b"rox\0";
+/// Bumped whenever the serialized layout changes
+const VERSION: u8 = 1;
+
+fn write_len(out: &mut Vec<u8>, len: usize) {
+    out.extend_from_slice(&(len as u64).to_le_bytes());
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    write_len(out, s.len());
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn write_value(out: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Number(n) => {
+            out.push(0);
+            out.extend_from_slice(&n.to_le_bytes());
+        }
+        Value::Boolean(b) => {
+            out.push(1);
+            out.push(*b as u8);
+        }
+        Value::Nil => out.push(2),
+        Value::Obj { idx } => {
+            out.push(3);
+            write_len(out, *idx);
+        }
+    }
+}
+
+/// A cursor over serialized bytes
+struct Reader<'b> {
+    bytes: &'b [u8],
+    pos: usize,
+}
+
+impl<'b> Reader<'b> {
+    fn error(&self, msg: &str) -> Error {
+        Error::Runtime(format!("invalid bytecode at byte {}: {}", self.pos, msg))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'b [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| self.error("unexpected end of input"))?;
+        let ret = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(ret)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    fn len(&mut self) -> Result<usize> {
+        let len = self.u64()?;
+        if len > usize::MAX as u64 {
+            return Err(self.error("length too large"));
+        }
+        Ok(len as usize)
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.len()?;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| self.error("string is not utf-8"))
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        Ok(match self.u8()? {
+            0 => Value::Number(f64::from_bits(self.u64()?)),
+            1 => Value::Boolean(self.u8()? != 0),
+            2 => Value::Nil,
+            3 => Value::Obj { idx: self.len()? },
+            tag => return Err(self.error(&format!("unknown value {}", tag))),
+        })
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct RunLength {
     value: usize,
     len: usize,
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, PartialEq)]
 pub struct RunList {
     values: Vec<RunLength>,
 }
@@ -94,6 +546,20 @@ impl RunList {
         }
         self.values.push(RunLength { value: i, len: 1 })
     }
+    /// Drop everything past the first `len` entries
+    #[tracing::instrument()]
+    pub fn truncate(&mut self, mut len: usize) {
+        let mut keep = 0;
+        for value in &mut self.values {
+            if len == 0 {
+                break;
+            }
+            keep += 1;
+            value.len = value.len.min(len);
+            len -= value.len;
+        }
+        self.values.truncate(keep);
+    }
     #[tracing::instrument()]
     pub fn get_unchecked(&self, idx: usize) -> usize {
         let mut ct = 0;
@@ -121,5 +587,176 @@ mod test {
         for (i, v) in values.iter().enumerate() {
             assert_eq!(*v, rl.get_unchecked(i));
         }
+        rl.truncate(4);
+        rl.push(8);
+        for (i, v) in [1, 2, 3, 3, 8].iter().enumerate() {
+            assert_eq!(*v, rl.get_unchecked(i));
+        }
+        rl.truncate(0);
+        rl.push(9);
+        assert_eq!(rl.get_unchecked(0), 9);
+    }
+
+    #[test]
+    fn disassemble() {
+        let chunk = crate::compile("!(5 - 4 > 3 * 2 == !nil)").unwrap();
+        assert_eq!(
+            chunk.disassemble("expression"),
+            "\
+== expression ==
+0000 0001 Constant         Number(1.0)
+0001    | Constant         Number(6.0)
+0002    | Gtr
+0003    | nil
+0004    | Not
+0005    | Eq
+0006    | Not
+0007    | Return
+"
+        );
+    }
+
+    #[test]
+    fn disassemble_jumps() {
+        let chunk = crate::compile("true ? 1 : 2").unwrap();
+        assert_eq!(
+            chunk.disassemble("ternary"),
+            "\
+== ternary ==
+0000 0001 true
+0001    | JumpIfFalse      0001 -> 0005
+0002    | Pop
+0003    | Constant         Number(1.0)
+0004    | Jump             0004 -> 0007
+0005    | Pop
+0006    | Constant         Number(2.0)
+0007    | Return
+"
+        );
+    }
+
+    #[test]
+    fn disassemble_lines() {
+        let chunk = crate::compile("1\n+ -\n2 *\n3").unwrap();
+        assert_eq!(
+            chunk.disassemble("lines"),
+            "\
+== lines ==
+0000 0001 Constant         Number(1.0)
+0001 0003 Constant         Number(2.0)
+0002 0002 Negate
+0003 0004 Constant         Number(3.0)
+0004 0003 Mul
+0005 0002 Add
+0006 0004 Return
+"
+        );
+    }
+
+    #[test]
+    fn instructions() {
+        let chunk = crate::compile("1 +\ntrue").unwrap();
+        assert_eq!(
+            chunk.instructions().collect::<Vec<_>>(),
+            vec![
+                (0, 1, &OpCode::Constant { idx: 0 }),
+                (1, 2, &OpCode::True),
+                (2, 1, &OpCode::Add),
+                (3, 2, &OpCode::Return),
+            ]
+        );
+    }
+
+    #[test]
+    fn serialize_round_trip() {
+        let lox = "{\"a\": -1 + -2}[\"a\"] == -3\n  ? \"yes\"\n  : \"no\"";
+        let chunk = crate::compile(lox).unwrap();
+        let bytes = chunk.serialize();
+        let loaded = Chunk::deserialize(&bytes).unwrap();
+        assert_eq!(loaded, chunk);
+        assert_eq!(loaded.disassemble("loaded"), chunk.disassemble("loaded"));
+        crate::VM::default().interpret_chunk(loaded).unwrap();
+    }
+
+    #[test]
+    fn deserialize_errors() {
+        let mut bytes = crate::compile("1 + 2").unwrap().serialize();
+        let good = bytes.clone();
+        bytes[MAGIC.len()] = VERSION + 1;
+        match Chunk::deserialize(&bytes) {
+            Err(Error::Runtime(msg)) => assert_eq!(msg, format!(
+                "unsupported bytecode version {}, expected {}",
+                VERSION + 1, VERSION
+            )),
+            other => panic!("expected a version error, found {:?}", other),
+        }
+        assert!(Chunk::deserialize(&good[..good.len() - 1]).is_err());
+        assert!(Chunk::deserialize(b"lox\0").is_err());
+        // point the constant past the end of the pool
+        let mut bytes = good;
+        let idx = MAGIC.len() + 1 + 8 + 1;
+        bytes[idx] = 9;
+        assert!(Chunk::deserialize(&bytes).is_err());
+        // a loop can't jump back before the first instruction
+        let mut chunk = Chunk::default();
+        chunk.write(OpCode::Nil, 1);
+        chunk.write(OpCode::Loop { offset: 3 }, 1);
+        assert!(Chunk::deserialize(&chunk.serialize()).is_err());
+        chunk.code[1] = OpCode::Loop { offset: 2 };
+        assert!(Chunk::deserialize(&chunk.serialize()).is_ok());
+        // a jump so far it wraps around
+        chunk.code[1] = OpCode::Jump { offset: usize::MAX };
+        assert!(Chunk::deserialize(&chunk.serialize()).is_err());
+        // strings are interned, so each may only appear once
+        let mut chunk = Chunk::default();
+        chunk.heap.push(Obj::string("x"));
+        chunk.heap.push(Obj::string("x"));
+        assert!(Chunk::deserialize(&chunk.serialize()).is_err());
+    }
+
+    #[test]
+    fn dedup_constants() {
+        let chunk = crate::compiler::Compiler::new("1 + 1").compile().unwrap();
+        assert_eq!(chunk.values.len(), 1);
+        // negating keeps the operands from being folded
+        let chunk = crate::compiler::Compiler::new("-1 + -1").compile().unwrap();
+        assert_eq!(chunk.values, vec![Value::Number(1.0)]);
+        let chunk = crate::compiler::Compiler::new(r#""x" + "x""#).compile().unwrap();
+        assert_eq!(chunk.values, vec![Value::Obj { idx: 0 }]);
+        assert_eq!(chunk.heap, vec![Obj::string("x")]);
+        let mut chunk = Chunk::default();
+        assert_eq!(chunk.add_constant(Value::Number(0.0)), 0);
+        assert_eq!(chunk.add_constant(Value::Number(-0.0)), 1);
+        assert_eq!(chunk.add_constant(Value::Number(-0.0)), 1);
+        let mut chunk = Chunk::default();
+        let first = chunk.add_obj(Obj::hash_table(Default::default()));
+        let second = chunk.add_obj(Obj::hash_table(Default::default()));
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn prune_unused() {
+        let mut chunk = Chunk::default();
+        let one = chunk.add_constant(Value::Number(1.0));
+        let _unused = chunk.add_constant(Value::Number(2.0));
+        let _unused_obj = chunk.add_obj(Obj::string("unused"));
+        let kept_obj = chunk.add_obj(Obj::string("kept"));
+        let kept = chunk.add_constant(Value::Obj { idx: kept_obj });
+        chunk.write(OpCode::Constant { idx: kept }, 1);
+        chunk.write(OpCode::Constant { idx: one }, 1);
+        chunk.write(OpCode::Constant { idx: kept }, 1);
+        chunk.write(OpCode::Return, 1);
+        chunk.prune_unused();
+        assert_eq!(chunk.values, vec![Value::Obj { idx: 0 }, Value::Number(1.0)]);
+        assert_eq!(chunk.heap, vec![Obj::string("kept")]);
+        assert_eq!(
+            chunk.code,
+            vec![
+                OpCode::Constant { idx: 0 },
+                OpCode::Constant { idx: 1 },
+                OpCode::Constant { idx: 0 },
+                OpCode::Return,
+            ]
+        );
     }
 }
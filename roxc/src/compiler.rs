@@ -1,14 +1,16 @@
 use crate::{
     scanner::{Scanner, ScannerError},
     scanner::{Token, TokenType},
-    Chunk, OpCode, Value, Obj,
+    Chunk, Error, OpCode, Obj, Result, Value,
 };
 use std::iter::Peekable;
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
 enum Prec {
     None,
+    Comma,
     Assignment,
+    Ternary,
     Or,
     And,
     Equality,
@@ -33,36 +35,40 @@ impl From<usize> for Prec {
         use Prec::*;
         match n {
             0 => None,
-            1 => Assignment,
-            2 => Or,
-            3 => And,
-            4 => Equality,
-            5 => Comparison,
-            6 => Term,
-            7 => Factor,
-            8 => Unary,
-            9 => Call,
-            10 => Priamary,
+            1 => Comma,
+            2 => Assignment,
+            3 => Ternary,
+            4 => Or,
+            5 => And,
+            6 => Equality,
+            7 => Comparison,
+            8 => Term,
+            9 => Factor,
+            10 => Unary,
+            11 => Call,
+            12 => Priamary,
             _ => panic!("Overflow Prec"),
         }
     }
 }
 
-impl Into<usize> for Prec {
-    fn into(self) -> usize {
+impl From<Prec> for usize {
+    fn from(prec: Prec) -> usize {
         use Prec::*;
-        match self {
+        match prec {
             None => 0,
-            Assignment => 1,
-            Or => 2,
-            And => 3,
-            Equality => 4,
-            Comparison => 5,
-            Term => 6,
-            Factor => 7,
-            Unary => 8,
-            Call => 9,
-            Priamary => 10,
+            Comma => 1,
+            Assignment => 2,
+            Ternary => 3,
+            Or => 4,
+            And => 5,
+            Equality => 6,
+            Comparison => 7,
+            Term => 8,
+            Factor => 9,
+            Unary => 10,
+            Call => 11,
+            Priamary => 12,
         }
     }
 }
@@ -77,6 +83,13 @@ pub struct Compiler<'a> {
     prev: Token<'a>,
     error: Option<ScannerError>,
     panic_mode: bool,
+    /// Set by the first error, a chunk that failed to
+    /// compile is never handed back
+    had_error: bool,
+    can_assign: bool,
+    /// The furthest instruction any jump lands on, code
+    /// before this can't be rewritten by `fold`
+    jump_target: usize,
 }
 
 impl<'a> std::fmt::Debug for Compiler<'a> {
@@ -85,6 +98,7 @@ impl<'a> std::fmt::Debug for Compiler<'a> {
             .field("prev", &self.prev)
             .field("current", &self.current)
             .field("panic_mode", &self.panic_mode)
+            .field("had_error", &self.had_error)
             .field("error", &self.error)
             .finish()
     }
@@ -93,29 +107,210 @@ impl<'a> std::fmt::Debug for Compiler<'a> {
 impl<'a> Compiler<'a> {
     #[tracing::instrument()]
     pub fn new(souce: &'a str) -> Self {
-        let scanner = Scanner::new(&souce).peekable();
+        let scanner = Scanner::new(souce).peekable();
         Self {
             scanner,
-            prev: Token::eof(0),
-            current: Token::eof(0),
+            prev: Token::eof(0, 0),
+            current: Token::eof(0, 0),
             chunk: Chunk::default(),
             error: None,
             panic_mode: false,
+            had_error: false,
+            can_assign: true,
+            jump_target: 0,
         }
     }
     #[tracing::instrument()]
-    pub fn compile(mut self) -> Chunk<'a> {
+    pub fn compile(mut self) -> Result<Chunk<'a>> {
         self.advance();
-        self.expression();
-        self.eat(TokenType::Eof);
+        while self.current.kind != TokenType::Eof {
+            self.declaration();
+        }
+        if self.had_error {
+            return Err(Error::Compiler(String::from("Unable to compile, see the errors above")));
+        }
         self.emit_return();
+        // folding leaves its operands behind in the constant table
+        self.chunk.prune_unused();
         if cfg!(feature = "debug") {
             println!("{:#?}", self.chunk);
         }
-        self.chunk
+        Ok(self.chunk)
+    }
+    #[tracing::instrument()]
+    fn declaration(&mut self) {
+        if self.current.kind == TokenType::Var {
+            self.advance();
+            self.var_declaration();
+        } else {
+            self.statement();
+        }
+        if self.panic_mode {
+            self.synchronize();
+        }
+    }
+    #[tracing::instrument()]
+    fn statement(&mut self) {
+        match self.current.kind {
+            TokenType::Print => {
+                self.advance();
+                self.print_statement();
+            }
+            TokenType::While => {
+                self.advance();
+                self.while_statement();
+            }
+            TokenType::For => {
+                self.advance();
+                self.for_statement();
+            }
+            _ => self.expression_statement(),
+        }
+    }
+    /// There are no scopes yet so every variable is a
+    /// global, one without a value starts as nil
+    #[tracing::instrument()]
+    fn var_declaration(&mut self) {
+        if !self.eat(TokenType::Ident) {
+            self.error("Expected variable name", true);
+            return;
+        }
+        let idx = self.identifier_constant();
+        if self.current.kind == TokenType::Eq {
+            self.advance();
+            self.expression();
+        } else {
+            self.emit_simple_op(OpCode::Nil, None);
+        }
+        if !self.eat(TokenType::Semi) {
+            self.error("Expected ; after variable declaration", true);
+            return;
+        }
+        self.emit_simple_op(OpCode::DefineGlobal { idx }, None);
+    }
+    /// The condition is checked before each pass through
+    /// the body and popped whichever way it went
+    #[tracing::instrument()]
+    fn while_statement(&mut self) {
+        let loop_start = self.chunk.code.len();
+        if !self.eat(TokenType::LeftParen) {
+            self.error("Expected ( after while", true);
+            return;
+        }
+        self.expression();
+        if !self.eat(TokenType::RightParen) {
+            self.error("Expected ) after condition", true);
+            return;
+        }
+        let exit_jump = self.emit_jump(OpCode::JumpIfFalse { offset: 0 });
+        self.emit_simple_op(OpCode::Pop, None);
+        self.statement();
+        self.emit_loop(loop_start);
+        self.patch_jump(exit_jump);
+        self.emit_simple_op(OpCode::Pop, None);
+    }
+    /// Desugared into the initializer followed by a `while`
+    /// loop, the increment is compiled before the body so
+    /// the body jumps over it on the way in and the end of
+    /// the body loops back to it
+    #[tracing::instrument()]
+    fn for_statement(&mut self) {
+        if !self.eat(TokenType::LeftParen) {
+            self.error("Expected ( after for", true);
+            return;
+        }
+        match self.current.kind {
+            TokenType::Semi => self.advance(),
+            TokenType::Var => {
+                self.advance();
+                self.var_declaration();
+            }
+            _ => self.expression_statement(),
+        }
+        let mut loop_start = self.chunk.code.len();
+        let mut exit_jump = None;
+        if self.current.kind == TokenType::Semi {
+            self.advance();
+        } else {
+            self.expression();
+            if !self.eat(TokenType::Semi) {
+                self.error("Expected ; after loop condition", true);
+                return;
+            }
+            exit_jump = Some(self.emit_jump(OpCode::JumpIfFalse { offset: 0 }));
+            self.emit_simple_op(OpCode::Pop, None);
+        }
+        if self.current.kind == TokenType::RightParen {
+            self.advance();
+        } else {
+            let body_jump = self.emit_jump(OpCode::Jump { offset: 0 });
+            let increment_start = self.chunk.code.len();
+            self.expression();
+            self.emit_simple_op(OpCode::Pop, None);
+            if !self.eat(TokenType::RightParen) {
+                self.error("Expected ) after for clauses", true);
+                return;
+            }
+            self.emit_loop(loop_start);
+            loop_start = increment_start;
+            self.patch_jump(body_jump);
+        }
+        self.statement();
+        self.emit_loop(loop_start);
+        if let Some(exit_jump) = exit_jump {
+            self.patch_jump(exit_jump);
+            self.emit_simple_op(OpCode::Pop, None);
+        }
+    }
+    #[tracing::instrument()]
+    fn print_statement(&mut self) {
+        let line = self.prev.line;
+        self.expression();
+        if !self.eat(TokenType::Semi) {
+            self.error("Expected ; after value", true);
+            return;
+        }
+        self.emit_op_on(line, OpCode::Print, None);
+    }
+    /// An expression evaluated for its effects, a final
+    /// expression without a `;` is left on the stack
+    #[tracing::instrument()]
+    fn expression_statement(&mut self) {
+        self.expression();
+        if self.current.kind == TokenType::Eof {
+            return;
+        }
+        if !self.eat(TokenType::Semi) {
+            self.error("Expected ; after expression", true);
+            return;
+        }
+        self.emit_simple_op(OpCode::Pop, None);
+    }
+    /// Skip the rest of a statement that failed to compile,
+    /// stopping after its `;` or before the keyword that
+    /// starts the next one
+    #[tracing::instrument()]
+    fn synchronize(&mut self) {
+        use TokenType::*;
+        self.panic_mode = false;
+        while self.current.kind != Eof {
+            if self.prev.kind == Semi {
+                return;
+            }
+            if let Class | Fun | Var | For | If | While | Print | Return = self.current.kind {
+                return;
+            }
+            self.advance();
+        }
     }
     #[tracing::instrument()]
     fn expression(&mut self) {
+        self.precedence(Prec::Comma);
+    }
+    /// An expression that stops at a `,`, for places
+    /// where commas separate items
+    #[tracing::instrument()]
+    fn item(&mut self) {
         self.precedence(Prec::Assignment);
     }
     #[tracing::instrument()]
@@ -129,8 +324,15 @@ impl<'a> Compiler<'a> {
     }
     #[tracing::instrument()]
     fn number(&mut self) {
-        if let Ok(n) = self.prev.slice.parse() {
-            self.emit_constant(Value::Number(n));
+        let slice = self.prev.slice.replace('_', "");
+        let n = match slice.get(..2) {
+            Some("0x") | Some("0X") => u64::from_str_radix(&slice[2..], 16).ok().map(|n| n as f64),
+            Some("0b") | Some("0B") => u64::from_str_radix(&slice[2..], 2).ok().map(|n| n as f64),
+            _ => slice.parse().ok(),
+        };
+        match n {
+            Some(n) => self.emit_constant(Value::Number(n)),
+            None => self.error("Number literal out of range", false),
         }
     }
     #[tracing::instrument()]
@@ -138,6 +340,26 @@ impl<'a> Compiler<'a> {
         let idx = self.chunk.add_obj(Obj::string(&self.prev.slice[1..self.prev.slice.len() - 1]));
         self.emit_constant(Value::Obj { idx });
     }
+    /// Read a global, or assign to it when followed by `=`
+    #[tracing::instrument()]
+    fn variable(&mut self) {
+        let line = self.prev.line;
+        let idx = self.identifier_constant();
+        if self.can_assign && self.current.kind == TokenType::Eq {
+            self.advance();
+            self.item();
+            self.emit_op_on(line, OpCode::SetGlobal { idx }, None);
+        } else {
+            self.emit_op_on(line, OpCode::GetGlobal { idx }, None);
+        }
+    }
+    /// Add the name of the identifier that was just
+    /// consumed to the constants
+    #[tracing::instrument()]
+    fn identifier_constant(&mut self) -> usize {
+        let idx = self.chunk.add_obj(Obj::string(self.prev.slice));
+        self.chunk.add_constant(Value::Obj { idx })
+    }
     #[tracing::instrument()]
     fn grouping(&mut self) {
         self.expression();
@@ -146,12 +368,55 @@ impl<'a> Compiler<'a> {
         }
     }
     #[tracing::instrument()]
+    fn table(&mut self) {
+        self.emit_simple_op(OpCode::Table, None);
+        if self.current.kind == TokenType::RightBrace {
+            self.advance();
+            return;
+        }
+        loop {
+            self.item();
+            if !self.eat(TokenType::Colon) {
+                self.error("Expected : after table key", true);
+                return;
+            }
+            let line = self.prev.line;
+            self.item();
+            self.emit_op_on(line, OpCode::TableEntry, None);
+            if self.current.kind != TokenType::Comma {
+                break;
+            }
+            self.advance();
+        }
+        if !self.eat(TokenType::RightBrace) {
+            self.error("Expected } after table entries", true);
+        }
+    }
+    #[tracing::instrument()]
+    fn index(&mut self) {
+        let can_assign = self.can_assign;
+        let line = self.prev.line;
+        self.expression();
+        if !self.eat(TokenType::RightBracket) {
+            self.error("Expected ] after index", true);
+            return;
+        }
+        if can_assign && self.current.kind == TokenType::Eq {
+            self.advance();
+            self.expression();
+            self.emit_op_on(line, OpCode::SetIndex, None);
+        } else {
+            self.emit_op_on(line, OpCode::GetIndex, None);
+        }
+    }
+    #[tracing::instrument()]
     fn unary(&mut self) {
         let op = self.prev.kind;
+        let line = self.prev.line;
         self.precedence(Prec::Unary);
         match op {
-            TokenType::Minus => self.emit_simple_op(OpCode::Negate, None),
-            TokenType::Bang => self.emit_simple_op(OpCode::Not, None),
+            TokenType::Minus => self.emit_op_on(line, OpCode::Negate, None),
+            TokenType::Bang => self.emit_op_on(line, OpCode::Not, None),
             _ => (),
         }
     }
@@ -159,7 +424,9 @@ impl<'a> Compiler<'a> {
     fn binary(&mut self) {
         use TokenType::*;
         let op = self.prev.kind;
+        let line = self.prev.line;
         let prec = Self::determine_precedence(op);
+        let rhs_start = self.chunk.code.len();
         self.precedence(prec + 1);
         let (first, second) = match op {
             Plus => (OpCode::Add, None),
@@ -174,24 +441,94 @@ impl<'a> Compiler<'a> {
             LessEq => (OpCode::Gtr, Some(OpCode::Not)),
             _ => return,
         };
-        self.emit_simple_op(first, second);
+        if second.is_none() && self.fold(first, rhs_start) {
+            return;
+        }
+        self.emit_op_on(line, first, second);
+    }
+    /// Replace two number constants ending at `rhs_start` with
+    /// the result of applying `op` to them. Nothing is folded
+    /// when the result wouldn't match what the VM computes, or
+    /// when a jump lands between the operands
+    #[tracing::instrument()]
+    fn fold(&mut self, op: OpCode, rhs_start: usize) -> bool {
+        let lhs_idx = match rhs_start.checked_sub(1) {
+            Some(idx) if idx >= self.jump_target => idx,
+            _ => return false,
+        };
+        if self.chunk.code.len() != rhs_start + 1 {
+            return false;
+        }
+        let (lhs, rhs) = match (self.chunk.code[lhs_idx], self.chunk.code[rhs_start]) {
+            (OpCode::Constant { idx: l }, OpCode::Constant { idx: r }) => {
+                match (self.chunk.values[l], self.chunk.values[r]) {
+                    (Value::Number(lhs), Value::Number(rhs)) => (lhs, rhs),
+                    _ => return false,
+                }
+            }
+            _ => return false,
+        };
+        let n = match op {
+            OpCode::Add => lhs + rhs,
+            OpCode::Sub => lhs - rhs,
+            OpCode::Mul => lhs * rhs,
+            OpCode::Div if rhs != 0.0 => lhs / rhs,
+            _ => return false,
+        };
+        if !n.is_finite() {
+            return false;
+        }
+        let line = self.chunk.lines.get_unchecked(lhs_idx);
+        self.chunk.code.truncate(lhs_idx);
+        self.chunk.lines.truncate(lhs_idx);
+        let idx = self.chunk.add_constant(Value::Number(n));
+        self.emit_op_on(line, OpCode::Constant { idx }, None);
+        true
+    }
+    /// Evaluate the left side only for its effects, the
+    /// right side is the value of the whole expression
+    #[tracing::instrument()]
+    fn comma(&mut self) {
+        self.emit_simple_op(OpCode::Pop, None);
+        self.precedence(Prec::Comma + 1);
+    }
+    #[tracing::instrument()]
+    fn ternary(&mut self) {
+        let else_jump = self.emit_jump(OpCode::JumpIfFalse { offset: 0 });
+        self.emit_simple_op(OpCode::Pop, None);
+        self.item();
+        if !self.eat(TokenType::Colon) {
+            self.error("Expected : after the first branch of ?", true);
+            return;
+        }
+        let end_jump = self.emit_jump(OpCode::Jump { offset: 0 });
+        self.patch_jump(else_jump);
+        self.emit_simple_op(OpCode::Pop, None);
+        self.precedence(Prec::Ternary);
+        self.patch_jump(end_jump);
     }
     #[tracing::instrument()]
     fn precedence(&mut self, precedence: Prec) {
         self.advance();
         let prefix = Self::prefix(self.prev.kind);
         if let Some(prefix) = prefix {
+            let can_assign = precedence <= Prec::Assignment;
+            self.can_assign = can_assign;
             prefix(self);
             while precedence <= Self::determine_precedence(self.current.kind) {
                 self.advance();
                 let infix = Self::infix(self.prev.kind);
                 if let Some(infix) = infix {
+                    self.can_assign = can_assign;
                     infix(self);
                 } else {
                     self.error("Expect infix expression", false);
                     return;
                 }
             }
+            if can_assign && self.current.kind == TokenType::Eq {
+                self.error("Invalid assignment target", true);
+            }
         } else {
             self.error("Expect prefix expression", false);
         }
@@ -203,6 +540,9 @@ impl<'a> Compiler<'a> {
             Minus | Plus => Prec::Term,
             Slash | Star => Prec::Factor,
             EqEq | BangEq | LessEq | GreaterEq | Greater | Less => Prec::Equality,
+            LeftBracket => Prec::Call,
+            Question => Prec::Ternary,
+            Comma => Prec::Comma,
             _ => Prec::None,
         }
     }
@@ -211,10 +551,12 @@ impl<'a> Compiler<'a> {
         use TokenType::*;
         match kind {
             LeftParen => Some(&Self::grouping),
+            LeftBrace => Some(&Self::table),
             Minus | Bang => Some(&Self::unary),
             Number => Some(&Self::number),
             String => Some(&Self::string),
             True | False | Nil => Some(&Self::literal),
+            Ident => Some(&Self::variable),
             _ => None,
         }
     }
@@ -224,6 +566,9 @@ impl<'a> Compiler<'a> {
         match kind {
             Minus | Plus | Slash | Star | Number | EqEq | BangEq | LessEq | GreaterEq | Greater
             | Less => Some(&Self::binary),
+            LeftBracket => Some(&Self::index),
+            Question => Some(&Self::ternary),
+            Comma => Some(&Self::comma),
             _ => None,
         }
     }
@@ -233,10 +578,6 @@ impl<'a> Compiler<'a> {
             self.advance();
             true
         } else {
-            eprintln!(
-                "{:?} didn't match {:?} skipping advance",
-                self.current.kind, kind
-            );
             false
         }
     }
@@ -244,11 +585,44 @@ impl<'a> Compiler<'a> {
     fn emit_return(&mut self) {
         self.emit_simple_op(OpCode::Return, None)
     }
+    /// Emit `code` (and `second`) on the line of the
+    /// token that was just consumed
     #[tracing::instrument()]
     fn emit_simple_op(&mut self, code: OpCode, second: Option<OpCode>) {
-        self.chunk.write(code, self.current.line);
+        self.emit_op_on(self.prev.line, code, second);
+    }
+    /// Emit `code` (and `second`) on `line`, for operators
+    /// that are emitted after their operands have been
+    /// compiled
+    #[tracing::instrument()]
+    fn emit_op_on(&mut self, line: usize, code: OpCode, second: Option<OpCode>) {
+        self.chunk.write(code, line);
         if let Some(s) = second {
-            self.chunk.write(s, self.current.line);
+            self.chunk.write(s, line);
+        }
+    }
+    /// Emit a jump with a placeholder offset, returning
+    /// where it is so `patch_jump` can fill it in
+    #[tracing::instrument()]
+    fn emit_jump(&mut self, code: OpCode) -> usize {
+        self.emit_simple_op(code, None);
+        self.chunk.code.len() - 1
+    }
+    /// Jump back to `start`, the offset is counted from
+    /// the instruction after the `Loop`
+    #[tracing::instrument()]
+    fn emit_loop(&mut self, start: usize) {
+        let offset = self.chunk.code.len() + 1 - start;
+        self.emit_simple_op(OpCode::Loop { offset }, None);
+    }
+    /// Point the jump at `idx` to the next instruction
+    #[tracing::instrument()]
+    fn patch_jump(&mut self, idx: usize) {
+        let target = self.chunk.code.len() - idx - 1;
+        self.jump_target = self.jump_target.max(self.chunk.code.len());
+        match &mut self.chunk.code[idx] {
+            OpCode::Jump { offset } | OpCode::JumpIfFalse { offset } => *offset = target,
+            _ => unreachable!("patched an instruction that isn't a jump"),
         }
     }
     #[tracing::instrument()]
@@ -258,20 +632,35 @@ impl<'a> Compiler<'a> {
     }
     #[tracing::instrument()]
     fn advance(&mut self) {
-        let tok = match self.scanner.next() {
-            Some(Ok(tok)) => tok,
-            Some(Err(e)) => {
-                eprintln!("scanner error {:}", e);
-                self.error = Some(e);
-                return;
+        let tok = loop {
+            match self.scanner.next() {
+                Some(Ok(tok)) => break tok,
+                // the scanner always moves past a bad token,
+                // so report it and try the next one
+                Some(Err(e)) => {
+                    if !self.panic_mode {
+                        eprintln!("[line {}] Error: {}", e.line, e);
+                    }
+                    self.panic_mode = true;
+                    self.had_error = true;
+                    self.error = Some(e);
+                }
+                // keep shifting so errors past the end of input
+                // are reported at the eof token
+                None => break Token::eof(self.current.line, self.current.span.1),
             }
-            _ => return,
         };
         self.prev = std::mem::replace(&mut self.current, tok);
     }
     #[tracing::instrument()]
     fn error(&mut self, msg: &str, current: bool) {
+        // everything up to the next statement is likely
+        // fallout from the first error
+        if self.panic_mode {
+            return;
+        }
         self.panic_mode = true;
+        self.had_error = true;
         let token = if current { &self.current } else { &self.prev };
         eprint!("[line {}] Error", token.line);
         if token.kind == TokenType::Eof {
@@ -288,11 +677,22 @@ impl<'a> Compiler<'a> {
 mod test {
     use super::*;
 
+    /// Compile `lox` without giving up on errors, so the
+    /// code kept after recovering from one can be checked
+    fn compile_with_errors(lox: &str) -> (Chunk<'_>, bool) {
+        let mut compiler = Compiler::new(lox);
+        compiler.advance();
+        while compiler.current.kind != TokenType::Eof {
+            compiler.declaration();
+        }
+        (compiler.chunk, compiler.had_error)
+    }
+
     #[test]
     fn strings() {
         let lox = r#""first" + "last""#;
         let c = Compiler::new(lox);
-        let chunk = c.compile();
+        let chunk = c.compile().unwrap();
         println!("{:#?}", chunk);
         assert_eq!(chunk.code, vec![
             OpCode::Constant { idx: 0 },
@@ -305,4 +705,130 @@ mod test {
             Obj::string("last"),
         ]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn number_literals() {
+        let chunk = Compiler::new("0xFF_FF, 0b1_0, 1_000.5, 1e1_0").compile().unwrap();
+        assert_eq!(chunk.values, vec![
+            Value::Number(65535.0),
+            Value::Number(2.0),
+            Value::Number(1000.5),
+            Value::Number(1e10),
+        ]);
+    }
+
+    #[test]
+    fn comma() {
+        let chunk = Compiler::new("1, 2").compile().unwrap();
+        assert_eq!(chunk.code, vec![
+            OpCode::Constant { idx: 0 },
+            OpCode::Pop,
+            OpCode::Constant { idx: 1 },
+            OpCode::Return,
+        ]);
+    }
+
+    #[test]
+    fn statements() {
+        let chunk = Compiler::new("1; 2;\nprint 3;").compile().unwrap();
+        assert_eq!(chunk.code, vec![
+            OpCode::Constant { idx: 0 },
+            OpCode::Pop,
+            OpCode::Constant { idx: 1 },
+            OpCode::Pop,
+            OpCode::Constant { idx: 2 },
+            OpCode::Print,
+            OpCode::Return,
+        ]);
+        assert_eq!(chunk.lines.get_unchecked(5), 2);
+        // a statement that fails to compile is skipped
+        let (chunk, had_error) = compile_with_errors("1 2; print 3;");
+        assert!(had_error);
+        assert_eq!(chunk.code[1..], [
+            OpCode::Constant { idx: 1 },
+            OpCode::Print,
+        ]);
+        assert!(Compiler::new("1 2; print 3;").compile().is_err());
+    }
+
+    #[test]
+    fn ternary() {
+        let chunk = Compiler::new("true ? 1 : 2").compile().unwrap();
+        assert_eq!(chunk.code, vec![
+            OpCode::True,
+            OpCode::JumpIfFalse { offset: 3 },
+            OpCode::Pop,
+            OpCode::Constant { idx: 0 },
+            OpCode::Jump { offset: 2 },
+            OpCode::Pop,
+            OpCode::Constant { idx: 1 },
+            OpCode::Return,
+        ]);
+    }
+
+    #[test]
+    fn constant_folding() {
+        let chunk = Compiler::new("1 + 2 * 3").compile().unwrap();
+        assert_eq!(chunk.code, vec![OpCode::Constant { idx: 0 }, OpCode::Return]);
+        assert_eq!(chunk.values, vec![Value::Number(7.0)]);
+        let chunk = Compiler::new("(1, 2) - 3").compile().unwrap();
+        assert_eq!(chunk.values[1], Value::Number(-1.0));
+        // division by zero and strings are left for the vm
+        for lox in &["1 / 0", r#""a" + "b""#, "1 + true"] {
+            let chunk = Compiler::new(lox).compile().unwrap();
+            assert_eq!(chunk.code.len(), 4, "{}", lox);
+        }
+        // 3 is added to whichever branch was taken
+        let chunk = Compiler::new("(true ? 1 : 2) + 3").compile().unwrap();
+        assert_eq!(chunk.code[6..], [
+            OpCode::Constant { idx: 1 },
+            OpCode::Constant { idx: 2 },
+            OpCode::Add,
+            OpCode::Return,
+        ]);
+        let chunk = Compiler::new("true ? 1 : 2 + 3").compile().unwrap();
+        assert_eq!(chunk.code[5..], [
+            OpCode::Pop,
+            OpCode::Constant { idx: 1 },
+            OpCode::Return,
+        ]);
+        assert_eq!(chunk.values[1], Value::Number(5.0));
+    }
+
+    #[test]
+    fn table() {
+        let lox = r#"{"a": 1}["a"] = 2"#;
+        let chunk = Compiler::new(lox).compile().unwrap();
+        assert_eq!(chunk.code, vec![
+            OpCode::Table,
+            OpCode::Constant { idx: 0 },
+            OpCode::Constant { idx: 1 },
+            OpCode::TableEntry,
+            OpCode::Constant { idx: 0 },
+            OpCode::Constant { idx: 2 },
+            OpCode::SetIndex,
+            OpCode::Return,
+        ]);
+    }
+    #[test]
+    fn synchronize() {
+        // only the first error in a statement is reported
+        // and the next statement still compiles
+        let (chunk, had_error) = compile_with_errors("print 1 +;\nprint 3;");
+        assert!(had_error);
+        assert_eq!(chunk.code[chunk.code.len() - 2..], [
+            OpCode::Constant { idx: 1 },
+            OpCode::Print,
+        ]);
+        assert_eq!(chunk.lines.get_unchecked(chunk.code.len() - 1), 2);
+        // a missing ; stops at the keyword that starts the
+        // next statement instead of swallowing it
+        let (chunk, had_error) = compile_with_errors("print 1 print 2;");
+        assert!(had_error);
+        assert_eq!(chunk.code[chunk.code.len() - 2..], [
+            OpCode::Constant { idx: 1 },
+            OpCode::Print,
+        ]);
+        assert!(Compiler::new("print 1 +;\nprint 3;").compile().is_err());
+    }
+}
@@ -16,6 +16,11 @@ pub use scanner::{Token, TokenType};
 
 pub type Result<T> = core::result::Result<T, error::Error>;
 
+/// Compile `source` into a `Chunk` without running it
+pub fn compile(source: &str) -> Result<Chunk<'_>> {
+    compiler::Compiler::new(source).compile()
+}
+
 
 #[cfg(test)]
 mod test {
@@ -28,30 +33,26 @@ mod test {
         let scanner = scanner::Scanner::new(lox);
         let tokens = scanner.collect::<TokenListR>().unwrap();
         assert_eq!(tokens, vec![
-            Token::new(TokenType::Bang, "!", 1),
-            Token::new(TokenType::LeftParen, "(", 1),
-            Token::new(TokenType::Number, "5", 1),
-            Token::new(TokenType::Minus, "-", 1),
-            Token::new(TokenType::Number, "4", 1),
-            Token::new(TokenType::Greater, ">", 1),
-            Token::new(TokenType::Number, "3", 1),
-            Token::new(TokenType::Star, "*", 1),
-            Token::new(TokenType::Number, "2", 1),
-            Token::new(TokenType::EqEq, "==", 1),
-            Token::new(TokenType::Bang, "!", 1),
-            Token::new(TokenType::Nil, "nil", 1),
-            Token::new(TokenType::RightParen, ")", 1),
-            Token::eof(1),
+            Token::new(TokenType::Bang, "!", 1, 0),
+            Token::new(TokenType::LeftParen, "(", 1, 1),
+            Token::new(TokenType::Number, "5", 1, 2),
+            Token::new(TokenType::Minus, "-", 1, 4),
+            Token::new(TokenType::Number, "4", 1, 6),
+            Token::new(TokenType::Greater, ">", 1, 8),
+            Token::new(TokenType::Number, "3", 1, 10),
+            Token::new(TokenType::Star, "*", 1, 12),
+            Token::new(TokenType::Number, "2", 1, 14),
+            Token::new(TokenType::EqEq, "==", 1, 16),
+            Token::new(TokenType::Bang, "!", 1, 19),
+            Token::new(TokenType::Nil, "nil", 1, 20),
+            Token::new(TokenType::RightParen, ")", 1, 23),
+            Token::eof(1, 24),
         ]);
         let compiler = compiler::Compiler::new(lox);
-        let chunk = compiler.compile();
+        let chunk = compiler.compile().unwrap();
         assert_eq!(chunk.code, vec![
-            OpCode::Constant { idx: 0 }, //5
-            OpCode::Constant { idx: 1 }, //4
-            OpCode::Sub,
-            OpCode::Constant { idx: 2 }, //3
-            OpCode::Constant { idx: 3 }, //2
-            OpCode::Mul,
+            OpCode::Constant { idx: 0 }, //5 - 4
+            OpCode::Constant { idx: 1 }, //3 * 2
             OpCode::Gtr,
             OpCode::Nil,
             OpCode::Not,
@@ -1,4 +1,4 @@
-use roxc::{Error,VM};
+use roxc::{compile, Error, VM};
 use std::path::PathBuf;
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
@@ -13,12 +13,17 @@ fn main() -> Result<()> {
         .expect("setting default subscriber failed");
     let mut args = std::env::args().skip(1);
     if let Some(arg) = args.next() {
+        if arg == "--emit-bytecode" {
+            match args.next().map(PathBuf::from) {
+                Some(path) if path.exists() => return emit_bytecode(path),
+                _ => usage(),
+            }
+        }
         let path = PathBuf::from(arg);
         if path.exists() {
             run_file(path)
         } else {
-            println!("Usage: clox [path]");
-            std::process::exit(64);
+            usage()
         }
     } else {
         repl()
@@ -42,7 +47,24 @@ fn repl() -> Result<()> {
     //     };
     //     interpret(line)?;
     // }
-    interpret(r#""yes" + "no""#).unwrap();
+    interpret(r#"print "yes" + "no";"#).unwrap();
+    Ok(())
+}
+fn usage() -> ! {
+    println!("Usage: clox [--emit-bytecode] [path]");
+    std::process::exit(64);
+}
+#[tracing::instrument]
+fn emit_bytecode(path: PathBuf) -> Result<()> {
+    let bytes = std::fs::read_to_string(&path)?;
+    let chunk = match compile(&bytes) {
+        Ok(chunk) => chunk,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(65);
+        }
+    };
+    print!("{}", chunk.disassemble(&path.display().to_string()));
     Ok(())
 }
 #[tracing::instrument]
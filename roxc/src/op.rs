@@ -14,6 +14,36 @@ pub enum OpCode {
     Eq,
     Gtr,
     Less,
+    /// Push a new, empty hash table
+    Table,
+    /// Pop a value and a key, inserting them into the
+    /// table left on the top of the stack
+    TableEntry,
+    /// Pop a key and a table, pushing the value at that
+    /// key or nil
+    GetIndex,
+    /// Pop a value, a key and a table, inserting the value
+    /// and pushing it back onto the stack
+    SetIndex,
+    /// Discard the value on the top of the stack
+    Pop,
+    /// Move forward `offset` instructions
+    Jump { offset: usize },
+    /// Move forward `offset` instructions when the value on
+    /// the top of the stack is falsey, leaving it in place
+    JumpIfFalse { offset: usize },
+    /// Move back `offset` instructions, counted from the
+    /// instruction after this one
+    Loop { offset: usize },
+    /// Pop a value and write it to stdout
+    Print,
+    /// Pop a value into the global named by constant `idx`
+    DefineGlobal { idx: usize },
+    /// Push the value of the global named by constant `idx`
+    GetGlobal { idx: usize },
+    /// Assign the value on the top of the stack to the
+    /// global named by constant `idx`, leaving it in place
+    SetGlobal { idx: usize },
 }
 
 impl std::fmt::Display for OpCode {
@@ -21,6 +51,12 @@ impl std::fmt::Display for OpCode {
         use OpCode::*;
         match self {
             Constant { .. } => write!(f, "Constant"),
+            Jump { .. } => write!(f, "Jump"),
+            JumpIfFalse { .. } => write!(f, "JumpIfFalse"),
+            Loop { .. } => write!(f, "Loop"),
+            DefineGlobal { .. } => write!(f, "DefineGlobal"),
+            GetGlobal { .. } => write!(f, "GetGlobal"),
+            SetGlobal { .. } => write!(f, "SetGlobal"),
             True => write!(f, "true"),
             False => write!(f, "false"),
             Nil => write!(f, "nil"),
@@ -32,7 +32,11 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
+    Colon,
     Comma,
+    Question,
     Period,
     Minus,
     Plus,
@@ -84,7 +88,11 @@ impl TryFrom<char> for TokenType {
             ')' => Ok(RightParen),
             '{' => Ok(LeftBrace),
             '}' => Ok(RightBrace),
+            '[' => Ok(LeftBracket),
+            ']' => Ok(RightBracket),
+            ':' => Ok(Colon),
             ',' => Ok(Comma),
+            '?' => Ok(Question),
             '.' => Ok(Period),
             '-' => Ok(Minus),
             '+' => Ok(Plus),
@@ -105,7 +113,7 @@ impl TryFrom<char> for TokenType {
                 no: Less,
             }),
             '"' => Err(MatchError::String),
-            _ if ch.is_digit(10) => Err(MatchError::Number),
+            _ if ch.is_ascii_digit() => Err(MatchError::Number),
             _ if ch.is_alphabetic() => Err(MatchError::Ident(ch)),
             _ => Err(MatchError::Error),
         }
@@ -123,21 +131,27 @@ pub struct Token<'a> {
     pub kind: TokenType,
     pub slice: &'a str,
     pub line: usize,
+    /// The start and end byte offsets of `slice` in the source
+    pub span: (usize, usize),
 }
 
 impl<'a> Token<'a> {
-    pub fn eof(line: usize) -> Self {
+    /// The end of input found at byte `offset`
+    pub fn eof(line: usize, offset: usize) -> Self {
         Self {
             kind: TokenType::Eof,
             slice: "",
             line,
+            span: (offset, offset),
         }
     }
-    pub fn new(kind: TokenType, slice: &'a str, line: usize) -> Self {
+    /// A token for `slice`, which starts at byte `start`
+    pub fn new(kind: TokenType, slice: &'a str, line: usize, start: usize) -> Self {
         Self {
             kind,
             slice,
             line,
+            span: (start, start + slice.len()),
         }
     }
 }
@@ -182,11 +196,7 @@ impl<'a> Scanner<'a> {
                 self.cursor += 1;
                 Ok(self.token(kind, start))
             }
-            Err(MatchError::Eof) => Ok(Token {
-                slice: &self.original[self.cursor..self.cursor],
-                kind: TokenType::Eof,
-                line: self.line,
-            }),
+            Err(MatchError::Eof) => Ok(Token::eof(self.line, self.cursor)),
             Err(MatchError::MaybeWithNext { yes, no }) => {
                 let start = self.cursor;
                 self.advance();
@@ -200,7 +210,12 @@ impl<'a> Scanner<'a> {
             }
             Err(MatchError::Ident(start)) => Ok(self.ident(start)),
             Err(MatchError::String) => self.string(),
-            Err(MatchError::Error) => Err(ScannerError::new(self.line, self.cursor)),
+            Err(MatchError::Error) => {
+                // skip the character so scanning can continue after it
+                let index = self.cursor;
+                self.advance();
+                Err(ScannerError::new(self.line, index))
+            }
             _ => self.number(),
         }
     }
@@ -248,30 +263,92 @@ impl<'a> Scanner<'a> {
         let start = self.cursor;
         let line = self.line;
         self.advance();
-        self.take_until(|c| !c.is_digit(10));
+        if &self.original[start..self.cursor] == "0" {
+            let radix = match self.chars.peek() {
+                Some('x') | Some('X') => Some(16),
+                Some('b') | Some('B') => Some(2),
+                _ => None,
+            };
+            if let Some(radix) = radix {
+                self.advance();
+                let digits_start = self.cursor;
+                if self.chars.peek().is_some_and(|c| c.is_digit(radix)) {
+                    self.digits(radix)?;
+                }
+                if self.cursor == digits_start {
+                    return Err(ScannerError::new(line, self.cursor));
+                }
+                // like too many decimal digits, more than fit
+                // in a u64 can't be compiled
+                let digits = self.original[digits_start..self.cursor].replace('_', "");
+                if u64::from_str_radix(&digits, radix).is_err() {
+                    return Err(ScannerError::new(line, start));
+                }
+                return Ok(self.token(TokenType::Number, start));
+            }
+        }
+        self.digits(10)?;
         if let Some(maybe_dot) = self.chars.peek() {
             if *maybe_dot == '.' {
                 let mut slice = self.original[self.cursor..].chars();
                 let _ = slice.next();
                 if let Some(num) = slice.next() {
-                    if num.is_digit(10) {
+                    if num.is_ascii_digit() {
                         self.advance();
                     }
                 }
             }
         }
-        self.take_until(|c| !c.is_digit(10));
+        self.digits(10)?;
+        if let Some('e') | Some('E') = self.chars.peek() {
+            self.advance();
+            if let Some('+') | Some('-') = self.chars.peek() {
+                self.advance();
+            }
+            match self.chars.peek() {
+                Some(c) if c.is_ascii_digit() => self.digits(10)?,
+                _ => return Err(ScannerError::new(line, self.cursor)),
+            }
+        }
+        // too many digits parse to infinity rather than failing
+        match self.original[start..self.cursor].replace('_', "").parse::<f64>() {
+            Ok(n) if n.is_finite() => (),
+            _ => return Err(ScannerError::new(line, start)),
+        }
         Ok(Token {
             kind: TokenType::Number,
             line,
             slice: &self.original[start..self.cursor],
+            span: (start, self.cursor),
         })
     }
+    /// Consume a run of digits, allowing single `_`s
+    /// between them as separators. A `_` that isn't
+    /// followed by a digit is an error
+    #[tracing::instrument(skip(self))]
+    fn digits(&mut self, radix: u32) -> Result<()> {
+        loop {
+            match self.chars.peek() {
+                Some(c) if c.is_digit(radix) => self.advance(),
+                Some('_') if self.original[self.cursor + 1..]
+                    .chars()
+                    .next()
+                    .is_some_and(|c| c.is_digit(radix)) => self.advance(),
+                Some('_') => return Err(ScannerError::new(self.line, self.cursor)),
+                _ => return Ok(()),
+            }
+        }
+    }
     #[tracing::instrument(skip(self))]
     fn string(&mut self) -> Result<Token<'a>> {
         let start = self.cursor;
+        let line = self.line;
+        self.advance();
+        self.take_until(|c| c == '"');
+        if self.chars.peek().is_none() {
+            return Err(ScannerError::new(line, start));
+        }
         self.advance();
-        self.take_through(|c| c == '"');
         Ok(self.token(TokenType::String, start))
     }
     #[tracing::instrument(skip(self))]
@@ -414,10 +491,14 @@ impl<'a> Scanner<'a> {
     }
     #[tracing::instrument(skip(self))]
     fn advance(&mut self) {
-        if let Some('\n') = self.chars.next() {
-            self.line += 1;
+        match self.chars.next() {
+            Some('\n') => {
+                self.line += 1;
+                self.cursor += 1;
+            }
+            Some(ch) => self.cursor += ch.len_utf8(),
+            None => (),
         }
-        self.cursor += 1;
         self.look_ahead = &self.original[self.cursor..];
     }
     #[tracing::instrument(skip(self))]
@@ -426,6 +507,7 @@ impl<'a> Scanner<'a> {
             kind,
             line: self.line,
             slice: &self.original[start..self.cursor],
+            span: (start, self.cursor),
         }
     }
 }
@@ -507,6 +589,39 @@ mod test {
         )
     }
 
+    #[test]
+    fn ternary_puncts() {
+        run_batch(
+            "true ? 1 : 2",
+            &[
+                (TokenType::True, "true"),
+                (TokenType::Question, "?"),
+                (TokenType::Number, "1"),
+                (TokenType::Colon, ":"),
+                (TokenType::Number, "2"),
+                (TokenType::Eof, ""),
+            ],
+        )
+    }
+
+    #[test]
+    fn table_puncts() {
+        run_batch(
+            r#"{"a": 1}["a"]"#,
+            &[
+                (TokenType::LeftBrace, "{"),
+                (TokenType::String, r#""a""#),
+                (TokenType::Colon, ":"),
+                (TokenType::Number, "1"),
+                (TokenType::RightBrace, "}"),
+                (TokenType::LeftBracket, "["),
+                (TokenType::String, r#""a""#),
+                (TokenType::RightBracket, "]"),
+                (TokenType::Eof, ""),
+            ],
+        )
+    }
+
     #[test]
     fn strings() {
         run_batch(
@@ -541,6 +656,83 @@ mod test {
         )
     }
 
+    #[test]
+    fn radix_numbers() {
+        run_batch(
+            "0xFF 0b1010 0x0 0",
+            &[
+                (TokenType::Number, "0xFF"),
+                (TokenType::Number, "0b1010"),
+                (TokenType::Number, "0x0"),
+                (TokenType::Number, "0"),
+                (TokenType::Eof, ""),
+            ],
+        )
+    }
+
+    #[test]
+    fn malformed_radix_number() {
+        let mut scanner = Scanner::new("0x ");
+        assert!(scanner.next().unwrap().is_err());
+        // one past u64::MAX
+        for lox in &["0x1_0000_0000_0000_0000 ", &format!("0b1{} ", "0".repeat(64))] {
+            let mut scanner = Scanner::new(lox);
+            assert!(scanner.next().unwrap().is_err(), "{}", lox);
+        }
+        run_batch("0xFFFF_FFFF_FFFF_FFFF", &[
+            (TokenType::Number, "0xFFFF_FFFF_FFFF_FFFF"),
+            (TokenType::Eof, ""),
+        ]);
+    }
+
+    #[test]
+    fn digit_separators() {
+        run_batch(
+            "1_000 0xFF_FF 1_0.2_5 1e1_0",
+            &[
+                (TokenType::Number, "1_000"),
+                (TokenType::Number, "0xFF_FF"),
+                (TokenType::Number, "1_0.2_5"),
+                (TokenType::Number, "1e1_0"),
+                (TokenType::Eof, ""),
+            ],
+        );
+        for lox in &["1_ ", "1__0 ", "1_.5 ", "0x_1 ", "1e_1 ", "1_e1 "] {
+            let mut scanner = Scanner::new(lox);
+            assert!(scanner.next().unwrap().is_err(), "{}", lox);
+        }
+    }
+
+    #[test]
+    fn exponent_numbers() {
+        run_batch(
+            "1e3 1.5E+2 2.5e-3",
+            &[
+                (TokenType::Number, "1e3"),
+                (TokenType::Number, "1.5E+2"),
+                (TokenType::Number, "2.5e-3"),
+                (TokenType::Eof, ""),
+            ],
+        )
+    }
+
+    #[test]
+    fn malformed_exponent() {
+        let mut scanner = Scanner::new("1e ");
+        assert!(scanner.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn out_of_range_number() {
+        let source = format!("1 + {}", "9".repeat(400));
+        let mut scanner = Scanner::new(&source);
+        assert!(scanner.next().unwrap().is_ok());
+        assert!(scanner.next().unwrap().is_ok());
+        assert!(matches!(scanner.next(), Some(Err(ScannerError { line: 1, index: 4 }))));
+        assert!(Scanner::new("1e308").next().unwrap().is_ok());
+        assert!(Scanner::new("1e309").next().unwrap().is_err());
+    }
+
     #[test]
     fn idents() {
         run_batch(
@@ -567,6 +759,54 @@ mod test {
         }
     }
     #[test]
+    fn eof_line() {
+        let tokens = Scanner::new("1 + 2\n\n\n").collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(tokens.last(), Some(&Token::eof(4, 8)));
+        let tokens = Scanner::new("1 + 2\n// done\n\n").collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(tokens.last(), Some(&Token::eof(4, 15)));
+    }
+    #[test]
+    fn spans() {
+        let source = "var héllo = \"wörld\";\n  12.5 >= 0x1F";
+        let tokens = Scanner::new(source).collect::<Result<Vec<_>>>().unwrap();
+        for token in &tokens {
+            assert_eq!(&source[token.span.0..token.span.1], token.slice);
+        }
+        let spans: Vec<(usize, usize)> = tokens.iter().map(|t| t.span).collect();
+        assert_eq!(
+            spans,
+            vec![
+                (0, 3),
+                (4, 10),
+                (11, 12),
+                (13, 21),
+                (21, 22),
+                (25, 29),
+                (30, 32),
+                (33, 37),
+                (37, 37),
+            ]
+        );
+    }
+    #[test]
+    fn non_ascii() {
+        run_batch("日本 + \"🦀 ü\" ü", &[
+            (TokenType::Ident, "日本"),
+            (TokenType::Plus, "+"),
+            (TokenType::String, "\"🦀 ü\""),
+            (TokenType::Ident, "ü"),
+            (TokenType::Eof, ""),
+        ]);
+        // a character that can't start a token is skipped
+        let results: Vec<_> = Scanner::new("a € b").collect();
+        assert_eq!(results.len(), 4);
+        assert!(matches!(results[1], Err(ScannerError { line: 1, index: 2 })));
+        assert_eq!(results[2].as_ref().map(|t| t.slice).ok(), Some("b"));
+        let results: Vec<_> = Scanner::new("1 \"unterminated ü").collect();
+        assert!(matches!(results[1], Err(ScannerError { line: 1, index: 2 })));
+        assert_eq!(results.last().and_then(|r| r.as_ref().ok()).map(|t| t.kind), Some(TokenType::Eof));
+    }
+    #[test]
     fn multiple_strings() {
         run_batch(r#""first" + "last""#, &[
             (TokenType::String, r#""first""#),
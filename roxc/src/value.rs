@@ -4,7 +4,7 @@ use std::{
     hash::BuildHasherDefault,
 };
 use hashers::fnv::FNV1aHasher64;
-type HashTable<'a> = HashMap<Cow<'a, str>, Value, BuildHasherDefault<FNV1aHasher64>>;
+pub type HashTable<'a> = HashMap<Cow<'a, str>, Value, BuildHasherDefault<FNV1aHasher64>>;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Value {
@@ -29,50 +29,6 @@ impl<'a> Obj<'a> {
     }
 }
 
-impl core::ops::Add for Value {
-    type Output = Value;
-    fn add(self, other: Value) -> Value {
-        use Value::*;
-        match (self, other) {
-            (Number(l), Number(r)) => Number(l + r),
-            (Nil, Nil) => Nil,
-            _ => unimplemented!("cannot add {:?} and {:?}", self, other),
-        }
-    }
-}
-
-impl core::ops::Sub for Value {
-    type Output = Value;
-    fn sub(self, other: Value) -> Value {
-        use Value::*;
-        match (self, other) {
-            (Number(l), Number(r)) => Number(l - r),
-            _ => unimplemented!("cannot add {:?} and {:?}", self, other),
-        }
-    }
-}
-impl core::ops::Mul for Value {
-    type Output = Value;
-    fn mul(self, other: Value) -> Value {
-        use Value::*;
-        match (self, other) {
-            (Number(l), Number(r)) => Number(l * r),
-            _ => unimplemented!("cannot add {:?} and {:?}", self, other),
-        }
-    }
-}
-
-impl core::ops::Div for Value {
-    type Output = Value;
-    fn div(self, other: Value) -> Value {
-        use Value::*;
-        match (self, other) {
-            (Number(l), Number(r)) => Number(l / r),
-            _ => unimplemented!("cannot add {:?} and {:?}", self, other),
-        }
-    }
-}
-
 impl core::ops::Neg for Value {
     type Output = Value;
     fn neg(self) -> Self {
@@ -85,18 +41,25 @@ impl core::ops::Neg for Value {
     }
 }
 
+/// Follows Lox truthiness, see `Value::is_falsey`
 impl core::ops::Not for Value {
     type Output = Value;
     fn not(self) -> Self {
-        use Value::*;
-        match self {
-            Boolean(b) => Boolean(!b),
-            Nil => Boolean(true),
-            _ => Boolean(false),
-        }
+        Value::Boolean(self.is_falsey())
+    }
+}
+
+impl Value {
+    /// Only `nil` and `false` are falsey, every other
+    /// value including `0` and `""` is truthy
+    pub fn is_falsey(&self) -> bool {
+        matches!(self, Value::Nil | Value::Boolean(false))
     }
 }
 
+/// Only numbers are ordered here, `Obj`s need the heap
+/// to be compared so the VM handles them. Booleans and
+/// nil are unordered and always produce `None`
 impl core::cmp::PartialOrd for Value {
     fn partial_cmp(&self, other: &Value) -> Option<core::cmp::Ordering> {
         use Value::*;
@@ -1,11 +1,13 @@
-use crate::{chunk::Chunk, compiler::Compiler, error::Error, op::OpCode, value::{Value, Obj}, Result};
-use std::{collections::{VecDeque, HashSet}, borrow::Cow};
+use crate::{chunk::Chunk, compiler::Compiler, error::Error, op::OpCode, value::{HashTable, Value, Obj}, Result};
+use rox_shared::format_number;
+use std::{cmp::Ordering, collections::VecDeque, borrow::Cow};
 
 #[derive(Default)]
 pub struct VM<'a> {
     pub chunk: Chunk<'a>,
     ip: usize,
     stack: VecDeque<Value>,
+    globals: HashTable<'a>,
 }
 impl<'a> std::fmt::Debug for VM<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -23,15 +25,23 @@ impl<'a> VM<'a> {
         self.ip = 0;
         self.run()
     }
+    /// Run an already compiled chunk, like one loaded
+    /// with `Chunk::deserialize`
+    #[tracing::instrument(skip(self, chunk))]
+    pub fn interpret_chunk(&mut self, chunk: Chunk<'a>) -> Result<()> {
+        self.chunk = chunk;
+        self.ip = 0;
+        self.run()
+    }
     #[tracing::instrument(skip(self, source))]
     fn compile(&self, source: &'a str) -> Result<Chunk<'a>> {
         let compiler = Compiler::new(source);
-        Ok(compiler.compile())
+        compiler.compile()
     }
     #[tracing::instrument(skip(self))]
     fn run(&mut self) -> Result<()> {
-        for i in 0..self.chunk.code[self.ip..].len() {
-            let inst = self.chunk.code[i];
+        while self.ip < self.chunk.code.len() {
+            let inst = self.chunk.code[self.ip];
             if cfg!(feature = "debug") {
                 self.chunk.dissassemble_inst(self.ip, &inst);
                 print!("          ");
@@ -40,9 +50,8 @@ impl<'a> VM<'a> {
             self.ip += 1;
             match inst {
                 OpCode::Return => {
-                    let last_value = self.stack.pop_back();
-                    self.print_value(last_value);
-                    println!();
+                    // there are no call frames yet, so this
+                    // halts the script
                     return Ok(());
                 }
                 OpCode::Constant { idx } => {
@@ -50,24 +59,23 @@ impl<'a> VM<'a> {
                     self.stack.push_back(c);
                 }
                 OpCode::Negate => {
-                    if let Some(Value::Number(_)) = self.stack.back() {
-                        let n = self.stack.pop_back().unwrap();
-                        self.stack.push_back(-n);
-                    } else {
-                        return self.runtime_err("Operand must be a number.");
+                    match self.pop_operand()? {
+                        Value::Number(n) => self.stack.push_back(Value::Number(-n)),
+                        other => {
+                            return self.runtime_err(&format!(
+                                "Operand of '-' must be a number, found {}",
+                                self.type_name(other)
+                            ))
+                        }
                     }
                 }
                 OpCode::Add => {
-                    if !self.operands_match() {
-                        return self.runtime_err("Cannot add unmatched operands");
-                    }
-                    let lhs = self.pop_operand().map_err(|e| {
+                    self.check_arithmetic('+')?;
+                    let lhs = self.pop_operand().inspect_err(|_| {
                         eprintln!("missing lhs in Add");
-                        e
                     })?;
-                    let rhs = self.pop_operand().map_err(|e| {
+                    let rhs = self.pop_operand().inspect_err(|_| {
                         eprintln!("missing lhs in Add");
-                        e
                     })?;
                     let v = match  (lhs, rhs) {
                         (Value::Obj { idx: l_idx }, Value::Obj { idx: r_idx}) => {
@@ -78,54 +86,28 @@ impl<'a> VM<'a> {
                                 let idx = self.chunk.add_obj(Obj::String(c));
                                 Value::Obj { idx }
                             } else {
-                                unimplemented!("")
+                                return self.runtime_err("Only strings can be added together");
                             }
                         }
-                        _ => lhs + rhs
+                        (Value::Number(l), Value::Number(r)) => Value::Number(l + r),
+                        _ => return self.runtime_err("Only numbers and strings can be added together"),
                     };
                     self.stack.push_back(v)
                 }
                 OpCode::Sub => {
-                    if !self.operands_match() {
-                        return self.runtime_err("Cannot subtract unmatched operands");
-                    }
-                    let lhs = self.pop_operand().map_err(|e| {
-                        eprintln!("missing lhs in Sub");
-                        e
-                    })?;
-                    let rhs = self.pop_operand().map_err(|e| {
-                        eprintln!("missing rhs in Sub");
-                        e
-                    })?;
-                    self.stack.push_back(rhs - lhs)
+                    self.check_arithmetic('-')?;
+                    let (lhs, rhs) = self.pop_numbers()?;
+                    self.stack.push_back(Value::Number(lhs - rhs))
                 }
                 OpCode::Mul => {
-                    if !self.operands_match() {
-                        return self.runtime_err("Cannot multiply unmatched operands");
-                    }
-                    let lhs = self.pop_operand().map_err(|e| {
-                        eprintln!("missing lhs in Mul");
-                        e
-                    })?;
-                    let rhs = self.pop_operand().map_err(|e| {
-                        eprintln!("missing rhs in Mul");
-                        e
-                    })?;
-                    self.stack.push_back(rhs * lhs)
+                    self.check_arithmetic('*')?;
+                    let (lhs, rhs) = self.pop_numbers()?;
+                    self.stack.push_back(Value::Number(lhs * rhs))
                 }
                 OpCode::Div => {
-                    if !self.operands_match() {
-                        return self.runtime_err("Cannot divide unmatched operands");
-                    }
-                    let lhs = self.pop_operand().map_err(|e| {
-                        eprintln!("missing lhs in Div");
-                        e
-                    })?;
-                    let rhs = self.pop_operand().map_err(|e| {
-                        eprintln!("missing rhs in Div");
-                        e
-                    })?;
-                    self.stack.push_back(rhs / lhs)
+                    self.check_arithmetic('/')?;
+                    let (lhs, rhs) = self.pop_numbers()?;
+                    self.stack.push_back(Value::Number(lhs / rhs))
                 }
                 OpCode::True => {
                     self.stack.push_back(Value::Boolean(true));
@@ -137,62 +119,196 @@ impl<'a> VM<'a> {
                     self.stack.push_back(Value::Nil);
                 }
                 OpCode::Not => {
-                    if self.stack.back().is_some() {
-                        let b = self.stack.pop_back().unwrap();
-                        self.stack.push_back(!b);
-                    } else {
-                        return self.runtime_err("Cannot negate a non boolean");
-                    }
+                    let value = self.pop_operand()?;
+                    self.stack.push_back(!value);
                 }
                 OpCode::Eq => {
-                    let lhs = self.pop_operand().map_err(|e| {
+                    let lhs = self.pop_operand().inspect_err(|_| {
                         eprintln!("missing lhs in Eq");
-                        e
                     })?;
-                    let rhs = self.pop_operand().map_err(|e| {
+                    let rhs = self.pop_operand().inspect_err(|_| {
                         eprintln!("missing rhs in Eq");
-                        e
                     })?;
-                    let b = match  (lhs, rhs) {
-                        (Value::Obj { idx: l_idx }, Value::Obj { idx: r_idx}) => {
-                            self.chunk.heap[l_idx] == self.chunk.heap[r_idx]
-                        }
-                        _ => lhs == rhs
-                    };
+                    let b = self.equal(lhs, rhs);
                     self.stack.push_back(Value::Boolean(b));
                 }
                 OpCode::Less => {
-                    if !self.operands_match() {
+                    if !self.operands_match()? {
                         return self.runtime_err("Cannot compare unmatched operands");
                     }
-                    let lhs = self.pop_operand().map_err(|e| {
-                        eprintln!("missing lhs in Less");
-                        e
-                    })?;
-                    let rhs = self.pop_operand().map_err(|e| {
+                    let rhs = self.pop_operand().inspect_err(|_| {
                         eprintln!("missing rhs in Less");
-                        e
                     })?;
-                    self.stack.push_back(Value::Boolean(lhs < rhs))
+                    let lhs = self.pop_operand().inspect_err(|_| {
+                        eprintln!("missing lhs in Less");
+                    })?;
+                    let ord = self.compare(lhs, rhs);
+                    self.stack
+                        .push_back(Value::Boolean(ord == Some(Ordering::Less)))
                 }
                 OpCode::Gtr => {
-                    if !self.operands_match() {
+                    if !self.operands_match()? {
                         return self.runtime_err("Cannot compare unmatched operands");
                     }
-                    let lhs = self.pop_operand().map_err(|e| {
-                        eprintln!("missing lhs in Gtr");
-                        e
-                    })?;
-                    let rhs = self.pop_operand().map_err(|e| {
+                    let rhs = self.pop_operand().inspect_err(|_| {
                         eprintln!("missing rhs in Gtr");
-                        e
                     })?;
-                    self.stack.push_back(Value::Boolean(lhs > rhs))
+                    let lhs = self.pop_operand().inspect_err(|_| {
+                        eprintln!("missing lhs in Gtr");
+                    })?;
+                    let ord = self.compare(lhs, rhs);
+                    self.stack
+                        .push_back(Value::Boolean(ord == Some(Ordering::Greater)))
+                }
+                OpCode::Table => {
+                    let idx = self.chunk.add_obj(Obj::hash_table(Default::default()));
+                    self.stack.push_back(Value::Obj { idx });
+                }
+                OpCode::TableEntry => {
+                    let value = self.pop_operand()?;
+                    let key = self.pop_operand()?;
+                    let table = *self
+                        .stack
+                        .back()
+                        .ok_or_else(|| self.runtime_error("missing table in TableEntry"))?;
+                    self.insert_entry(table, key, value)?;
+                }
+                OpCode::GetIndex => {
+                    let key = self.pop_operand()?;
+                    let table = self.pop_operand()?;
+                    let key = self.table_key(key)?;
+                    let entries = self.table_entries(table)?;
+                    let value = entries.get(key.as_ref()).copied().unwrap_or(Value::Nil);
+                    self.stack.push_back(value);
+                }
+                OpCode::SetIndex => {
+                    let value = self.pop_operand()?;
+                    let key = self.pop_operand()?;
+                    let table = self.pop_operand()?;
+                    self.insert_entry(table, key, value)?;
+                    self.stack.push_back(value);
+                }
+                OpCode::Pop => {
+                    self.pop_operand()?;
+                }
+                OpCode::Jump { offset } => {
+                    self.ip += offset;
+                }
+                OpCode::JumpIfFalse { offset } => {
+                    if self.stack.back().is_some_and(Value::is_falsey) {
+                        self.ip += offset;
+                    }
+                }
+                OpCode::Loop { offset } => {
+                    self.ip -= offset;
+                }
+                OpCode::Print => {
+                    let value = self.pop_operand()?;
+                    self.print_value(Some(value));
+                    println!();
+                }
+                OpCode::DefineGlobal { idx } => {
+                    let name = self.global_name(idx)?;
+                    let value = self.pop_operand()?;
+                    self.globals.insert(name, value);
+                }
+                OpCode::GetGlobal { idx } => {
+                    let name = self.global_name(idx)?;
+                    match self.globals.get(&name) {
+                        Some(value) => self.stack.push_back(*value),
+                        None => return self.runtime_err(&format!("Undefined variable '{}'", name)),
+                    }
+                }
+                OpCode::SetGlobal { idx } => {
+                    let name = self.global_name(idx)?;
+                    let value = *self
+                        .stack
+                        .back()
+                        .ok_or_else(|| self.runtime_error("Invalid operation, not enough operands"))?;
+                    match self.globals.get_mut(&name) {
+                        Some(slot) => *slot = value,
+                        None => return self.runtime_err(&format!("Undefined variable '{}'", name)),
+                    }
                 }
             }
         }
         Ok(())
     }
+    /// The name of `value`'s type for error messages,
+    /// `Obj`s are looked up on the heap
+    fn type_name(&self, value: Value) -> &'static str {
+        match value {
+            Value::Number(_) => "number",
+            Value::Boolean(_) => "boolean",
+            Value::Nil => "nil",
+            Value::Obj { idx } => match &self.chunk.heap[idx] {
+                Obj::String(_) => "string",
+                Obj::HashTable { .. } => "table",
+            },
+        }
+    }
+    /// The name of a global, held as a string in constant `idx`
+    fn global_name(&self, idx: usize) -> Result<Cow<'a, str>> {
+        if let Value::Obj { idx } = self.chunk.values[idx] {
+            if let Obj::String(s) = &self.chunk.heap[idx] {
+                return Ok(s.clone());
+            }
+        }
+        Err(self.runtime_error("Global names must be strings"))
+    }
+    /// Look up the heap string used as a table key
+    fn table_key(&self, key: Value) -> Result<Cow<'a, str>> {
+        if let Value::Obj { idx } = key {
+            if let Obj::String(s) = &self.chunk.heap[idx] {
+                return Ok(s.clone());
+            }
+        }
+        Err(self.runtime_error("Table keys must be strings"))
+    }
+
+    fn table_entries(&self, table: Value) -> Result<&HashTable<'a>> {
+        if let Value::Obj { idx } = table {
+            if let Obj::HashTable { entries } = &self.chunk.heap[idx] {
+                return Ok(entries);
+            }
+        }
+        Err(self.runtime_error("Only tables can be indexed"))
+    }
+
+    fn insert_entry(&mut self, table: Value, key: Value, value: Value) -> Result<()> {
+        let key = self.table_key(key)?;
+        self.table_entries(table)?;
+        if let Value::Obj { idx } = table {
+            if let Obj::HashTable { entries } = &mut self.chunk.heap[idx] {
+                entries.insert(key, value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Any two values can be compared for equality, values
+    /// of different types are never equal. Strings are
+    /// interned and tables compare by identity so two
+    /// `Obj`s are equal when they share a heap index
+    fn equal(&self, lhs: Value, rhs: Value) -> bool {
+        lhs == rhs
+    }
+
+    /// Order two values, looking up `Obj`s on the heap
+    /// so strings compare by their contents. Anything
+    /// `Value`'s `PartialOrd` can't order (booleans, nil,
+    /// mixed types) is `None`
+    fn compare(&self, lhs: Value, rhs: Value) -> Option<Ordering> {
+        match (lhs, rhs) {
+            (Value::Obj { idx: l_idx }, Value::Obj { idx: r_idx }) => {
+                match (&self.chunk.heap[l_idx], &self.chunk.heap[r_idx]) {
+                    (Obj::String(l), Obj::String(r)) => Some(l.cmp(r)),
+                    _ => None,
+                }
+            }
+            _ => lhs.partial_cmp(&rhs),
+        }
+    }
     fn print_value(&self, value: Option<Value>) {
         print!("{}", self.format_value(value.as_ref(), 0));
     }
@@ -201,7 +317,7 @@ impl<'a> VM<'a> {
         let mut ret = String::new();
         if let Some(value) = value {
             match value {
-                Value::Number(n) => ret.push_str(&format!("{}", n)),
+                Value::Number(n) => ret.push_str(&format_number(*n)),
                 Value::Nil => ret.push_str("nil"),
                 Value::Boolean(b) => ret.push_str(&format!("{}", b)),
                 Value::Obj { idx } => {
@@ -228,13 +344,57 @@ impl<'a> VM<'a> {
         ret
     }
     #[tracing::instrument()]
-    fn operands_match(&self) -> bool {
-        match (self.stack.back(), self.stack.get(self.stack.len() - 2)) {
+    fn operands_match(&self) -> Result<bool> {
+        if self.stack.len() < 2 {
+            return Err(self.runtime_error("Invalid operation, not enough operands"));
+        }
+        Ok(matches!(
+            (self.stack.back(), self.stack.get(self.stack.len() - 2)),
             (Some(Value::Number(_)), Some(Value::Number(_)))
-            | (Some(Value::Boolean(_)), Some(Value::Boolean(_)))
-            | (Some(Value::Nil), Some(Value::Nil))
-            | (Some(Value::Obj { .. }), Some(Value::Obj { .. })) => true,
+                | (Some(Value::Boolean(_)), Some(Value::Boolean(_)))
+                | (Some(Value::Nil), Some(Value::Nil))
+                | (Some(Value::Obj { .. }), Some(Value::Obj { .. }))
+        ))
+    }
+    /// Arithmetic needs two numbers, `+` also takes two
+    /// strings. The operands are still on the stack so
+    /// the error can name their types
+    #[tracing::instrument()]
+    fn check_arithmetic(&self, op: char) -> Result<()> {
+        let len = self.stack.len();
+        if len < 2 {
+            return self.runtime_err("Invalid operation, not enough operands");
+        }
+        let (lhs, rhs) = (self.stack[len - 2], self.stack[len - 1]);
+        let valid = match (lhs, rhs) {
+            (Value::Number(_), Value::Number(_)) => true,
+            (Value::Obj { idx: l }, Value::Obj { idx: r }) if op == '+' => matches!(
+                (&self.chunk.heap[l], &self.chunk.heap[r]),
+                (Obj::String(_), Obj::String(_))
+            ),
             _ => false,
+        };
+        if valid {
+            return Ok(());
+        }
+        let expected = if op == '+' { "two numbers or two strings" } else { "numbers" };
+        self.runtime_err(&format!(
+            "Operands of '{}' must be {}, found {} and {}",
+            op,
+            expected,
+            self.type_name(lhs),
+            self.type_name(rhs)
+        ))
+    }
+    /// Pop the two numbers `check_arithmetic` found, the
+    /// left hand side is the deeper of the two
+    #[tracing::instrument()]
+    fn pop_numbers(&mut self) -> Result<(f64, f64)> {
+        let rhs = self.pop_operand()?;
+        let lhs = self.pop_operand()?;
+        match (lhs, rhs) {
+            (Value::Number(l), Value::Number(r)) => Ok((l, r)),
+            _ => Err(self.runtime_error("Operands must be numbers")),
         }
     }
     #[tracing::instrument()]
@@ -250,20 +410,320 @@ impl<'a> VM<'a> {
     #[tracing::instrument()]
     fn runtime_error(&self, msg: &str) -> Error {
         eprintln!("{}", msg);
-        eprintln!(
-            "[line {}] in script",
-            self.chunk.lines.get_unchecked(self.ip)
-        );
+        eprintln!("[line {}] in script", self.current_line());
         Error::Runtime(msg.to_string())
     }
+    /// The source line of the instruction being run, `ip`
+    /// has already moved past it
+    fn current_line(&self) -> usize {
+        self.chunk.lines.get_unchecked(self.ip.saturating_sub(1))
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
+    fn run_chunk<'a>(chunk: Chunk<'a>) -> VM<'a> {
+        let mut vm = VM {
+            chunk,
+            ..VM::default()
+        };
+        vm.run().expect("failed to run chunk");
+        vm
+    }
+
+    fn compare_strings(lhs: &'static str, rhs: &'static str, op: OpCode) -> Option<Value> {
+        let mut chunk = Chunk::default();
+        let l_idx = chunk.add_obj(Obj::string(lhs));
+        let r_idx = chunk.add_obj(Obj::string(rhs));
+        let l = chunk.add_constant(Value::Obj { idx: l_idx });
+        let r = chunk.add_constant(Value::Obj { idx: r_idx });
+        chunk.write(OpCode::Constant { idx: l }, 1);
+        chunk.write(OpCode::Constant { idx: r }, 1);
+        chunk.write(op, 1);
+        run_chunk(chunk).stack.pop_back()
+    }
+
+    fn eval(source: &str) -> VM<'_> {
+        eval_chunk(crate::compile(source).unwrap())
+    }
+
+    /// Run `chunk` without its final `Return` so the
+    /// result is left on the stack
+    fn eval_chunk(mut chunk: Chunk<'_>) -> VM<'_> {
+        assert_eq!(chunk.code.pop(), Some(OpCode::Return));
+        run_chunk(chunk)
+    }
+
+    #[test]
+    fn table_round_trip() {
+        for (key, expected) in [("one", 1.0), ("two", 2.0), ("three", 3.0)].iter() {
+            let source = format!(r#"{{"one": 1, "two": 2, "three": 3}}["{}"]"#, key);
+            let mut vm = eval(&source);
+            assert_eq!(vm.stack.pop_back(), Some(Value::Number(*expected)));
+        }
+        let mut vm = eval(r#"{"one": 1}["missing"]"#);
+        assert_eq!(vm.stack.pop_back(), Some(Value::Nil));
+    }
+
+    #[test]
+    fn statements() {
+        let vm = run_chunk(crate::compile("1; 2; print 3;").unwrap());
+        assert!(vm.stack.is_empty(), "{:?}", vm.stack);
+        let mut vm = eval("print 1; 2 + 3");
+        assert_eq!(vm.stack.pop_back(), Some(Value::Number(5.0)));
+        assert!(vm.stack.is_empty(), "{:?}", vm.stack);
+    }
+
+    #[test]
+    fn comma() {
+        let mut vm = eval("1, 2, 3");
+        assert_eq!(vm.stack.pop_back(), Some(Value::Number(3.0)));
+        assert!(vm.stack.is_empty());
+    }
+
+    #[test]
+    fn ternary() {
+        for (source, expected) in [
+            ("true ? 1 : 2", 1.0),
+            ("nil ? 1 : 2", 2.0),
+            ("0 ? 1 : 2", 1.0),
+            ("false ? 1 : true ? 2 : 3", 2.0),
+            ("false ? 1 : false ? 2 : 3", 3.0),
+        ].iter() {
+            let mut vm = eval(source);
+            assert_eq!(vm.stack.pop_back(), Some(Value::Number(*expected)), "{}", source);
+            assert!(vm.stack.is_empty(), "{}", source);
+        }
+        let mut vm = eval(r#"{"a": true ? 1 : 2, "b": 3}["a"]"#);
+        assert_eq!(vm.stack.pop_back(), Some(Value::Number(1.0)));
+    }
+
+    #[test]
+    fn table_set() {
+        let mut vm = eval(r#"{"one": 1, "two": 2}["three"] = 3"#);
+        assert_eq!(vm.stack.pop_back(), Some(Value::Number(3.0)));
+        let entries = vm
+            .chunk
+            .heap
+            .iter()
+            .find_map(|obj| match obj {
+                Obj::HashTable { entries } => Some(entries),
+                _ => None,
+            })
+            .expect("no table on the heap");
+        assert_eq!(entries.len(), 3);
+        for (key, expected) in [("one", 1.0), ("two", 2.0), ("three", 3.0)].iter() {
+            assert_eq!(entries.get(*key), Some(&Value::Number(*expected)));
+        }
+    }
+
+    fn equality(source: &str) -> Option<Value> {
+        eval(source).stack.pop_back()
+    }
+
+    #[test]
+    fn deserialized_chunk() {
+        let bytes = crate::compile(r#"{"a": -1 + -2}["a"] == -3 ? "yes" : "no""#)
+            .unwrap()
+            .serialize();
+        let mut vm = eval_chunk(Chunk::deserialize(&bytes).unwrap());
+        let idx = match vm.stack.pop_back() {
+            Some(Value::Obj { idx }) => idx,
+            other => panic!("expected a string, found {:?}", other),
+        };
+        assert_eq!(vm.chunk.heap[idx], Obj::string("yes"));
+    }
+
+    #[test]
+    fn missing_operands() {
+        for op in [OpCode::Add, OpCode::Less, OpCode::Negate, OpCode::Print].iter() {
+            let mut chunk = Chunk::default();
+            chunk.write(*op, 1);
+            chunk.write(OpCode::Return, 1);
+            let chunk = Chunk::deserialize(&chunk.serialize()).unwrap();
+            match VM::default().interpret_chunk(chunk) {
+                Err(Error::Runtime(msg)) => {
+                    assert_eq!(msg, "Invalid operation, not enough operands")
+                }
+                other => panic!("expected {:?} to fail, found {:?}", op, other),
+            }
+        }
+        let mut chunk = Chunk::default();
+        chunk.write(OpCode::Table, 1);
+        chunk.write(OpCode::Table, 1);
+        chunk.write(OpCode::Add, 1);
+        assert!(VM::default().interpret_chunk(chunk).is_err());
+    }
+
+    #[test]
+    fn interned_strings() {
+        let vm = eval(r#""ab" == "ab""#);
+        assert_eq!(vm.chunk.heap, vec![Obj::string("ab")]);
+        let mut vm = eval(r#""a" + "a""#);
+        let idx = match vm.stack.pop_back() {
+            Some(Value::Obj { idx }) => idx,
+            other => panic!("expected a string, found {:?}", other),
+        };
+        assert_eq!(vm.chunk.interned("aa"), Some(idx));
+        assert_eq!(vm.chunk.add_obj(Obj::string("aa")), idx);
+        assert_eq!(equality(r#""a" + "a" == "aa""#), Some(Value::Boolean(true)));
+    }
+
+    #[test]
+    fn equality_same_type() {
+        assert_eq!(equality("nil == nil"), Some(Value::Boolean(true)));
+        assert_eq!(equality("nil != nil"), Some(Value::Boolean(false)));
+        assert_eq!(equality("true == false"), Some(Value::Boolean(false)));
+        assert_eq!(equality("true != false"), Some(Value::Boolean(true)));
+        assert_eq!(equality("false == false"), Some(Value::Boolean(true)));
+        assert_eq!(equality("1 == 1"), Some(Value::Boolean(true)));
+        assert_eq!(equality(r#""a" == "a""#), Some(Value::Boolean(true)));
+        assert_eq!(equality(r#""a" != "b""#), Some(Value::Boolean(true)));
+        assert_eq!(equality("{} == {}"), Some(Value::Boolean(false)));
+    }
+
+    #[test]
+    fn unary_operands() {
+        assert_eq!(equality("!0"), Some(Value::Boolean(false)));
+        assert_eq!(equality(r#"!"""#), Some(Value::Boolean(false)));
+        assert_eq!(equality("!{}"), Some(Value::Boolean(false)));
+        assert_eq!(equality("!nil"), Some(Value::Boolean(true)));
+        assert_eq!(equality("!false"), Some(Value::Boolean(true)));
+        assert_eq!(equality("!!1"), Some(Value::Boolean(true)));
+        assert_eq!(equality("-(1)"), Some(Value::Number(-1.0)));
+        for (source, found) in [(r#"-"x""#, "string"), ("-nil", "nil"), ("-{}", "table")].iter() {
+            match VM::default().interpret(source) {
+                Err(Error::Runtime(msg)) => assert_eq!(
+                    msg,
+                    format!("Operand of '-' must be a number, found {}", found)
+                ),
+                other => panic!("expected a runtime error for {}, found {:?}", source, other),
+            }
+        }
+    }
+
+    #[test]
+    fn arithmetic_operands() {
+        let sources = [
+            ("true + true", "'+' must be two numbers or two strings, found boolean and boolean"),
+            ("nil + nil", "'+' must be two numbers or two strings, found nil and nil"),
+            (r#"1 + "a""#, "'+' must be two numbers or two strings, found number and string"),
+            ("{} + {}", "'+' must be two numbers or two strings, found table and table"),
+            ("nil - nil", "'-' must be numbers, found nil and nil"),
+            (r#""a" * "b""#, "'*' must be numbers, found string and string"),
+            ("1 / false", "'/' must be numbers, found number and boolean"),
+        ];
+        for (source, msg) in sources.iter() {
+            match VM::default().interpret(source) {
+                Err(Error::Runtime(found)) => assert_eq!(found, format!("Operands of {}", msg)),
+                other => panic!("expected a runtime error for {}, found {:?}", source, other),
+            }
+        }
+        assert_eq!(equality("7 - 2 * 3 / -(1)"), Some(Value::Number(13.0)));
+    }
+
+    #[test]
+    fn equality_cross_type() {
+        assert_eq!(equality(r#"1 == "1""#), Some(Value::Boolean(false)));
+        assert_eq!(equality(r#"1 != "1""#), Some(Value::Boolean(true)));
+        assert_eq!(equality("nil == false"), Some(Value::Boolean(false)));
+        assert_eq!(equality("0 == nil"), Some(Value::Boolean(false)));
+        assert_eq!(equality("true == 1"), Some(Value::Boolean(false)));
+        assert_eq!(equality(r#""" == nil"#), Some(Value::Boolean(false)));
+        assert_eq!(equality(r#"{} == "table""#), Some(Value::Boolean(false)));
+    }
+
+    #[test]
+    fn computed_number_output() {
+        let mut vm = eval("10 / 2");
+        let value = vm.stack.pop_back();
+        assert_eq!(vm.format_value(value.as_ref(), 0), "5");
+        let mut vm = eval("10 / 3");
+        let value = vm.stack.pop_back();
+        assert_eq!(vm.format_value(value.as_ref(), 0), "3.3333333333333335");
+    }
+
+    #[test]
+    fn number_output() {
+        // matches the output of `print 3.0;` and `print 0.5;` in roxi
+        let vm = VM::default();
+        assert_eq!(vm.format_value(Some(&Value::Number(3.0)), 0), "3");
+        assert_eq!(vm.format_value(Some(&Value::Number(0.5)), 0), "0.5");
+    }
+
     #[test]
     fn strings() {
-        
+        assert_eq!(compare_strings("a", "b", OpCode::Less), Some(Value::Boolean(true)));
+        assert_eq!(compare_strings("b", "a", OpCode::Less), Some(Value::Boolean(false)));
+        assert_eq!(compare_strings("b", "a", OpCode::Gtr), Some(Value::Boolean(true)));
+        assert_eq!(compare_strings("a", "b", OpCode::Gtr), Some(Value::Boolean(false)));
+        assert_eq!(compare_strings("a", "a", OpCode::Less), Some(Value::Boolean(false)));
+        assert_eq!(compare_strings("abc", "abd", OpCode::Less), Some(Value::Boolean(true)));
+    }
+
+    #[test]
+    fn numbers() {
+        let mut chunk = Chunk::default();
+        let one = chunk.add_constant(Value::Number(1.0));
+        let two = chunk.add_constant(Value::Number(2.0));
+        chunk.write(OpCode::Constant { idx: one }, 1);
+        chunk.write(OpCode::Constant { idx: two }, 1);
+        chunk.write(OpCode::Less, 1);
+        assert_eq!(run_chunk(chunk).stack.pop_back(), Some(Value::Boolean(true)));
+        // the folded -0.0 keeps its sign in the constant table
+        let mut vm = eval("1 / (0 * (0 - 1))");
+        assert_eq!(vm.stack.pop_back(), Some(Value::Number(f64::NEG_INFINITY)));
+    }
+
+    #[test]
+    fn loop_sum() {
+        let sources = [
+            "var sum = 0;\nvar i = 10;\nwhile (i > 0) sum = sum + i, i = i - 1;\nsum",
+            "var sum = 0;\nfor (var i = 1; i <= 10; i = i + 1) sum = sum + i;\nsum",
+            "var sum = 0;\nvar i = 0;\nfor (; i < 10;) i = i + 1, sum = sum + i;\nsum",
+        ];
+        for source in sources.iter() {
+            let chunk = crate::compile(source).unwrap();
+            let bytes = chunk.serialize();
+            let mut vm = eval_chunk(chunk);
+            assert_eq!(vm.stack.pop_back(), Some(Value::Number(55.0)), "{}", source);
+            assert!(vm.stack.is_empty(), "{:?}", vm.stack);
+            let mut vm = eval_chunk(Chunk::deserialize(&bytes).unwrap());
+            assert_eq!(vm.stack.pop_back(), Some(Value::Number(55.0)), "{}", source);
+        }
+    }
+
+    #[test]
+    fn globals() {
+        let mut vm = eval("var a;\nvar b = 1;\na = b = b + 1;\na");
+        assert_eq!(vm.stack.pop_back(), Some(Value::Number(2.0)));
+        assert_eq!(vm.globals.get("b"), Some(&Value::Number(2.0)));
+        for source in ["print missing;", "missing = 1;"].iter() {
+            let chunk = crate::compile(source).unwrap();
+            match VM::default().interpret_chunk(chunk) {
+                Err(Error::Runtime(msg)) => assert_eq!(msg, "Undefined variable 'missing'"),
+                other => panic!("expected {} to fail, found {:?}", source, other),
+            }
+        }
+    }
+
+    #[test]
+    fn unordered() {
+        let mut chunk = Chunk::default();
+        chunk.write(OpCode::True, 1);
+        chunk.write(OpCode::False, 1);
+        chunk.write(OpCode::Gtr, 1);
+        assert_eq!(run_chunk(chunk).stack.pop_back(), Some(Value::Boolean(false)));
+    }
+
+    #[test]
+    fn runtime_error_line() {
+        let mut vm = VM::default();
+        assert!(vm.interpret("1 +\n2\n-\ntrue\n+ 3").is_err());
+        assert_eq!(vm.current_line(), 3);
+        assert!(vm.interpret("-\n\n(nil)").is_err());
+        assert_eq!(vm.current_line(), 1);
     }
 }
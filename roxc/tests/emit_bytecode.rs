@@ -0,0 +1,44 @@
+use std::process::Command;
+
+#[test]
+fn emit_bytecode() {
+    let dir = std::env::temp_dir().join(format!("roxc-emit-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("add.lox");
+    // negated operands keep the arithmetic from being folded
+    std::fs::write(&path, "-1 + 2 * -3").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_roxc"))
+        .arg("--emit-bytecode")
+        .arg(&path)
+        .output()
+        .expect("failed to run roxc");
+    let _ = std::fs::remove_dir_all(&dir);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("== "), "missing header: {}", stdout);
+    assert!(stdout.contains("Constant"), "missing Constant: {}", stdout);
+    assert!(stdout.contains("Mul"), "missing Mul: {}", stdout);
+    assert!(stdout.contains("Add"), "missing Add: {}", stdout);
+    assert!(stdout.contains("Return"), "missing Return: {}", stdout);
+    // compiling shouldn't run the program and print its result
+    assert!(!stdout.lines().any(|l| l.trim() == "7"), "{}", stdout);
+}
+
+#[test]
+fn compile_errors() {
+    let dir = std::env::temp_dir().join(format!("roxc-emit-err-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("bad.lox");
+    std::fs::write(&path, "print 1 +;").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_roxc"))
+        .arg("--emit-bytecode")
+        .arg(&path)
+        .output()
+        .expect("failed to run roxc");
+    let _ = std::fs::remove_dir_all(&dir);
+    assert_eq!(output.status.code(), Some(65));
+    // nothing is disassembled for a chunk that failed to compile
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Expect prefix expression"), "{}", stderr);
+}
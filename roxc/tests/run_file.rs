@@ -0,0 +1,24 @@
+use std::process::{Command, Output};
+
+fn run(name: &str, source: &str) -> Output {
+    let dir = std::env::temp_dir().join(format!("roxc-run-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("main.lox");
+    std::fs::write(&path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_roxc"))
+        .arg(&path)
+        .output()
+        .expect("failed to run roxc");
+    let _ = std::fs::remove_dir_all(&dir);
+    output
+}
+
+#[test]
+fn only_print_writes_output() {
+    let output = run("silent", "1 + 2;\n3 * 4");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+    let output = run("print", "1 + 2;\nprint 3 * 4;\nprint nil;");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "12\nnil\n");
+}
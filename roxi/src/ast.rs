@@ -0,0 +1,30 @@
+//! The parse tree produced by the `Parser`, for tools
+//! that want to work with Lox source without running it
+//!
+//! ```
+//! use roxi::{
+//!     ast::{Expr, Literal, Parser, Stmt},
+//!     Scanner,
+//! };
+//!
+//! let scanner = Scanner::new("var answer = 42;".to_string()).unwrap();
+//! let (stmts, errors) = Parser::new(scanner).parse_all();
+//! assert!(errors.is_empty());
+//! match &stmts[0] {
+//!     Stmt::Var {
+//!         name,
+//!         value: Some(Expr::Literal(Literal::Number(n), _)),
+//!         ..
+//!     } => {
+//!         assert_eq!(name, "answer");
+//!         assert_eq!(*n, 42.0);
+//!     }
+//!     other => panic!("unexpected statement {:?}", other),
+//! }
+//! ```
+pub use crate::{
+    expr::{Binding, Expr, Literal, Span},
+    parser::Parser,
+    printer::Printer,
+    stmt::{Function, Stmt},
+};
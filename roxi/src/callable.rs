@@ -11,5 +11,18 @@ where
     fn arity(&self) -> usize {
         0
     }
+    /// The most arguments this accepts, anything
+    /// past `arity` is optional
+    fn max_arity(&self) -> usize {
+        self.arity()
+    }
+    /// Whether any number of arguments past `arity` is
+    /// accepted, `max_arity` is ignored when this is set
+    fn is_variadic(&self) -> bool {
+        false
+    }
+    /// Run this callable. `int` isn't borrowed by anything else
+    /// while this runs, so it can be used to call other values
+    /// with `Interpreter::call_value`, as many times as needed
     fn call(&mut self, int: &mut Interpreter, args: &[Value]) -> Result<Value, Error>;
 }
@@ -1,31 +1,37 @@
 use crate::{
-    callable::Callable, error::Error, func::Func, interpreter::Interpreter, stmt::Function,
-    value::Value,
+    callable::Callable, env::Env, error::Error, func::Func, interpreter::Interpreter,
+    ordered_map::OrderedMap, stmt::Function, value::Value,
 };
-use std::collections::HashMap;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 #[derive(Clone, Debug)]
 pub struct Class {
     pub name: String,
-    pub methods: Vec<Function>,
-    pub env_idx: usize,
+    /// Methods not defined here are looked up on this
+    pub super_class: Option<Rc<Class>>,
+    /// Shared between copies of this class, which also
+    /// gives it an identity for `==`
+    pub methods: Rc<Vec<Function>>,
+    pub env: Env,
 }
-#[derive(Clone, Debug)]
+/// Instances are shared, every value referring to
+/// one sees changes made through any of the others
+#[derive(Clone)]
 pub struct ClassInstance {
     pub class: Class,
-    pub fields: HashMap<String, Value>,
-    pub methods: HashMap<String, Method>,
+    pub fields: OrderedMap<Value>,
+    pub methods: HashMap<String, Func>,
 }
 
+/// A method looked up on an instance, bound to that instance
 #[derive(Clone, Debug)]
 pub struct Method {
     pub func: Func,
-    pub this_depth: usize,
-    pub this_name: String,
+    pub this: Rc<RefCell<ClassInstance>>,
 }
 
 impl std::fmt::Display for Method {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}.{}", self.this_name, self.func.name)
+        write!(f, "{}.{}", self.this.borrow().class.name, self.func.name)
     }
 }
 
@@ -33,49 +39,29 @@ impl Callable for Class {
     fn name(&self) -> &str {
         &self.name
     }
+    /// Takes whatever `init` takes, which may be inherited
     fn arity(&self) -> usize {
-        0
+        self.find_method("init")
+            .map(|init| init.params.len())
+            .unwrap_or(0)
     }
     fn call(&mut self, int: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
-        let mut methods = HashMap::new();
-        let mut init: Option<Method> = None;
-        for def in &self.methods {
-            let func = Func {
-                name: def.name.to_string(),
-                params: def.params.clone(),
-                body: def.body.clone(),
-                env: int.env.clone(),
-                env_idx: self.env_idx,
-            };
-            let meth = Method {
-                func,
-                this_depth: int.env.depth(),
-                this_name: String::new(), // will get replaced at caller
-            };
-            if meth.func.name == "init" {
-                init = Some(meth)
-            } else {
-                methods.insert(def.name.to_string(), meth);
-            }
-        }
-        let ret = ClassInstance {
-            fields: HashMap::new(),
+        let mut methods = self.bound_methods();
+        let init = methods.remove("init");
+        let ret = Rc::new(RefCell::new(ClassInstance {
+            fields: OrderedMap::default(),
             class: self.clone(),
             methods,
-        };
+        }));
 
-        if let Some(mut init) = init {
-            int.env.descend();
-            init.this_name = "*".to_string();
-            init.this_depth = int.env.depth();
-            int.env.define("*", Some(Value::Class(ret)));
+        if let Some(func) = init {
+            let mut init = Method {
+                func,
+                this: ret.clone(),
+            };
             init.call(int, args)?;
-            let updated = int.env.get("*")?;
-            int.env.ascend();
-            Ok(updated)
-        } else {
-            Ok(Value::Class(ret))
         }
+        Ok(Value::Class(ret))
     }
 }
 
@@ -87,21 +73,48 @@ impl Callable for Method {
         self.func.arity()
     }
     fn call(&mut self, int: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
-        let this = int.env.get(&self.this_name)?;
-        int.env.descend();
-        int.env.define("this", Some(this));
-        for (name, value) in self.func.params.iter().zip(args.iter().cloned()) {
-            int.env.define(name, Some(value));
-        }
-        let ret = match int.execute_block(&mut self.func.body) {
-            Ok(_) => Ok(Value::Nil),
-            Err(Error::Return(v)) => Ok(v),
-            Err(e) => Err(e),
+        self.func
+            .bind(Value::Class(self.this.clone()))
+            .call(int, args)
+    }
+}
+
+impl Class {
+    /// The definition of `name` on this class or the
+    /// closest superclass that has one
+    fn find_method(&self, name: &str) -> Option<&Function> {
+        self.methods
+            .iter()
+            .find(|def| def.name == name)
+            .or_else(|| self.super_class.as_ref()?.find_method(name))
+    }
+    /// Every method an instance of this class has, including
+    /// the ones inherited from its superclasses
+    fn bound_methods(&self) -> HashMap<String, Func> {
+        let mut methods = match &self.super_class {
+            Some(super_class) => super_class.bound_methods(),
+            None => HashMap::new(),
         };
-        let updated_this = int.env.get("this")?;
-        int.env.ascend();
-        int.env.assign(&self.this_name, updated_this)?;
-        ret
+        for def in self.methods.iter() {
+            methods.insert(def.name.to_string(), self.func(def));
+        }
+        methods
+    }
+    /// The closest definition of `name`, closed over the
+    /// environment of the class that defines it
+    pub fn bound_method(&self, name: &str) -> Option<Func> {
+        match self.methods.iter().find(|def| def.name == name) {
+            Some(def) => Some(self.func(def)),
+            None => self.super_class.as_ref()?.bound_method(name),
+        }
+    }
+    fn func(&self, def: &Function) -> Func {
+        Func {
+            name: def.name.to_string(),
+            params: def.params.clone(),
+            body: def.body.clone(),
+            env: self.env.clone(),
+        }
     }
 }
 
@@ -111,37 +124,39 @@ impl ::std::fmt::Display for Class {
     }
 }
 
+/// Only the field names are written since an instance
+/// can hold a reference to itself
+impl ::std::fmt::Debug for ClassInstance {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        let fields: Vec<&String> = self.fields.iter().map(|(k, _)| k).collect();
+        f.debug_struct("ClassInstance")
+            .field("class", &self.class.name)
+            .field("fields", &fields)
+            .finish()
+    }
+}
+
 impl ClassInstance {
-    pub fn get(&self, key: &str) -> Result<Value, Error> {
-        if let Some(val) = self.fields.get(key) {
+    /// Look up a field or a method bound to `this`
+    pub fn get(this: &Rc<RefCell<Self>>, key: &str) -> Result<Value, Error> {
+        let inst = this.borrow();
+        if let Some(val) = inst.fields.get(key) {
             Ok(val.clone())
-        } else if let Some(val) = self.methods.get(key) {
-            Ok(Value::Method(val.clone()))
+        } else if let Some(func) = inst.methods.get(key) {
+            Ok(Value::Method(Method {
+                func: func.clone(),
+                this: this.clone(),
+            }))
         } else {
             Err(Error::Runtime(format!(
                 "Undefined propety on {} instance: {}",
-                self.class.name(),
+                inst.class.name(),
                 key
             )))
         }
     }
 
-    pub fn get_mut(&mut self, key: &str) -> Result<&mut Value, Error> {
-        if let Some(val) = self.fields.get_mut(key) {
-            Ok(val)
-        } else {
-            Err(Error::Runtime(format!(
-                "Undefined propety on {} instance: {}",
-                self.class.name(),
-                key
-            )))
-        }
-    }
     pub fn set(&mut self, key: &str, value: Value) {
-        if let Some(val) = self.fields.get_mut(key) {
-            *val = value;
-        } else {
-            self.fields.insert(key.to_string(), value);
-        }
+        self.fields.insert(key.to_string(), value);
     }
 }
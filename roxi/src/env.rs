@@ -1,107 +1,203 @@
-use crate::{error::Error, value::Value};
-use hash_chain::ChainMap;
-
-#[derive(Debug, Clone)]
-pub struct EnvStub {
-    pub values: ChainMap<String, Value>,
-    pub idx: usize,
+use crate::{error::Error, expr::Binding, value::Value};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+/// The variables defined in one block along with
+/// the scope the block is nested in
+#[derive(Default)]
+struct Scope {
+    values: HashMap<String, Value>,
+    parent: Option<Rc<RefCell<Scope>>>,
 }
 
-#[derive(Debug, Clone)]
+/// The builtins and the program's top level scope
+const GLOBAL_DEPTH: usize = 2;
+
+/// The innermost open scope. Cloning an `Env` shares the
+/// scopes it points to, so a closure holding a clone sees
+/// (and makes) changes to the variables that were in
+/// scope where it was created
+#[derive(Clone)]
 pub struct Env {
-    values: ChainMap<String, Value>,
+    scope: Rc<RefCell<Scope>>,
+    depth: usize,
 }
 
 impl Env {
     pub fn depth(&self) -> usize {
-        self.values.child_len()
+        self.depth
+    }
+
+    /// If both point to the same innermost scope
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.scope, &other.scope)
     }
 
     pub fn root() -> Self {
         let mut start = Self::global();
-        start.values.new_child();
+        start.descend();
         start
     }
 
     fn global() -> Self {
-        let mut values = ChainMap::default();
-        values.insert(String::from("clock"), Value::clock());
-        values.insert(String::from("mod"), Value::modulo());
-        let mut ret = Self::new(0);
-        ret.values = values;
+        let mut ret = Self::new(1);
+        ret.define("clock", Some(Value::clock()));
+        ret.define("mod", Some(Value::modulo()));
+        ret.define("to_number", Some(Value::to_number()));
+        ret.define("to_string", Some(Value::to_string()));
+        ret.define("floor", Some(Value::floor()));
+        ret.define("ceil", Some(Value::ceil()));
+        ret.define("round", Some(Value::round()));
+        ret.define("abs", Some(Value::abs()));
+        ret.define("weak", Some(Value::weak()));
+        ret.define("read_line", Some(Value::read_line()));
+        ret.define("memoize", Some(Value::memoize()));
+        ret.define("assert", Some(Value::assert()));
+        ret.define("to_base", Some(Value::to_base()));
+        ret.define("source", Some(Value::source()));
+        ret.define("new_map", Some(Value::new_map()));
+        ret.define("partial_right", Some(Value::partial_right()));
+        ret.define("printf", Some(Value::printf()));
+        ret.define("len", Some(Value::len()));
+        ret.define("has_field", Some(Value::has_field()));
+        ret.define("delete_field", Some(Value::delete_field()));
+        ret.define("times", Some(Value::times()));
+        ret.define("fields", Some(Value::fields()));
+        ret.define("to_json", Some(Value::to_json()));
+        ret.define("typeof", Some(Value::type_of()));
+        ret.define("exit", Some(Value::exit()));
         ret
     }
 
     pub fn new(depth: usize) -> Self {
-        let mut values = ChainMap::default();
-        for _ in 0..depth {
-            values.new_child()
+        let mut ret = Self {
+            scope: Default::default(),
+            depth: 1,
+        };
+        for _ in 1..depth {
+            ret.descend()
         }
-        Self { values }
+        ret
     }
 
     pub fn descend(&mut self) {
         log::trace!("decending from, {} {:?}", self.depth(), self);
-        self.values.new_child()
+        let parent = self.scope.clone();
+        self.scope = Rc::new(RefCell::new(Scope {
+            values: HashMap::new(),
+            parent: Some(parent),
+        }));
+        self.depth += 1;
     }
 
     pub fn ascend(&mut self) {
         log::trace!("ascending from, {}", self.depth());
-        let _ = self.values.remove_child();
+        let parent = self.scope.borrow().parent.clone();
+        if let Some(parent) = parent {
+            self.scope = parent;
+            self.depth -= 1;
+        }
+    }
+
+    /// Replace the innermost scope with a copy of itself,
+    /// anything holding on to the old one keeps its values
+    pub fn fork(&mut self) {
+        let copy = {
+            let scope = self.scope.borrow();
+            Scope {
+                values: scope.values.clone(),
+                parent: scope.parent.clone(),
+            }
+        };
+        self.scope = Rc::new(RefCell::new(copy));
     }
 
     pub fn assign(&mut self, s: &str, new: Value) -> Result<Value, Error> {
-        let old = self.get_mut(s)?;
-        let _ = std::mem::replace(old, new.clone());
+        let scope = self.find(s)?;
+        scope.borrow_mut().values.insert(s.to_string(), new.clone());
         Ok(new)
     }
 
     pub fn define(&mut self, s: &str, val: Option<Value>) {
-        let resolved = val.unwrap_or_else(|| Value::Nil);
-        self.values.insert(s.to_string(), resolved);
+        let resolved = val.unwrap_or(Value::Nil);
+        self.scope
+            .borrow_mut()
+            .values
+            .insert(s.to_string(), resolved);
     }
 
     pub fn get(&self, s: &str) -> Result<Value, Error> {
         log::trace!("get {:?} {}", s, self.depth());
-        if let Some(val) = self.values.get(s) {
-            Ok(val.clone())
-        } else {
-            Err(Error::Runtime(format!(
-                "variable {:?} is not yet defined",
-                s
-            )))
-        }
+        let scope = self.find(s)?;
+        let val = scope.borrow().values[s].clone();
+        Ok(val)
     }
 
-    pub fn get_mut(&mut self, s: &str) -> Result<&mut Value, Error> {
-        if let Some(val) = self.values.get_mut(s) {
-            Ok(val)
-        } else {
-            Err(Error::Runtime(format!(
-                "variable {:?} is not yet defined",
-                s
-            )))
-        }
+    /// Look `s` up in the program's top level scope or the
+    /// builtins, ignoring any scopes that are currently open
+    pub fn get_global(&self, s: &str) -> Option<Value> {
+        self.scopes()
+            .skip(self.depth.saturating_sub(GLOBAL_DEPTH))
+            .find_map(|scope| scope.borrow().values.get(s).cloned())
     }
 
-    pub fn split_to_base(&mut self) -> Self {
-        self.split(2)
+    /// Look `s` up where the resolver found it
+    pub fn get_bound(&self, s: &str, binding: Binding) -> Result<Value, Error> {
+        let scope = self.find_bound(s, binding)?;
+        let val = scope.borrow().values[s].clone();
+        Ok(val)
     }
 
-    pub fn split(&mut self, idx: usize) -> Self {
-        let mut values = self.values.split_off(idx);
-        if values.child_len() == 0 {
-            values.new_child()
-        }
-        Self { values }
+    /// Assign to `s` where the resolver found it
+    pub fn assign_bound(&mut self, s: &str, binding: Binding, new: Value) -> Result<Value, Error> {
+        let scope = self.find_bound(s, binding)?;
+        scope.borrow_mut().values.insert(s.to_string(), new.clone());
+        Ok(new)
+    }
+
+    /// The scope `binding` points to, which has to define `s`
+    /// by now. Unresolved names fall back to `find`
+    fn find_bound(&self, s: &str, binding: Binding) -> Result<Rc<RefCell<Scope>>, Error> {
+        let scope = match binding {
+            Binding::Unresolved => return self.find(s),
+            Binding::Local(distance) => self.scopes().nth(distance),
+            Binding::Global => self
+                .scopes()
+                .skip(self.depth.saturating_sub(GLOBAL_DEPTH))
+                .find(|scope| scope.borrow().values.contains_key(s)),
+        };
+        scope
+            .filter(|scope| scope.borrow().values.contains_key(s))
+            .ok_or_else(|| Error::Runtime(format!("variable {:?} is not yet defined", s)))
+    }
+
+    /// The innermost scope defining `s`
+    fn find(&self, s: &str) -> Result<Rc<RefCell<Scope>>, Error> {
+        self.scopes()
+            .find(|scope| scope.borrow().values.contains_key(s))
+            .ok_or_else(|| Error::Runtime(format!("variable {:?} is not yet defined", s)))
     }
 
-    pub fn append(&mut self, mut other: Self) {
-        self.values.append(&mut other.values)
+    /// Every open scope, innermost first
+    fn scopes(&self) -> impl Iterator<Item = Rc<RefCell<Scope>>> {
+        std::iter::successors(Some(self.scope.clone()), |scope| {
+            scope.borrow().parent.clone()
+        })
     }
+}
 
-    pub fn clone_to_base(&self) -> Self {
-        let mut other = self.clone();
-        other.split(2)
+/// Only the names in each scope are written, innermost
+/// first, since a closure in a scope holds a reference
+/// back to it
+impl ::std::fmt::Debug for Env {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        let scopes: Vec<Vec<String>> = self
+            .scopes()
+            .map(|scope| {
+                let mut names: Vec<String> = scope.borrow().values.keys().cloned().collect();
+                names.sort();
+                names
+            })
+            .collect();
+        f.debug_struct("Env").field("scopes", &scopes).finish()
     }
 }
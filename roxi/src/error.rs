@@ -1,19 +1,79 @@
+use crate::expr::Span;
+use rox_shared::{ScannerError, Token};
+
 #[derive(Clone, Debug)]
 pub enum Error {
-    Scanner(String),
+    Scanner(ScannerError),
     Parser(String),
     Resolution(String),
     Runtime(String),
     Return(crate::value::Value),
+    /// `exit` was called, the whole program stops with this code
+    Exit(i32),
+    Located {
+        line: usize,
+        column: usize,
+        error: Box<Error>,
+    },
 }
+
+impl Error {
+    /// Attach the position of `token` to this error
+    pub fn at(self, token: &Token) -> Self {
+        self.at_position(token.line, token.column)
+    }
+
+    /// Attach the start of `span` to this error
+    pub fn at_span(self, span: Span) -> Self {
+        self.at_position(span.line, span.column)
+    }
+
+    /// Attach a source position to this error, errors that
+    /// already have one keep their original position
+    pub fn at_position(self, line: usize, column: usize) -> Self {
+        match self {
+            Error::Located { .. } | Error::Return(_) | Error::Exit(_) => self,
+            _ => Error::Located {
+                line,
+                column,
+                error: Box::new(self),
+            },
+        }
+    }
+
+    /// The `(line, column)` this error originated from, if known
+    pub fn position(&self) -> Option<(usize, usize)> {
+        if let Error::Located { line, column, .. } = self {
+            Some((*line, *column))
+        } else {
+            None
+        }
+    }
+
+    /// This error without any position information
+    pub fn inner(&self) -> &Error {
+        if let Error::Located { error, .. } = self {
+            error.inner()
+        } else {
+            self
+        }
+    }
+}
+
 impl ::std::fmt::Display for Error {
     fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
         match self {
             Error::Scanner(s) => format!("Scanning error: {}", s).fmt(f),
             Error::Parser(s) => format!("Parser error: {}", s).fmt(f),
-            Error::Resolution(s) => format!("Runtime error: {}", s).fmt(f),
+            Error::Resolution(s) => format!("Resolution error: {}", s).fmt(f),
             Error::Runtime(s) => format!("Runtime error: {}", s).fmt(f),
             Error::Return(v) => v.fmt(f),
+            Error::Exit(code) => write!(f, "exited with code {}", code),
+            Error::Located {
+                line,
+                column,
+                error,
+            } => write!(f, "[line {}:{}] {}", line, column, error),
         }
     }
 }
@@ -1,44 +1,133 @@
 use crate::{error::Error, value::Value};
-use rox_shared::Token;
-#[derive(Debug, Clone)]
+use rox_shared::{format_number, Token};
+use std::cell::Cell;
+#[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
     Binary {
         left: Box<Expr>,
         operator: Token,
         right: Box<Expr>,
+        span: Span,
     },
-    Grouping(Box<Expr>),
-    Literal(Literal),
+    Grouping(Box<Expr>, Span),
+    Literal(Literal, Span),
     Unary {
         operator: Token,
         right: Box<Expr>,
+        span: Span,
     },
-    Var(String),
+    Var(String, Span, Cell<Binding>),
     Assign {
         name: String,
         value: Box<Expr>,
+        span: Span,
+        binding: Cell<Binding>,
     },
     Log {
         left: Box<Expr>,
         operator: Token,
         right: Box<Expr>,
+        span: Span,
     },
     Call {
         callee: Box<Expr>,
+        /// The closing `)`, where errors from the call are reported
+        paren: Token,
         arguments: Vec<Expr>,
+        span: Span,
     },
     Get {
         object: Box<Expr>,
         name: String,
+        span: Span,
+    },
+    /// `object?.name`, which is `nil` when `object` is
+    OptionalGet {
+        object: Box<Expr>,
+        name: String,
+        span: Span,
     },
     Set {
         object: Box<Expr>,
         name: String,
         value: Box<Expr>,
+        span: Span,
     },
-    This,
+    /// `object.name += value` and the other compound
+    /// assignments, `operator` is the binary operator applied
+    CompoundSet {
+        object: Box<Expr>,
+        name: String,
+        operator: Token,
+        value: Box<Expr>,
+        span: Span,
+    },
+    This(Span),
+    /// `target++` or `target--`, which evaluates to the
+    /// value `target` had before it was updated
+    Postfix {
+        target: Box<Expr>,
+        operator: Token,
+        span: Span,
+    },
+    /// `super.method`, the superclass's version of `method`
+    /// bound to the current `this`
+    Super {
+        method: String,
+        span: Span,
+    },
+    /// A string with `${}` expressions embedded in it, the
+    /// parts alternate between string literals and expressions
+    Interpolation(Vec<Expr>, Span),
+}
+
+/// Which scope a variable refers to, filled in by the
+/// `Resolver`. Until it has run variables are looked up
+/// by name in every open scope. Kept in a `Cell` so the
+/// tree can be walked through a shared reference
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Binding {
+    #[default]
+    Unresolved,
+    /// Defined this many scopes out from where it is used
+    Local(usize),
+    /// Defined at the top level or a builtin
+    Global,
+}
+
+/// The start and end offsets of an expression in the source,
+/// taken from the tokens it was parsed from, along with the
+/// line and column it starts at for reporting errors
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    /// A span from the start of this one to the end of `other`
+    pub fn to(self, other: Span) -> Self {
+        Self {
+            end: other.end,
+            ..self
+        }
+    }
 }
-#[derive(Debug, Clone)]
+
+impl From<&Token> for Span {
+    fn from(token: &Token) -> Self {
+        Self {
+            start: token.span.0,
+            end: token.span.1,
+            line: token.line,
+            column: token.column,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Literal {
     String(String),
     Number(f64),
@@ -56,7 +145,7 @@ impl ::std::fmt::Display for Literal {
     fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
         match self {
             Literal::String(s) => write!(f, "\"{}\"", s),
-            Literal::Number(n) => n.fmt(f),
+            Literal::Number(n) => f.write_str(&format_number(*n)),
             Literal::Bool(b) => b.fmt(f),
             Literal::Nil => write!(f, "nil"),
         }
@@ -64,36 +153,86 @@ impl ::std::fmt::Display for Literal {
 }
 
 impl Expr {
-    pub fn accept<T>(&mut self, visitor: &mut impl ExprVisitor<T>) -> Result<T, Error> {
+    pub fn accept<T>(&self, visitor: &mut impl ExprVisitor<T>) -> Result<T, Error> {
         match self {
             Expr::Binary {
                 left,
                 operator,
                 right,
+                ..
             } => visitor.visit_bin(left, operator, right),
-            Expr::Grouping(_) => visitor.visit_group(self),
-            Expr::Literal(lit) => visitor.visit_lit(lit),
-            Expr::Unary { operator, right } => visitor.visit_un(operator, right),
-            Expr::Var(name) => visitor.visit_var(name),
-            Expr::Assign { name, value } => visitor.visit_assign(name, value),
+            Expr::Grouping(..) => visitor.visit_group(self),
+            Expr::Literal(lit, _) => visitor.visit_lit(lit),
+            Expr::Unary {
+                operator, right, ..
+            } => visitor.visit_un(operator, right),
+            Expr::Var(name, span, binding) => visitor.visit_var(name, *span, binding),
+            Expr::Assign {
+                name,
+                value,
+                span,
+                binding,
+            } => visitor.visit_assign(name, value, *span, binding),
             Expr::Log {
                 left,
                 operator,
                 right,
+                ..
             } => visitor.visit_log(left, operator, right),
-            Expr::Call { callee, arguments } => visitor.visit_call(callee, arguments),
-            Expr::Get { object, name } => visitor.visit_get(object, name),
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+                ..
+            } => visitor.visit_call(callee, paren, arguments),
+            Expr::Get { object, name, span } => visitor.visit_get(object, name, *span),
+            Expr::OptionalGet { object, name, .. } => visitor.visit_optional_get(object, name),
             Expr::Set {
                 object,
                 name,
                 value,
+                ..
             } => visitor.visit_set(object, name, value),
-            Expr::This => visitor.visit_this(),
+            Expr::CompoundSet {
+                object,
+                name,
+                operator,
+                value,
+                ..
+            } => visitor.visit_compound_set(object, name, operator, value),
+            Expr::This(_) => visitor.visit_this(),
+            Expr::Postfix {
+                target, operator, ..
+            } => visitor.visit_postfix(target, operator),
+            Expr::Super { method, span } => visitor.visit_super(method, *span),
+            Expr::Interpolation(parts, _) => visitor.visit_interpolation(parts),
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Binary { span, .. }
+            | Expr::Grouping(_, span)
+            | Expr::Literal(_, span)
+            | Expr::Unary { span, .. }
+            | Expr::Var(_, span, _)
+            | Expr::Assign { span, .. }
+            | Expr::Log { span, .. }
+            | Expr::Call { span, .. }
+            | Expr::Get { span, .. }
+            | Expr::OptionalGet { span, .. }
+            | Expr::Set { span, .. }
+            | Expr::CompoundSet { span, .. }
+            | Expr::This(span)
+            | Expr::Postfix { span, .. }
+            | Expr::Super { span, .. }
+            | Expr::Interpolation(_, span) => *span,
         }
     }
 
     pub fn binary(left: Expr, right: Expr, op: Token) -> Self {
         Expr::Binary {
+            span: left.span().to(right.span()),
             left: Box::new(left),
             right: Box::new(right),
             operator: op,
@@ -102,21 +241,25 @@ impl Expr {
 
     pub fn unary(op: Token, right: Expr) -> Self {
         Expr::Unary {
+            span: Span::from(&op).to(right.span()),
             operator: op,
             right: Box::new(right),
         }
     }
-    pub fn grouping(inner: Expr) -> Self {
-        Expr::Grouping(Box::new(inner))
+    pub fn grouping(inner: Expr, span: Span) -> Self {
+        Expr::Grouping(Box::new(inner), span)
     }
-    pub fn assign(name: String, value: Expr) -> Self {
+    pub fn assign(name: String, target: Span, value: Expr) -> Self {
         Expr::Assign {
             name,
+            span: target.to(value.span()),
             value: Box::new(value),
+            binding: Cell::default(),
         }
     }
     pub fn log(left: Expr, right: Expr, op: Token) -> Self {
         Expr::Log {
+            span: left.span().to(right.span()),
             left: Box::new(left),
             operator: op,
             right: Box::new(right),
@@ -125,15 +268,32 @@ impl Expr {
 }
 
 pub trait ExprVisitor<T> {
-    fn visit_bin(&mut self, left: &mut Expr, op: &Token, right: &mut Expr) -> Result<T, Error>;
-    fn visit_group(&mut self, group: &mut Expr) -> Result<T, Error>;
+    fn visit_bin(&mut self, left: &Expr, op: &Token, right: &Expr) -> Result<T, Error>;
+    fn visit_group(&mut self, group: &Expr) -> Result<T, Error>;
     fn visit_lit(&self, lit: &Literal) -> Result<T, Error>;
-    fn visit_un(&mut self, op: &Token, ex: &mut Expr) -> Result<T, Error>;
-    fn visit_var(&mut self, name: &str) -> Result<T, Error>;
-    fn visit_assign(&mut self, name: &str, value: &mut Expr) -> Result<T, Error>;
-    fn visit_log(&mut self, left: &mut Expr, op: &Token, right: &mut Expr) -> Result<T, Error>;
-    fn visit_call(&mut self, callee: &mut Expr, arguments: &mut [Expr]) -> Result<T, Error>;
-    fn visit_get(&mut self, object: &mut Expr, name: &str) -> Result<T, Error>;
-    fn visit_set(&mut self, object: &mut Expr, name: &str, value: &mut Expr) -> Result<T, Error>;
+    fn visit_un(&mut self, op: &Token, ex: &Expr) -> Result<T, Error>;
+    fn visit_var(&mut self, name: &str, span: Span, binding: &Cell<Binding>) -> Result<T, Error>;
+    fn visit_assign(
+        &mut self,
+        name: &str,
+        value: &Expr,
+        span: Span,
+        binding: &Cell<Binding>,
+    ) -> Result<T, Error>;
+    fn visit_log(&mut self, left: &Expr, op: &Token, right: &Expr) -> Result<T, Error>;
+    fn visit_call(&mut self, callee: &Expr, paren: &Token, arguments: &[Expr]) -> Result<T, Error>;
+    fn visit_get(&mut self, object: &Expr, name: &str, span: Span) -> Result<T, Error>;
+    fn visit_optional_get(&mut self, object: &Expr, name: &str) -> Result<T, Error>;
+    fn visit_set(&mut self, object: &Expr, name: &str, value: &Expr) -> Result<T, Error>;
+    fn visit_compound_set(
+        &mut self,
+        object: &Expr,
+        name: &str,
+        operator: &Token,
+        value: &Expr,
+    ) -> Result<T, Error>;
     fn visit_this(&mut self) -> Result<T, Error>;
+    fn visit_postfix(&mut self, target: &Expr, operator: &Token) -> Result<T, Error>;
+    fn visit_super(&mut self, method: &str, span: Span) -> Result<T, Error>;
+    fn visit_interpolation(&mut self, parts: &[Expr]) -> Result<T, Error>;
 }
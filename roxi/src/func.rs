@@ -1,14 +1,30 @@
 use crate::{
     callable::Callable, env::Env, error::Error, interpreter::Interpreter, stmt::Stmt, value::Value,
 };
+use std::rc::Rc;
 
+/// A function along with the scopes that were live
+/// where it was declared
 #[derive(Debug, Clone)]
 pub struct Func {
     pub name: String,
     pub params: Vec<String>,
-    pub body: Vec<Stmt>,
+    /// Shared with every function made from the same
+    /// declaration, together with `env` this gives it
+    /// an identity for `==`
+    pub body: Rc<Vec<Stmt>>,
     pub env: Env,
-    pub env_idx: usize,
+}
+
+impl Func {
+    /// A copy of this function with `this` defined in
+    /// a scope just outside of its body
+    pub fn bind(&self, this: Value) -> Self {
+        let mut ret = self.clone();
+        ret.env.descend();
+        ret.env.define("this", Some(this));
+        ret
+    }
 }
 
 impl Callable for Func {
@@ -19,21 +35,18 @@ impl Callable for Func {
         self.params.len()
     }
     fn call(&mut self, int: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
-        let tail_env = int.env.split_to_base();
-        int.env.append(self.env.clone());
-
+        let mut env = self.env.clone();
+        env.descend();
         for (name, value) in self.params.iter().zip(args.iter().cloned()) {
-            int.env.define(name, Some(value));
+            env.define(name, Some(value));
         }
-        let ret = match int.execute_block(&mut self.body) {
+        let caller_env = std::mem::replace(&mut int.env, env);
+        let ret = match int.execute_all(&self.body) {
             Ok(_) => Ok(Value::Nil),
             Err(Error::Return(v)) => Ok(v),
             Err(e) => Err(e),
         };
-
-        self.env = int.env.clone_to_base();
-        int.env.append(tail_env);
-        int.env.assign(self.name(), Value::Func(self.clone()))?;
+        int.env = caller_env;
         ret
     }
 }
@@ -47,6 +60,7 @@ impl ::std::fmt::Display for Func {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::test_util::run;
     #[test]
     fn nested_funcs() {
         let _ = pretty_env_logger::try_init();
@@ -67,16 +81,15 @@ var test1 = counter();
 var test2 = counter();
 ";
         let mut int = Interpreter::new();
-        let mut p =
-            crate::parser::Parser::new(crate::scanner::Scanner::new(lox.to_string()).unwrap());
-        int.interpret(&mut p.next().unwrap().unwrap()).unwrap();
-        int.interpret(&mut p.next().unwrap().unwrap()).unwrap();
-        int.interpret(&mut p.next().unwrap().unwrap()).unwrap();
+        let mut p = crate::parser::Parser::new(crate::Scanner::new(lox.to_string()).unwrap());
+        int.interpret(&p.next().unwrap().unwrap()).unwrap();
+        int.interpret(&p.next().unwrap().unwrap()).unwrap();
+        int.interpret(&p.next().unwrap().unwrap()).unwrap();
 
-        int.interpret(&mut p.next().unwrap().unwrap()).unwrap();
+        int.interpret(&p.next().unwrap().unwrap()).unwrap();
         let test = int.env.get("test1").expect("Failed to get test1 from env");
         assert_eq!(test, Value::Number(1f64), "test1 was not 1");
-        int.interpret(&mut p.next().unwrap().unwrap()).unwrap();
+        int.interpret(&p.next().unwrap().unwrap()).unwrap();
         let test2 = int.env.get("test2").expect("Failed to get test2 from env");
         assert_eq!(test2, Value::Number(2f64), "test2 was not 2");
     }
@@ -91,14 +104,78 @@ var test2 = counter();
         }
         var test = fib(4);";
         let mut int = Interpreter::new();
-        let mut p =
-            crate::parser::Parser::new(crate::scanner::Scanner::new(lox.to_string()).unwrap());
-        let mut fib = p.next().unwrap().expect("failed to define fib");
-        int.interpret(&mut fib).expect("failed to define fib def");
-        let mut test = p.next().unwrap().expect("failed to parse test assignment");
-        int.interpret(&mut test)
+        let mut p = crate::parser::Parser::new(crate::Scanner::new(lox.to_string()).unwrap());
+        let fib = p.next().unwrap().expect("failed to define fib");
+        int.interpret(&fib).expect("failed to define fib def");
+        let test = p.next().unwrap().expect("failed to parse test assignment");
+        int.interpret(&test)
             .expect("failed to evalue test assignment");
         let test = int.env.get("test").expect("Failed to get test from env");
         assert_eq!(test, Value::Number(3f64));
     }
+
+    #[test]
+    fn independent_counters() {
+        let lox = "
+fun makeCounter() {
+  var i = 0;
+  fun count() {
+    i = i + 1;
+    return i;
+  }
+  return count;
+}
+var first = makeCounter();
+var second = makeCounter();
+first();
+first();
+var a = first();
+var b = second();
+";
+        let int = run(lox);
+        assert_eq!(int.env.get("a").unwrap(), Value::Number(3f64));
+        assert_eq!(int.env.get("b").unwrap(), Value::Number(1f64));
+    }
+
+    #[test]
+    fn mutates_outer() {
+        let lox = "
+var total = 0;
+fun add(n) {
+  total = total + n;
+}
+add(2);
+add(3);
+var seen;
+{
+  var local = 1;
+  fun bump() { local = local + 1; }
+  bump();
+  seen = local;
+}
+";
+        let int = run(lox);
+        assert_eq!(int.env.get("total").unwrap(), Value::Number(5f64));
+        assert_eq!(int.env.get("seen").unwrap(), Value::Number(2f64));
+    }
+
+    #[test]
+    fn shared_capture() {
+        let lox = "
+var get;
+var set;
+fun make() {
+  var value = 1;
+  fun getter() { return value; }
+  fun setter(v) { value = v; }
+  get = getter;
+  set = setter;
+}
+make();
+set(5);
+var result = get();
+";
+        let int = run(lox);
+        assert_eq!(int.env.get("result").unwrap(), Value::Number(5f64));
+    }
 }
@@ -1,9 +1,46 @@
-use crate::{callable::Callable, error::Error, interpreter::Interpreter, value::Value};
+use crate::{
+    callable::Callable,
+    class::ClassInstance,
+    error::Error,
+    expr::Span,
+    interpreter::Interpreter,
+    ordered_map::OrderedMap,
+    printer::Printer,
+    stmt::{Function, Stmt},
+    value::Value,
+};
+use rox_shared::{Scanner, TokenType};
+use std::{cell::RefCell, collections::HashMap, io::Write, rc::Rc};
 
 #[derive(Debug, Clone)]
 pub enum NativeFunc {
     Clock(Clock),
     Mod(Mod),
+    ToNumber(ToNumber),
+    ToString(ToString),
+    Floor(Floor),
+    Ceil(Ceil),
+    Round(Round),
+    Abs(Abs),
+    Weak(Weak),
+    ReadLine(ReadLine),
+    Memoize(Memoize),
+    Memoized(Memoized),
+    Assert(Assert),
+    ToBase(ToBase),
+    Source(Source),
+    NewMap(NewMap),
+    PartialRight(PartialRight),
+    Printf(Printf),
+    Len(Len),
+    HasField(HasField),
+    DeleteField(DeleteField),
+    Partial(Partial),
+    Times(Times),
+    Fields(Fields),
+    ToJson(ToJson),
+    TypeOf(TypeOf),
+    Exit(Exit),
 }
 
 impl ::std::fmt::Display for NativeFunc {
@@ -11,6 +48,31 @@ impl ::std::fmt::Display for NativeFunc {
         match self {
             NativeFunc::Clock(c) => c.fmt(f),
             NativeFunc::Mod(m) => m.fmt(f),
+            NativeFunc::ToNumber(n) => n.fmt(f),
+            NativeFunc::ToString(s) => s.fmt(f),
+            NativeFunc::Floor(n) => n.fmt(f),
+            NativeFunc::Ceil(n) => n.fmt(f),
+            NativeFunc::Round(n) => n.fmt(f),
+            NativeFunc::Abs(n) => n.fmt(f),
+            NativeFunc::Weak(w) => w.fmt(f),
+            NativeFunc::ReadLine(r) => r.fmt(f),
+            NativeFunc::Memoize(m) => m.fmt(f),
+            NativeFunc::Memoized(m) => m.fmt(f),
+            NativeFunc::Assert(a) => a.fmt(f),
+            NativeFunc::ToBase(b) => b.fmt(f),
+            NativeFunc::Source(s) => s.fmt(f),
+            NativeFunc::NewMap(m) => m.fmt(f),
+            NativeFunc::PartialRight(p) => p.fmt(f),
+            NativeFunc::Printf(p) => p.fmt(f),
+            NativeFunc::Len(l) => l.fmt(f),
+            NativeFunc::HasField(h) => h.fmt(f),
+            NativeFunc::DeleteField(d) => d.fmt(f),
+            NativeFunc::Partial(p) => p.fmt(f),
+            NativeFunc::Times(t) => t.fmt(f),
+            NativeFunc::Fields(n) => n.fmt(f),
+            NativeFunc::ToJson(j) => j.fmt(f),
+            NativeFunc::TypeOf(t) => t.fmt(f),
+            NativeFunc::Exit(e) => e.fmt(f),
         }
     }
 }
@@ -20,18 +82,109 @@ impl Callable for NativeFunc {
         match self {
             NativeFunc::Clock(c) => c.name(),
             NativeFunc::Mod(m) => m.name(),
+            NativeFunc::ToNumber(n) => n.name(),
+            NativeFunc::ToString(s) => s.name(),
+            NativeFunc::Floor(n) => n.name(),
+            NativeFunc::Ceil(n) => n.name(),
+            NativeFunc::Round(n) => n.name(),
+            NativeFunc::Abs(n) => n.name(),
+            NativeFunc::Weak(w) => w.name(),
+            NativeFunc::ReadLine(r) => r.name(),
+            NativeFunc::Memoize(m) => m.name(),
+            NativeFunc::Memoized(m) => m.name(),
+            NativeFunc::Assert(a) => a.name(),
+            NativeFunc::ToBase(b) => b.name(),
+            NativeFunc::Source(s) => s.name(),
+            NativeFunc::NewMap(m) => m.name(),
+            NativeFunc::PartialRight(p) => p.name(),
+            NativeFunc::Printf(p) => p.name(),
+            NativeFunc::Len(l) => l.name(),
+            NativeFunc::HasField(h) => h.name(),
+            NativeFunc::DeleteField(d) => d.name(),
+            NativeFunc::Partial(p) => p.name(),
+            NativeFunc::Times(t) => t.name(),
+            NativeFunc::Fields(n) => n.name(),
+            NativeFunc::ToJson(j) => j.name(),
+            NativeFunc::TypeOf(t) => t.name(),
+            NativeFunc::Exit(e) => e.name(),
         }
     }
     fn arity(&self) -> usize {
         match self {
             NativeFunc::Clock(c) => c.arity(),
             NativeFunc::Mod(m) => m.arity(),
+            NativeFunc::ToNumber(n) => n.arity(),
+            NativeFunc::ToString(s) => s.arity(),
+            NativeFunc::Floor(n) => n.arity(),
+            NativeFunc::Ceil(n) => n.arity(),
+            NativeFunc::Round(n) => n.arity(),
+            NativeFunc::Abs(n) => n.arity(),
+            NativeFunc::Weak(w) => w.arity(),
+            NativeFunc::ReadLine(r) => r.arity(),
+            NativeFunc::Memoize(m) => m.arity(),
+            NativeFunc::Memoized(m) => m.arity(),
+            NativeFunc::Assert(a) => a.arity(),
+            NativeFunc::ToBase(b) => b.arity(),
+            NativeFunc::Source(s) => s.arity(),
+            NativeFunc::NewMap(m) => m.arity(),
+            NativeFunc::PartialRight(p) => p.arity(),
+            NativeFunc::Printf(p) => p.arity(),
+            NativeFunc::Len(l) => l.arity(),
+            NativeFunc::HasField(h) => h.arity(),
+            NativeFunc::DeleteField(d) => d.arity(),
+            NativeFunc::Partial(p) => p.arity(),
+            NativeFunc::Times(t) => t.arity(),
+            NativeFunc::Fields(n) => n.arity(),
+            NativeFunc::ToJson(j) => j.arity(),
+            NativeFunc::TypeOf(t) => t.arity(),
+            NativeFunc::Exit(e) => e.arity(),
+        }
+    }
+    fn max_arity(&self) -> usize {
+        match self {
+            NativeFunc::Memoized(m) => m.max_arity(),
+            NativeFunc::Assert(a) => a.max_arity(),
+            NativeFunc::Partial(p) => p.max_arity(),
+            _ => self.arity(),
+        }
+    }
+    fn is_variadic(&self) -> bool {
+        match self {
+            NativeFunc::Memoized(m) => m.is_variadic(),
+            NativeFunc::Partial(p) => p.is_variadic(),
+            NativeFunc::Printf(p) => p.is_variadic(),
+            _ => false,
         }
     }
     fn call(&mut self, int: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
         match self {
             NativeFunc::Clock(c) => c.call(int, args),
             NativeFunc::Mod(m) => m.call(int, args),
+            NativeFunc::ToNumber(n) => n.call(int, args),
+            NativeFunc::ToString(s) => s.call(int, args),
+            NativeFunc::Floor(n) => n.call(int, args),
+            NativeFunc::Ceil(n) => n.call(int, args),
+            NativeFunc::Round(n) => n.call(int, args),
+            NativeFunc::Abs(n) => n.call(int, args),
+            NativeFunc::Weak(w) => w.call(int, args),
+            NativeFunc::ReadLine(r) => r.call(int, args),
+            NativeFunc::Memoize(m) => m.call(int, args),
+            NativeFunc::Memoized(m) => m.call(int, args),
+            NativeFunc::Assert(a) => a.call(int, args),
+            NativeFunc::ToBase(b) => b.call(int, args),
+            NativeFunc::Source(s) => s.call(int, args),
+            NativeFunc::NewMap(m) => m.call(int, args),
+            NativeFunc::PartialRight(p) => p.call(int, args),
+            NativeFunc::Printf(p) => p.call(int, args),
+            NativeFunc::Len(l) => l.call(int, args),
+            NativeFunc::HasField(h) => h.call(int, args),
+            NativeFunc::DeleteField(d) => d.call(int, args),
+            NativeFunc::Partial(p) => p.call(int, args),
+            NativeFunc::Times(t) => t.call(int, args),
+            NativeFunc::Fields(n) => n.call(int, args),
+            NativeFunc::ToJson(j) => j.call(int, args),
+            NativeFunc::TypeOf(t) => t.call(int, args),
+            NativeFunc::Exit(e) => e.call(int, args),
         }
     }
 }
@@ -43,12 +196,18 @@ impl Callable for Clock {
     fn name(&self) -> &str {
         "clock"
     }
-    fn call(&mut self, _: &mut Interpreter, _: &[Value]) -> Result<Value, Error> {
-        let now = ::std::time::SystemTime::now();
-        let dur = now
-            .duration_since(::std::time::UNIX_EPOCH)
-            .map_err(|e| Error::Runtime(format!("Error calculating current timestamp:\n{}", e)))?;
-        Ok(Value::Number(dur.as_secs_f64() * 1000.0))
+    fn call(&mut self, int: &mut Interpreter, _: &[Value]) -> Result<Value, Error> {
+        Ok(Value::Number(int.now()))
+    }
+}
+
+/// Milliseconds since the unix epoch, negative if the
+/// system clock is set before it
+pub(crate) fn system_clock() -> f64 {
+    let now = ::std::time::SystemTime::now();
+    match now.duration_since(::std::time::UNIX_EPOCH) {
+        Ok(dur) => dur.as_secs_f64() * 1000.0,
+        Err(e) => -e.duration().as_secs_f64() * 1000.0,
     }
 }
 #[derive(Debug, Clone)]
@@ -61,7 +220,7 @@ impl Callable for Mod {
         2
     }
     fn call(&mut self, _: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
-        if let Some(Value::Number(lhs)) = args.get(0) {
+        if let Some(Value::Number(lhs)) = args.first() {
             if let Some(Value::Number(rhs)) = args.get(1) {
                 return Ok(Value::Number(lhs % rhs));
             }
@@ -73,6 +232,808 @@ impl Callable for Mod {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct ToNumber;
+impl Callable for ToNumber {
+    fn name(&self) -> &str {
+        "to_number"
+    }
+    fn arity(&self) -> usize {
+        1
+    }
+    fn call(&mut self, _: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+        match args.first() {
+            Some(Value::String(s)) => Ok(parse_number(s).map(Value::Number).unwrap_or(Value::Nil)),
+            Some(Value::Number(n)) => Ok(Value::Number(*n)),
+            _ => Err(Error::Runtime(format!(
+                "invalid arguments provided to to_number: {:?}",
+                args
+            ))),
+        }
+    }
+}
+
+/// Parse a string by scanning it as a single number
+/// literal, so anything the scanner accepts works here
+/// (`1.5`, `1e3`, `0xFF`, `1_000`), allowing surrounding
+/// whitespace and a leading `-`
+fn parse_number(s: &str) -> Option<f64> {
+    let s = s.trim();
+    let (sign, digits) = match s.strip_prefix('-') {
+        Some(digits) => (-1.0, digits),
+        None => (1.0, s),
+    };
+    if !digits.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    let tokens = Scanner::new(digits.to_string())
+        .and_then(|mut scanner| scanner.scan_tokens())
+        .ok()?;
+    match tokens.as_slice() {
+        [token] if token.lexeme == digits => match token.kind {
+            TokenType::Number(n) => Some(sign * n),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ToString;
+impl Callable for ToString {
+    fn name(&self) -> &str {
+        "to_string"
+    }
+    fn arity(&self) -> usize {
+        1
+    }
+    fn call(&mut self, _: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+        match args.first() {
+            Some(v) => Ok(Value::String(v.stringify())),
+            None => Err(Error::Runtime(
+                "invalid arguments provided to to_string: []".to_string(),
+            )),
+        }
+    }
+}
+
+fn number_arg(name: &str, args: &[Value]) -> Result<f64, Error> {
+    if let Some(Value::Number(n)) = args.first() {
+        Ok(*n)
+    } else {
+        Err(Error::Runtime(format!(
+            "invalid arguments provided to {}: {:?}",
+            name, args
+        )))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Floor;
+impl Callable for Floor {
+    fn name(&self) -> &str {
+        "floor"
+    }
+    fn arity(&self) -> usize {
+        1
+    }
+    fn call(&mut self, _: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+        Ok(Value::Number(number_arg(self.name(), args)?.floor()))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Ceil;
+impl Callable for Ceil {
+    fn name(&self) -> &str {
+        "ceil"
+    }
+    fn arity(&self) -> usize {
+        1
+    }
+    fn call(&mut self, _: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+        Ok(Value::Number(number_arg(self.name(), args)?.ceil()))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Round;
+impl Callable for Round {
+    fn name(&self) -> &str {
+        "round"
+    }
+    fn arity(&self) -> usize {
+        1
+    }
+    fn call(&mut self, _: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+        Ok(Value::Number(number_arg(self.name(), args)?.round()))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Abs;
+impl Callable for Abs {
+    fn name(&self) -> &str {
+        "abs"
+    }
+    fn arity(&self) -> usize {
+        1
+    }
+    fn call(&mut self, _: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+        Ok(Value::Number(number_arg(self.name(), args)?.abs()))
+    }
+}
+
+/// A reference to an instance that doesn't keep it alive,
+/// so instances can refer back to each other without the
+/// cycle keeping them all around forever
+#[derive(Debug, Clone)]
+pub struct Weak;
+impl Callable for Weak {
+    fn name(&self) -> &str {
+        "weak"
+    }
+    fn arity(&self) -> usize {
+        1
+    }
+    fn call(&mut self, _: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+        match args.first() {
+            Some(Value::Class(inst)) => Ok(Value::WeakRef(Rc::downgrade(inst))),
+            Some(weak @ Value::WeakRef(_)) => Ok(weak.clone()),
+            other => Err(Error::Runtime(format!(
+                "weak expects an instance, found {}",
+                other.unwrap_or(&Value::Nil).type_name()
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReadLine;
+impl Callable for ReadLine {
+    fn name(&self) -> &str {
+        "read_line"
+    }
+    fn call(&mut self, int: &mut Interpreter, _: &[Value]) -> Result<Value, Error> {
+        let mut line = String::new();
+        let read = int
+            .input
+            .read_line(&mut line)
+            .map_err(|e| Error::Runtime(format!("IO Error: {}", e)))?;
+        if read == 0 {
+            return Ok(Value::Nil);
+        }
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Ok(Value::String(line))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Memoize;
+impl Callable for Memoize {
+    fn name(&self) -> &str {
+        "memoize"
+    }
+    fn arity(&self) -> usize {
+        1
+    }
+    fn call(&mut self, _: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+        match args.first() {
+            Some(func) if func.as_callable().is_some() => {
+                Ok(Value::NativeFunc(NativeFunc::Memoized(Memoized {
+                    func: Box::new(func.clone()),
+                    cache: Rc::default(),
+                })))
+            }
+            _ => Err(Error::Runtime(format!(
+                "invalid arguments provided to memoize: {:?}",
+                args
+            ))),
+        }
+    }
+}
+
+/// A function wrapped by `memoize`, calls with the same
+/// arguments share a result. The cache is shared between
+/// copies of this value so it survives being looked up
+/// from the environment
+#[derive(Debug, Clone)]
+pub struct Memoized {
+    func: Box<Value>,
+    cache: Rc<RefCell<HashMap<String, Value>>>,
+}
+
+impl Memoized {
+    /// Only strings, numbers, booleans and nil can be
+    /// used as a key, anything else is always recomputed
+    fn key(args: &[Value]) -> Option<String> {
+        let mut key = String::new();
+        for arg in args {
+            match arg {
+                Value::String(_) | Value::Number(_) | Value::Bool(_) | Value::Nil => {
+                    key.push_str(&format!("{:?},", arg))
+                }
+                _ => return None,
+            }
+        }
+        Some(key)
+    }
+}
+
+impl Callable for Memoized {
+    fn name(&self) -> &str {
+        self.func
+            .as_callable()
+            .map(|f| f.name())
+            .unwrap_or("memoized")
+    }
+    fn arity(&self) -> usize {
+        self.func.as_callable().map(|f| f.arity()).unwrap_or(0)
+    }
+    fn max_arity(&self) -> usize {
+        self.func.as_callable().map(|f| f.max_arity()).unwrap_or(0)
+    }
+    fn is_variadic(&self) -> bool {
+        self.func
+            .as_callable()
+            .map(|f| f.is_variadic())
+            .unwrap_or(false)
+    }
+    fn call(&mut self, int: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+        let key = Self::key(args);
+        if let Some(key) = &key {
+            if let Some(value) = self.cache.borrow().get(key) {
+                return Ok(value.clone());
+            }
+        }
+        let value = int.call_value(&mut self.func, args)?;
+        if let Some(key) = key {
+            self.cache.borrow_mut().insert(key, value.clone());
+        }
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Assert;
+impl Callable for Assert {
+    fn name(&self) -> &str {
+        "assert"
+    }
+    fn arity(&self) -> usize {
+        1
+    }
+    fn max_arity(&self) -> usize {
+        2
+    }
+    fn call(&mut self, _: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+        match args {
+            [cond, ..] if Interpreter::is_truthy(cond) => Ok(Value::Nil),
+            [_, msg] => Err(Error::Runtime(format!(
+                "assertion failed: {}",
+                msg.stringify()
+            ))),
+            _ => Err(Error::Runtime("assertion failed".to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PartialRight;
+impl Callable for PartialRight {
+    fn name(&self) -> &str {
+        "partial_right"
+    }
+    fn arity(&self) -> usize {
+        2
+    }
+    fn call(&mut self, _: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+        match args {
+            [func, arg] if func.as_callable().map(|f| f.arity() > 0).unwrap_or(false) => {
+                Ok(Value::NativeFunc(NativeFunc::Partial(Partial {
+                    func: Box::new(func.clone()),
+                    arg: Box::new(arg.clone()),
+                })))
+            }
+            [func, _] => Err(Error::Runtime(format!(
+                "partial_right expects a function that takes arguments, found {}",
+                func
+            ))),
+            _ => Err(Error::Runtime(format!(
+                "invalid arguments provided to partial_right: {:?}",
+                args
+            ))),
+        }
+    }
+}
+
+/// A function wrapped by `partial_right`, `arg` is
+/// passed after the arguments of every call
+#[derive(Debug, Clone)]
+pub struct Partial {
+    func: Box<Value>,
+    arg: Box<Value>,
+}
+
+impl Callable for Partial {
+    fn name(&self) -> &str {
+        self.func
+            .as_callable()
+            .map(|f| f.name())
+            .unwrap_or("partial")
+    }
+    fn arity(&self) -> usize {
+        self.func
+            .as_callable()
+            .map(|f| f.arity())
+            .unwrap_or(1)
+            .saturating_sub(1)
+    }
+    fn max_arity(&self) -> usize {
+        self.func
+            .as_callable()
+            .map(|f| f.max_arity())
+            .unwrap_or(1)
+            .saturating_sub(1)
+    }
+    fn is_variadic(&self) -> bool {
+        self.func
+            .as_callable()
+            .map(|f| f.is_variadic())
+            .unwrap_or(false)
+    }
+    fn call(&mut self, int: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+        let mut args = args.to_vec();
+        args.push((*self.arg).clone());
+        int.call_value(&mut self.func, &args)
+    }
+}
+
+/// Prints its first argument with each `{}` replaced
+/// by one of the arguments that follow it
+#[derive(Debug, Clone)]
+pub struct Printf;
+impl Callable for Printf {
+    fn name(&self) -> &str {
+        "printf"
+    }
+    fn arity(&self) -> usize {
+        1
+    }
+    fn is_variadic(&self) -> bool {
+        true
+    }
+    fn call(&mut self, int: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+        let (fmt, values) = match args {
+            [Value::String(fmt), values @ ..] => (fmt, values),
+            _ => {
+                return Err(Error::Runtime(format!(
+                    "printf expects a format string, found {}",
+                    args.first().unwrap_or(&Value::Nil).type_name()
+                )))
+            }
+        };
+        let pieces: Vec<&str> = fmt.split("{}").collect();
+        if pieces.len() - 1 != values.len() {
+            return Err(Error::Runtime(format!(
+                "printf format has {} placeholders but {} values were provided",
+                pieces.len() - 1,
+                values.len()
+            )));
+        }
+        let mut out = pieces[0].to_string();
+        for (value, piece) in values.iter().zip(&pieces[1..]) {
+            out.push_str(&value.stringify());
+            out.push_str(piece);
+        }
+        writeln!(int.output, "{}", out).map_err(|e| Error::Runtime(format!("IO Error: {}", e)))?;
+        Ok(Value::Nil)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Len;
+impl Callable for Len {
+    fn name(&self) -> &str {
+        "len"
+    }
+    fn arity(&self) -> usize {
+        1
+    }
+    fn call(&mut self, _: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+        let arg = args.first().unwrap_or(&Value::Nil);
+        arg.length()
+            .map(|len| Value::Number(len as f64))
+            .ok_or_else(|| {
+                Error::Runtime(format!(
+                    "len expects a string or map, found {}",
+                    arg.type_name()
+                ))
+            })
+    }
+}
+
+/// Calls a function `n` times, passing the iteration
+/// index when the function takes an argument
+#[derive(Debug, Clone)]
+pub struct Times;
+impl Callable for Times {
+    fn name(&self) -> &str {
+        "times"
+    }
+    fn arity(&self) -> usize {
+        2
+    }
+    fn call(&mut self, int: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+        let (n, func) = match args {
+            [Value::Number(n), func] => (*n, func),
+            _ => {
+                return Err(Error::Runtime(format!(
+                    "invalid arguments provided to times: {:?}",
+                    args
+                )))
+            }
+        };
+        if n.fract() != 0.0 || n < 0.0 {
+            return Err(Error::Runtime(format!(
+                "times expects a non-negative integer, found {}",
+                Value::Number(n)
+            )));
+        }
+        let takes_index = match func.as_callable() {
+            Some(f) if f.arity() <= 1 => f.arity() == 1,
+            _ => {
+                return Err(Error::Runtime(format!(
+                    "times expects a function taking zero or one arguments, found {}",
+                    func
+                )))
+            }
+        };
+        let mut func = func.clone();
+        for i in 0..n as u64 {
+            if takes_index {
+                int.call_value(&mut func, &[Value::Number(i as f64)])?;
+            } else {
+                int.call_value(&mut func, &[])?;
+            }
+        }
+        Ok(Value::Nil)
+    }
+}
+
+/// The instance and field name passed to `has_field`
+/// or `delete_field`
+fn field_args<'a>(
+    name: &str,
+    args: &'a [Value],
+) -> Result<(&'a Rc<RefCell<ClassInstance>>, &'a str), Error> {
+    match args {
+        [Value::Class(inst), Value::String(field)] => Ok((inst, field)),
+        _ => Err(Error::Runtime(format!(
+            "{} expects an instance and a field name, found {}",
+            name,
+            args.iter()
+                .map(Value::type_name)
+                .collect::<Vec<_>>()
+                .join(" and ")
+        ))),
+    }
+}
+
+/// Whether an instance has a field, methods don't count
+#[derive(Debug, Clone)]
+pub struct HasField;
+impl Callable for HasField {
+    fn name(&self) -> &str {
+        "has_field"
+    }
+    fn arity(&self) -> usize {
+        2
+    }
+    fn call(&mut self, _: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+        let (inst, field) = field_args(self.name(), args)?;
+        let has = inst.borrow().fields.contains_key(field);
+        Ok(Value::Bool(has))
+    }
+}
+
+/// Remove a field from an instance, returning its value
+/// or nil if it wasn't set. Methods can't be removed
+#[derive(Debug, Clone)]
+pub struct DeleteField;
+impl Callable for DeleteField {
+    fn name(&self) -> &str {
+        "delete_field"
+    }
+    fn arity(&self) -> usize {
+        2
+    }
+    fn call(&mut self, _: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+        let (inst, field) = field_args(self.name(), args)?;
+        let removed = inst.borrow_mut().fields.remove(field);
+        Ok(removed.unwrap_or(Value::Nil))
+    }
+}
+
+/// Stops the program with an exit code
+#[derive(Debug, Clone)]
+pub struct Exit;
+impl Callable for Exit {
+    fn name(&self) -> &str {
+        "exit"
+    }
+    fn arity(&self) -> usize {
+        1
+    }
+    fn call(&mut self, _: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+        match args.first() {
+            Some(Value::Number(n))
+                if n.fract() == 0.0 && (i32::MIN as f64..=i32::MAX as f64).contains(n) =>
+            {
+                Err(Error::Exit(*n as i32))
+            }
+            other => Err(Error::Runtime(format!(
+                "exit expects an integer exit code, found {}",
+                other.unwrap_or(&Value::Nil)
+            ))),
+        }
+    }
+}
+
+/// The kind of a value, one of number, string, bool,
+/// nil, function, class or instance. Natives and methods
+/// are functions, and maps are instances since their
+/// entries are read and set like fields
+#[derive(Debug, Clone)]
+pub struct TypeOf;
+impl Callable for TypeOf {
+    fn name(&self) -> &str {
+        "typeof"
+    }
+    fn arity(&self) -> usize {
+        1
+    }
+    fn call(&mut self, _: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+        let kind = match args.first().unwrap_or(&Value::Nil) {
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Bool(_) => "bool",
+            Value::Nil => "nil",
+            Value::Func(_) | Value::NativeFunc(_) | Value::Method(_) => "function",
+            Value::Init(_) => "class",
+            Value::Class(_) | Value::Map(_) | Value::WeakRef(_) => "instance",
+        };
+        Ok(Value::String(kind.to_string()))
+    }
+}
+
+/// A copy of an instance's fields as a map, in the
+/// order they were first set
+#[derive(Debug, Clone)]
+pub struct Fields;
+impl Callable for Fields {
+    fn name(&self) -> &str {
+        "fields"
+    }
+    fn arity(&self) -> usize {
+        1
+    }
+    fn call(&mut self, _: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+        match args.first() {
+            Some(Value::Class(inst)) => Ok(Value::Map(Rc::new(RefCell::new(
+                inst.borrow().fields.clone(),
+            )))),
+            other => Err(Error::Runtime(format!(
+                "fields expects an instance, found {}",
+                other.unwrap_or(&Value::Nil).type_name()
+            ))),
+        }
+    }
+}
+
+/// Serializes a value as JSON. Maps and instances become
+/// objects with their entries in insertion order
+#[derive(Debug, Clone)]
+pub struct ToJson;
+impl Callable for ToJson {
+    fn name(&self) -> &str {
+        "to_json"
+    }
+    fn arity(&self) -> usize {
+        1
+    }
+    fn call(&mut self, _: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+        let mut out = String::new();
+        Self::write(
+            args.first().unwrap_or(&Value::Nil),
+            &mut out,
+            &mut Vec::new(),
+        )?;
+        Ok(Value::String(out))
+    }
+}
+
+impl ToJson {
+    /// `parents` holds the maps and instances currently
+    /// being written, finding one again means a cycle
+    fn write(value: &Value, out: &mut String, parents: &mut Vec<*const ()>) -> Result<(), Error> {
+        match value {
+            Value::String(s) => out.push_str(&crate::json_string(s)),
+            Value::Number(n) if n.is_finite() => out.push_str(&rox_shared::format_number(*n)),
+            Value::Number(_) | Value::Nil => out.push_str("null"),
+            Value::Bool(b) => out.push_str(&b.to_string()),
+            Value::Map(map) => {
+                Self::write_object(map.as_ptr() as *const (), &map.borrow(), out, parents)?
+            }
+            Value::Class(inst) => Self::write_object(
+                inst.as_ptr() as *const (),
+                &inst.borrow().fields,
+                out,
+                parents,
+            )?,
+            other => {
+                return Err(Error::Runtime(format!(
+                    "to_json can't serialize a {}",
+                    other.type_name()
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    fn write_object(
+        ptr: *const (),
+        entries: &OrderedMap<Value>,
+        out: &mut String,
+        parents: &mut Vec<*const ()>,
+    ) -> Result<(), Error> {
+        if parents.contains(&ptr) {
+            return Err(Error::Runtime(
+                "to_json can't serialize a value that contains itself".to_string(),
+            ));
+        }
+        parents.push(ptr);
+        out.push('{');
+        for (i, (key, value)) in entries.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push_str(&crate::json_string(key));
+            out.push(':');
+            Self::write(value, out, parents)?;
+        }
+        out.push('}');
+        parents.pop();
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ToBase;
+impl Callable for ToBase {
+    fn name(&self) -> &str {
+        "to_base"
+    }
+    fn arity(&self) -> usize {
+        2
+    }
+    fn call(&mut self, _: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+        let (n, base) = match args {
+            [Value::Number(n), Value::Number(base)] => (*n, *base),
+            _ => {
+                return Err(Error::Runtime(format!(
+                    "invalid arguments provided to to_base: {:?}",
+                    args
+                )))
+            }
+        };
+        if base.fract() != 0.0 || !(2.0..=36.0).contains(&base) {
+            return Err(Error::Runtime(format!(
+                "to_base expects a base from 2 to 36, found {}",
+                Value::Number(base)
+            )));
+        }
+        if n.fract() != 0.0 || n < 0.0 {
+            return Err(Error::Runtime(format!(
+                "to_base expects a non-negative integer, found {}",
+                Value::Number(n)
+            )));
+        }
+        // u64::MAX rounds up to 2^64 as an f64, which
+        // doesn't fit
+        if n >= u64::MAX as f64 {
+            return Err(Error::Runtime(format!(
+                "to_base expects a number below 2^64, found {}",
+                Value::Number(n)
+            )));
+        }
+        let base = base as u64;
+        let mut n = n as u64;
+        let mut digits = Vec::new();
+        loop {
+            digits.push(std::char::from_digit((n % base) as u32, base as u32).unwrap_or('?'));
+            n /= base;
+            if n == 0 {
+                break;
+            }
+        }
+        Ok(Value::String(digits.into_iter().rev().collect()))
+    }
+}
+
+/// Rebuilds an approximate source string for a user
+/// defined function or class from its parse tree
+#[derive(Debug, Clone)]
+pub struct Source;
+impl Callable for Source {
+    fn name(&self) -> &str {
+        "source"
+    }
+    fn arity(&self) -> usize {
+        1
+    }
+    fn call(&mut self, _: &mut Interpreter, args: &[Value]) -> Result<Value, Error> {
+        // the tree is only printed, so the spans
+        // that go missing here aren't needed
+        let stmt = match args.first() {
+            Some(Value::Func(func)) => Stmt::Func(Function {
+                name: func.name.clone(),
+                span: Span::default(),
+                params: func.params.clone(),
+                param_spans: Vec::new(),
+                body: func.body.clone(),
+            }),
+            Some(Value::Method(method)) => Stmt::Func(Function {
+                name: method.func.name.clone(),
+                span: Span::default(),
+                params: method.func.params.clone(),
+                param_spans: Vec::new(),
+                body: method.func.body.clone(),
+            }),
+            Some(Value::Init(class)) => Stmt::Class {
+                name: class.name.clone(),
+                span: Span::default(),
+                super_class: class
+                    .super_class
+                    .as_ref()
+                    .map(|s| (s.name.clone(), Span::default())),
+                methods: class.methods.to_vec(),
+            },
+            Some(Value::NativeFunc(_)) => return Ok(Value::String("<native>".to_string())),
+            Some(other) => {
+                return Err(Error::Runtime(format!(
+                    "source expects a function or class, found {}",
+                    other.type_name()
+                )))
+            }
+            None => {
+                return Err(Error::Runtime(
+                    "invalid arguments provided to source: []".to_string(),
+                ))
+            }
+        };
+        let source = Printer::print(&[stmt]);
+        Ok(Value::String(source.trim_end().to_string()))
+    }
+}
+
+/// Creates a new, empty map
+#[derive(Debug, Clone)]
+pub struct NewMap;
+impl Callable for NewMap {
+    fn name(&self) -> &str {
+        "new_map"
+    }
+    fn call(&mut self, _: &mut Interpreter, _: &[Value]) -> Result<Value, Error> {
+        Ok(Value::Map(Default::default()))
+    }
+}
+
 impl ::std::fmt::Display for Clock {
     fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
         write!(f, "[native fn clock]")
@@ -83,3 +1044,548 @@ impl ::std::fmt::Display for Mod {
         write!(f, "[native fn mod]")
     }
 }
+impl ::std::fmt::Display for ToNumber {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "[native fn to_number]")
+    }
+}
+impl ::std::fmt::Display for ToString {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "[native fn to_string]")
+    }
+}
+impl ::std::fmt::Display for Floor {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "[native fn floor]")
+    }
+}
+impl ::std::fmt::Display for Ceil {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "[native fn ceil]")
+    }
+}
+impl ::std::fmt::Display for Round {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "[native fn round]")
+    }
+}
+impl ::std::fmt::Display for Abs {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "[native fn abs]")
+    }
+}
+impl ::std::fmt::Display for Weak {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "[native fn weak]")
+    }
+}
+impl ::std::fmt::Display for ReadLine {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "[native fn read_line]")
+    }
+}
+impl ::std::fmt::Display for Assert {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "[native fn assert]")
+    }
+}
+impl ::std::fmt::Display for ToBase {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "[native fn to_base]")
+    }
+}
+impl ::std::fmt::Display for Source {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "[native fn source]")
+    }
+}
+impl ::std::fmt::Display for NewMap {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "[native fn new_map]")
+    }
+}
+impl ::std::fmt::Display for PartialRight {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "[native fn partial_right]")
+    }
+}
+impl ::std::fmt::Display for Partial {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "[partial {}]", self.name())
+    }
+}
+impl ::std::fmt::Display for Printf {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "[native fn printf]")
+    }
+}
+impl ::std::fmt::Display for Len {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "[native fn len]")
+    }
+}
+impl ::std::fmt::Display for Exit {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "[native fn exit]")
+    }
+}
+impl ::std::fmt::Display for TypeOf {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "[native fn typeof]")
+    }
+}
+impl ::std::fmt::Display for Fields {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "[native fn fields]")
+    }
+}
+impl ::std::fmt::Display for ToJson {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "[native fn to_json]")
+    }
+}
+impl ::std::fmt::Display for Times {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "[native fn times]")
+    }
+}
+impl ::std::fmt::Display for HasField {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "[native fn has_field]")
+    }
+}
+impl ::std::fmt::Display for DeleteField {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "[native fn delete_field]")
+    }
+}
+impl ::std::fmt::Display for Memoize {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "[native fn memoize]")
+    }
+}
+impl ::std::fmt::Display for Memoized {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "[memoized {}]", self.name())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{run, run_err, run_with, SharedOutput};
+
+    fn eval(expr: &str) -> Value {
+        run(&format!("var result = {};\n", expr))
+            .env
+            .get("result")
+            .expect("Failed to get result from env")
+    }
+
+    #[test]
+    fn fake_clock() {
+        let lox = "var start = clock();\nvar elapsed = clock() - start;\n";
+        let mut int = Interpreter::new().with_clock(|| 1234.5);
+        run_with(&mut int, lox).unwrap();
+        assert_eq!(int.get_global("start"), Some(Value::Number(1234.5)));
+        assert_eq!(int.get_global("elapsed"), Some(Value::Number(0.0)));
+        assert!(system_clock() > 0.0);
+    }
+
+    #[test]
+    fn to_number() {
+        assert_eq!(eval(r#"to_number("42")"#), Value::Number(42.0));
+        assert_eq!(eval(r#"to_number("1.5")"#), Value::Number(1.5));
+        assert_eq!(eval(r#"to_number("-3")"#), Value::Number(-3.0));
+        assert_eq!(eval(r#"to_number("abc")"#), Value::Nil);
+        assert_eq!(eval(r#"to_number("1.")"#), Value::Nil);
+        assert_eq!(eval(r#"to_number("inf")"#), Value::Nil);
+        assert_eq!(eval(r#"to_number("")"#), Value::Nil);
+        assert_eq!(parse_number("  1.5\n"), Some(1.5));
+        assert_eq!(parse_number("\t-2 "), Some(-2.0));
+        assert_eq!(parse_number("1e3"), Some(1000.0));
+        assert_eq!(parse_number("-0xFF"), Some(-255.0));
+        assert_eq!(parse_number("1_000"), Some(1000.0));
+        for s in &["1_", "- 1", "1 2", "1//", "0x", ".5", "1e"] {
+            assert_eq!(parse_number(s), None, "{}", s);
+        }
+    }
+
+    #[test]
+    fn to_string() {
+        assert_eq!(eval("to_string(1.5)"), Value::String("1.5".to_string()));
+        assert_eq!(eval("to_string(true)"), Value::String("true".to_string()));
+        assert_eq!(eval("to_string(nil)"), Value::String("nil".to_string()));
+        assert_eq!(
+            eval(r#"to_string("text")"#),
+            Value::String("text".to_string())
+        );
+    }
+
+    #[test]
+    fn rounding() {
+        assert_eq!(eval("floor(1.7)"), Value::Number(1.0));
+        assert_eq!(eval("floor(-1.2)"), Value::Number(-2.0));
+        assert_eq!(eval("ceil(1.2)"), Value::Number(2.0));
+        assert_eq!(eval("ceil(-1.7)"), Value::Number(-1.0));
+        assert_eq!(eval("round(1.5)"), Value::Number(2.0));
+        assert_eq!(eval("round(1.4)"), Value::Number(1.0));
+        assert_eq!(eval("abs(-3)"), Value::Number(3.0));
+        assert_eq!(eval("abs(3)"), Value::Number(3.0));
+    }
+
+    #[test]
+    fn read_line() {
+        let lox = "
+print read_line();
+print read_line();
+print read_line();
+";
+        let output = SharedOutput::default();
+        let input = std::io::Cursor::new("first\nsecond\r\n");
+        let mut int = Interpreter::with_io(input, output.clone());
+        run_with(&mut int, lox).unwrap();
+        assert_eq!(output.contents(), "\"first\"\n\"second\"\nnil\n");
+    }
+
+    fn eval_err(expr: &str) -> Error {
+        run_err(&format!("var result = {};\n", expr))
+    }
+
+    #[test]
+    fn assert() {
+        assert_eq!(eval("assert(1 < 2)"), Value::Nil);
+        assert_eq!(eval(r#"assert(true, "unused")"#), Value::Nil);
+        assert_eq!(
+            eval_err("assert(1 > 2)").inner().to_string(),
+            "Runtime error: assertion failed"
+        );
+        assert_eq!(
+            eval_err(r#"assert(1 > 2, "math is broken")"#)
+                .inner()
+                .to_string(),
+            "Runtime error: assertion failed: math is broken"
+        );
+        assert_eq!(
+            eval_err("assert()").inner().to_string(),
+            "Runtime error: [native fn assert] was expecting 1 to 2 arguments but 0 were provided"
+        );
+    }
+
+    #[test]
+    fn partial_right() {
+        let lox = "
+fun sub(a, b) {
+    return a - b;
+}
+var minus_two = partial_right(sub, 2);
+var result = minus_two(10);
+var shown = to_string(minus_two);
+";
+        let int = run(lox);
+        assert_eq!(int.env.get("result").unwrap(), Value::Number(8.0));
+        assert_eq!(
+            int.env.get("shown").unwrap(),
+            Value::String("[partial sub]".to_string())
+        );
+        assert_eq!(
+            eval_err("partial_right(clock, 1)").inner().to_string(),
+            "Runtime error: partial_right expects a function that takes arguments, found [native fn clock]"
+        );
+        let err = run_err(&format!("{}\nminus_two(1, 2);", lox));
+        assert_eq!(
+            err.inner().to_string(),
+            "Runtime error: [partial sub] was expecting 1 arguments but 2 were provided"
+        );
+    }
+
+    #[test]
+    fn printf() {
+        let lox = r#"
+printf("no placeholders");
+printf("{} + {} = {}", 1, 2, 1 + 2);
+printf("{}!", "hi");
+var fmt = memoize(printf);
+fmt("{}{}", nil, true);
+"#;
+        let out = SharedOutput::default();
+        let mut int = Interpreter::with_io(std::io::empty(), out.clone());
+        run_with(&mut int, lox).unwrap();
+        assert_eq!(out.contents(), "no placeholders\n1 + 2 = 3\nhi!\nniltrue\n");
+        assert_eq!(
+            eval_err(r#"printf("{} {}", 1)"#).inner().to_string(),
+            "Runtime error: printf format has 2 placeholders but 1 values were provided"
+        );
+        assert_eq!(
+            eval_err("printf()").inner().to_string(),
+            "Runtime error: [native fn printf] was expecting at least 1 arguments but 0 were provided"
+        );
+        assert_eq!(
+            eval_err("printf(1)").inner().to_string(),
+            "Runtime error: printf expects a format string, found number"
+        );
+    }
+
+    #[test]
+    fn new_map() {
+        assert_eq!(
+            eval("to_string(new_map)"),
+            Value::String("[native fn new_map]".to_string())
+        );
+        // `map` is left for a function that maps over a list
+        assert_eq!(
+            run_err("map();").to_string(),
+            "[line 1:1] Runtime error: variable \"map\" is not yet defined"
+        );
+    }
+
+    #[test]
+    fn len() {
+        assert_eq!(eval(r#"len("")"#), Value::Number(0.0));
+        assert_eq!(eval(r#"len("lox")"#), Value::Number(3.0));
+        assert_eq!(eval(r#"len("héllo")"#), Value::Number(5.0));
+        assert_eq!(eval(r#"len("日本")"#), Value::Number(2.0));
+        assert_eq!(eval(r#"len("🦀!")"#), Value::Number(2.0));
+        assert_eq!(eval("len(new_map())"), Value::Number(0.0));
+        assert_eq!(
+            eval_err("len(5)").inner().to_string(),
+            "Runtime error: len expects a string or map, found number"
+        );
+        assert_eq!(Value::Nil.length(), None);
+        assert_eq!(Value::Bool(true).length(), None);
+    }
+
+    #[test]
+    fn ordered_fields() {
+        let lox = r#"
+class Point {}
+var p = Point();
+p.y = 2;
+p.x = 1;
+p.label = "two
+lines";
+p.y = 3;
+var m = new_map();
+m.z = true;
+m.a = nil;
+m.point = p;
+var shown = to_string(fields(p));
+var json = to_json(m);
+"#;
+        for _ in 0..5 {
+            let int = run(lox);
+            assert_eq!(
+                int.get_global("shown"),
+                Some(Value::String(
+                    "{y: 3, x: 1, label: \"two\nlines\"}".to_string()
+                ))
+            );
+            assert_eq!(
+                int.get_global("json"),
+                Some(Value::String(
+                    r#"{"z":true,"a":null,"point":{"y":3,"x":1,"label":"two\nlines"}}"#.to_string()
+                ))
+            );
+        }
+        assert_eq!(
+            run_err("var m = new_map();\nm.me = m;\nto_json(m);")
+                .inner()
+                .to_string(),
+            "Runtime error: to_json can't serialize a value that contains itself"
+        );
+        assert_eq!(
+            eval_err("to_json(clock)").inner().to_string(),
+            "Runtime error: to_json can't serialize a native function"
+        );
+        assert_eq!(
+            eval_err("fields(1)").inner().to_string(),
+            "Runtime error: fields expects an instance, found number"
+        );
+    }
+
+    #[test]
+    fn exit() {
+        let err = run_err("fun stop() {\n  exit(3);\n  print 1;\n}\nstop();\nprint 2;");
+        assert!(matches!(err, Error::Exit(3)), "{:?}", err);
+        assert_eq!(
+            eval_err("exit(1.5)").inner().to_string(),
+            "Runtime error: exit expects an integer exit code, found 1.5"
+        );
+    }
+
+    #[test]
+    fn type_of() {
+        let lox = r#"
+class Point {
+    norm() { return 0; }
+}
+fun f() {}
+var p = Point();
+var results = new_map();
+results.number = typeof(1);
+results.string = typeof("s");
+results.bool = typeof(false);
+results.nothing = typeof(nil);
+results.function = typeof(f);
+results.ctor = typeof(Point);
+results.instance = typeof(p);
+results.native = typeof(clock);
+results.method = typeof(p.norm);
+results.map = typeof(results);
+results.weak = typeof(weak(p));
+"#;
+        let int = run(lox);
+        assert_eq!(
+            int.get_global("results").unwrap().to_string(),
+            "{number: \"number\", string: \"string\", bool: \"bool\", nothing: \"nil\", \
+             function: \"function\", ctor: \"class\", instance: \"instance\", \
+             native: \"function\", method: \"function\", map: \"instance\", \
+             weak: \"instance\"}"
+        );
+    }
+
+    #[test]
+    fn times() {
+        let lox = "
+var count = 0;
+var seen = \"\";
+fun bump() { count = count + 1; }
+fun record(i) { seen = seen + to_string(i); }
+var result = times(3, bump);
+times(4, record);
+times(0, record);
+";
+        let int = run(lox);
+        assert_eq!(int.get_global("result"), Some(Value::Nil));
+        assert_eq!(int.get_global("count"), Some(Value::Number(3.0)));
+        assert_eq!(
+            int.get_global("seen"),
+            Some(Value::String("0123".to_string()))
+        );
+        assert_eq!(
+            eval_err("times(-1, clock)").inner().to_string(),
+            "Runtime error: times expects a non-negative integer, found -1"
+        );
+        assert_eq!(
+            eval_err("times(1.5, clock)").inner().to_string(),
+            "Runtime error: times expects a non-negative integer, found 1.5"
+        );
+        assert_eq!(
+            eval_err("times(1, to_base)").inner().to_string(),
+            "Runtime error: times expects a function taking zero or one arguments, found [native fn to_base]"
+        );
+    }
+
+    #[test]
+    fn delete_field() {
+        let lox = r#"
+class Box {
+    peek() { return this.item; }
+}
+var box = Box();
+box.item = 1;
+var before = has_field(box, "item");
+var removed = delete_field(box, "item");
+var after = has_field(box, "item");
+var again = delete_field(box, "item");
+var method = delete_field(box, "peek");
+var still_callable = box.peek;
+"#;
+        let int = run(lox);
+        assert_eq!(int.env.get("before").unwrap(), Value::Bool(true));
+        assert_eq!(int.env.get("removed").unwrap(), Value::Number(1.0));
+        assert_eq!(int.env.get("after").unwrap(), Value::Bool(false));
+        assert_eq!(int.env.get("again").unwrap(), Value::Nil);
+        assert_eq!(int.env.get("method").unwrap(), Value::Nil);
+        assert!(matches!(
+            int.env.get("still_callable").unwrap(),
+            Value::Method(_)
+        ));
+        assert_eq!(
+            eval_err(r#"delete_field(1, "a")"#).inner().to_string(),
+            "Runtime error: delete_field expects an instance and a field name, found number and string"
+        );
+    }
+
+    #[test]
+    fn memoize() {
+        let lox = "
+var calls = 0;
+fun square(n) {
+    calls = calls + 1;
+    return n * n;
+}
+var fast = memoize(square);
+var first = fast(3);
+var second = fast(3);
+var third = fast(4);
+";
+        let int = run(lox);
+        assert_eq!(int.env.get("first").unwrap(), Value::Number(9.0));
+        assert_eq!(int.env.get("second").unwrap(), Value::Number(9.0));
+        assert_eq!(int.env.get("third").unwrap(), Value::Number(16.0));
+        assert_eq!(int.env.get("calls").unwrap(), Value::Number(2.0));
+    }
+
+    #[test]
+    fn source() {
+        let lox = "
+fun add(left, right) {
+    var sum = left + right;
+    return sum;
+}
+var result = source(add);
+var native = source(clock);
+";
+        let int = run(lox);
+        assert_eq!(
+            int.env.get("result").unwrap(),
+            Value::String(
+                "fun add(left, right) {\n  var sum = left + right;\n  return sum;\n}".to_string()
+            )
+        );
+        assert_eq!(
+            int.env.get("native").unwrap(),
+            Value::String("<native>".to_string())
+        );
+        assert_eq!(
+            eval_err("source(1)").inner().to_string(),
+            "Runtime error: source expects a function or class, found number"
+        );
+    }
+
+    #[test]
+    fn to_base() {
+        assert_eq!(eval("to_base(255, 16)"), Value::String("ff".to_string()));
+        assert_eq!(eval("to_base(5, 2)"), Value::String("101".to_string()));
+        assert_eq!(eval("to_base(0, 8)"), Value::String("0".to_string()));
+        assert_eq!(eval("to_base(35, 36)"), Value::String("z".to_string()));
+        assert_eq!(
+            eval_err("to_base(10, 1)").inner().to_string(),
+            "Runtime error: to_base expects a base from 2 to 36, found 1"
+        );
+        assert_eq!(
+            eval_err("to_base(10, 37)").inner().to_string(),
+            "Runtime error: to_base expects a base from 2 to 36, found 37"
+        );
+        assert_eq!(
+            eval_err("to_base(-1, 2)").inner().to_string(),
+            "Runtime error: to_base expects a non-negative integer, found -1"
+        );
+        assert_eq!(
+            eval_err("to_base(1.5, 2)").inner().to_string(),
+            "Runtime error: to_base expects a non-negative integer, found 1.5"
+        );
+        // the largest f64 below 2^64
+        assert_eq!(
+            eval("to_base(18446744073709549568, 16)"),
+            Value::String("fffffffffffff800".to_string())
+        );
+        assert_eq!(
+            eval_err("to_base(18446744073709551616, 16)")
+                .inner()
+                .to_string(),
+            "Runtime error: to_base expects a number below 2^64, found 18446744073709552000"
+        );
+    }
+}
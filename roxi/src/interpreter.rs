@@ -1,9 +1,8 @@
 use super::{
-    callable::Callable,
-    class::Class,
+    class::{Class, ClassInstance, Method},
     env::Env,
     error::Error,
-    expr::{Expr, ExprVisitor, Literal},
+    expr::{Binding, Expr, ExprVisitor, Literal, Span},
     func::Func,
     stmt::{Function, Stmt, StmtVisitor},
     value::Value,
@@ -11,50 +10,59 @@ use super::{
 use rox_shared::{Token, TokenType};
 
 use log::trace;
+use std::{
+    cell::{Cell, RefCell},
+    io::{stdin, stdout, BufRead, BufReader, Write},
+    rc::{Rc, Weak},
+};
 
 pub struct Interpreter {
     pub env: Env,
-    pub closures: Vec<Env>,
+    /// Where `read_line` pulls its input from
+    pub input: Box<dyn BufRead>,
+    /// Where `print` statements are written
+    pub output: Box<dyn Write>,
+    /// How many calls are currently running
+    depth: usize,
+    /// How deep calls can nest before giving up, this keeps
+    /// runaway recursion from overflowing the native stack
+    max_depth: usize,
+    /// How many statements a run can execute, `None` for no limit
+    gas: Option<usize>,
+    /// How much of `gas` is left
+    gas_left: Option<usize>,
+    /// Let dividing by zero produce `inf` or `NaN` instead
+    /// of failing
+    ieee_division: bool,
+    /// Where `clock` reads the time from, in milliseconds
+    clock: Box<dyn Fn() -> f64>,
 }
 
+/// The default for `Interpreter::max_depth`, each Lox call
+/// takes several native frames so this stays well inside
+/// an 8MB main thread stack even in debug builds. Going
+/// deeper needs a thread with a bigger stack, see
+/// `STACK_PER_CALL`
+pub const MAX_DEPTH: usize = 256;
+
+/// About how much native stack a single Lox call can use
+/// in a debug build, with room to spare. A thread running
+/// with a `max_depth` of `n` needs `n` times this
+pub const STACK_PER_CALL: usize = 32 * 1024;
+
 type IntResult = Result<Value, Error>;
 
 impl ExprVisitor<Value> for Interpreter {
-    fn visit_bin(&mut self, left: &mut Expr, op: &Token, right: &mut Expr) -> IntResult {
+    fn visit_bin(&mut self, left: &Expr, op: &Token, right: &Expr) -> IntResult {
         trace!("visit_bin {:?}. {:?} {:?}", left, op.lexeme, right);
         let left = self.evaluate(left)?;
         let right = self.evaluate(right)?;
-        let ret = match (&op.kind, &left, &right) {
-            (TokenType::Minus, Value::Number(lhs), Value::Number(rhs)) => Value::Number(lhs - rhs),
-            (TokenType::Slash, Value::Number(lhs), Value::Number(rhs)) => Value::Number(lhs / rhs),
-            (TokenType::Star, Value::Number(lhs), Value::Number(rhs)) => Value::Number(lhs * rhs),
-            (TokenType::Plus, Value::Number(lhs), Value::Number(rhs)) => Value::Number(lhs + rhs),
-            (TokenType::Greater, Value::Number(lhs), Value::Number(rhs)) => Value::Bool(lhs > rhs),
-            (TokenType::GreaterEqual, Value::Number(lhs), Value::Number(rhs)) => {
-                Value::Bool(lhs >= rhs)
-            }
-            (TokenType::Less, Value::Number(lhs), Value::Number(rhs)) => Value::Bool(lhs < rhs),
-            (TokenType::LessEqual, Value::Number(lhs), Value::Number(rhs)) => {
-                Value::Bool(lhs <= rhs)
-            }
-            (TokenType::Plus, Value::String(lhs), Value::String(rhs)) => {
-                Value::String(format!("{}{}", lhs, rhs))
-            }
-            (TokenType::EqualEqual, l, r) => Value::Bool(Self::is_equal(l, r)),
-            (TokenType::BangEqual, l, r) => Value::Bool(!Self::is_equal(l, r)),
-            _ => {
-                return Err(Error::Runtime(format!(
-                    "Invalid binary operation: {:?} {:?} {:?}",
-                    left, op, right
-                )))
-            }
-        };
-        Ok(ret)
+        self.binary_op(op, left, right)
     }
 
-    fn visit_group(&mut self, group: &mut Expr) -> IntResult {
+    fn visit_group(&mut self, group: &Expr) -> IntResult {
         trace!("visit_group {:?}", group);
-        if let Expr::Grouping(inner) = group {
+        if let Expr::Grouping(inner, _) = group {
             self.evaluate(inner)
         } else {
             Err(Error::Runtime("Visited group unexpectedly".into()))
@@ -66,134 +74,201 @@ impl ExprVisitor<Value> for Interpreter {
         Ok(lit.clone_into())
     }
 
-    fn visit_un(&mut self, op: &Token, ex: &mut Expr) -> IntResult {
+    fn visit_un(&mut self, op: &Token, ex: &Expr) -> IntResult {
         trace!("visit_unary {:?} {:?}", op.lexeme, ex);
         let right = self.evaluate(ex)?;
+        // Lox has no unary `+`, the parser only produces `-` and `!`
         let ret = match (&op.kind, right) {
             (TokenType::Minus, Value::Number(n)) => Value::Number(-n),
-            (TokenType::Plus, Value::Number(n)) => Value::Number(n),
             (TokenType::Bang, a) => Value::Bool(!Self::is_truthy(&a)),
-            _ => {
+            (_, a) => {
                 return Err(Error::Runtime(format!(
-                    "Invalid unary operation {:?}, {:?}",
-                    op, ex
-                )))
+                    "Operand of '{}' must be a number, got {}",
+                    op.lexeme,
+                    a.type_name()
+                ))
+                .at(op))
             }
         };
         Ok(ret)
     }
 
-    fn visit_var(&mut self, name: &str) -> IntResult {
-        trace!("visit_var {}", name);
-        self.env.get(name)
+    fn visit_var(&mut self, name: &str, span: Span, binding: &Cell<Binding>) -> IntResult {
+        trace!("visit_var {} {:?}", name, binding);
+        self.env
+            .get_bound(name, binding.get())
+            .map_err(|e| e.at_span(span))
     }
 
-    fn visit_assign(&mut self, name: &str, expr: &mut Expr) -> IntResult {
+    fn visit_assign(
+        &mut self,
+        name: &str,
+        expr: &Expr,
+        span: Span,
+        binding: &Cell<Binding>,
+    ) -> IntResult {
         trace!("visit_assign {:?} {:?}", name, expr);
         let mut val = self.evaluate(expr)?;
-        match &mut val {
-            Value::Class(ref mut inst) => {
-                for (_, method) in inst.methods.iter_mut() {
-                    method.this_name = name.to_string()
-                }
-            }
-            _ => (),
+        if let Value::Func(ref mut f) = val {
+            f.name = name.to_string();
         }
-        self.env.assign(name, val)
+        self.env
+            .assign_bound(name, binding.get(), val)
+            .map_err(|e| e.at_span(span))
     }
 
-    fn visit_log(&mut self, left: &mut Expr, op: &Token, right: &mut Expr) -> IntResult {
+    fn visit_log(&mut self, left: &Expr, op: &Token, right: &Expr) -> IntResult {
         trace!("visit_log {:?} {:?} {:?}", left, op.lexeme, right);
         let left = self.evaluate(left)?;
-        let ret = match (&op.kind, Self::is_truthy(&left)) {
-            (TokenType::Or, true) => Value::Bool(true),
-            (TokenType::Or, false) | (TokenType::And, true) => {
-                Value::Bool(Self::is_truthy(&self.evaluate(right)?))
-            }
-            _ => Value::Bool(false),
-        };
-        Ok(ret)
+        match (&op.kind, Self::is_truthy(&left)) {
+            (TokenType::Or, true) | (TokenType::And, false) => Ok(left),
+            _ => self.evaluate(right),
+        }
     }
 
-    fn visit_call(&mut self, callee: &mut Expr, arguments: &mut [Expr]) -> IntResult {
+    fn visit_call(&mut self, callee: &Expr, paren: &Token, arguments: &[Expr]) -> IntResult {
         trace!("visit_call {:?} {:?}", callee, arguments);
         let mut callee = self.evaluate(callee)?;
         let args = arguments
-            .iter_mut()
+            .iter()
             .map(|e| self.evaluate(e))
             .collect::<Result<Vec<Value>, Error>>()?;
-        match &mut callee {
-            Value::Func(c) => {
-                let v = self.handle_callable(c, &args)?;
-                Ok(v)
-            }
-            Value::Init(c) => self.handle_callable(c, &args),
-            Value::NativeFunc(c) => self.handle_callable(c, &args),
-            Value::Method(m) => self.handle_callable(m, &args),
-            _ => Err(Error::Runtime(format!(
-                "Attempt to call a something that is not a function {}",
-                callee
-            ))),
-        }
+        Self::check_call(&callee, args.len()).map_err(|e| e.at(paren))?;
+        self.call_value(&mut callee, &args).map_err(|e| e.at(paren))
     }
 
-    fn visit_get(&mut self, object: &mut Expr, name: &str) -> IntResult {
+    fn visit_get(&mut self, object: &Expr, name: &str, span: Span) -> IntResult {
         trace!("visit_get {:?} {:?}", object, name);
-        if let Value::Class(class) = self.evaluate(object)? {
-            class.get(name)
-        } else {
-            Err(Error::Runtime(format!(
-                "cannot find property {} on {:?}",
-                name, object
-            )))
+        let object = self.evaluate(object)?;
+        Self::get_property(object, name).map_err(|e| e.at_span(span))
+    }
+    fn visit_optional_get(&mut self, object: &Expr, name: &str) -> IntResult {
+        trace!("visit_optional_get {:?} {:?}", object, name);
+        match self.evaluate(object)? {
+            Value::Nil => Ok(Value::Nil),
+            object => Self::get_property(object, name),
         }
     }
-    fn visit_set(&mut self, object: &mut Expr, name: &str, value: &mut Expr) -> IntResult {
+    fn visit_set(&mut self, object: &Expr, name: &str, value: &Expr) -> IntResult {
         trace!("visit_set {:?} {:?} {:?}", object, name, value);
         let value = self.evaluate(value)?;
-        if let Value::Class(inst) = self.evaluate_mut(object)? {
-            inst.set(name, value.clone());
-        }
-
+        let object = self.evaluate(object)?;
+        Self::set_property(&object, name, value.clone())?;
+        Ok(value)
+    }
+    fn visit_compound_set(
+        &mut self,
+        object: &Expr,
+        name: &str,
+        operator: &Token,
+        value: &Expr,
+    ) -> IntResult {
+        trace!("visit_compound_set {:?} {:?} {:?}", object, name, value);
+        // like `visit_postfix`, the object is only evaluated once
+        let object = self.evaluate(object)?;
+        let current = Self::get_property(object.clone(), name)?;
+        let value = self.evaluate(value)?;
+        let value = self.binary_op(operator, current, value)?;
+        Self::set_property(&object, name, value.clone())?;
         Ok(value)
     }
     fn visit_this(&mut self) -> IntResult {
         trace!("visit_this");
         self.env.get("this")
     }
+    fn visit_postfix(&mut self, target: &Expr, operator: &Token) -> IntResult {
+        trace!("visit_postfix {:?} {:?}", target, operator.lexeme);
+        // the object is only evaluated once, it is kept
+        // around to write the updated value back to
+        let (object, old) = match target {
+            Expr::Var(name, _, binding) => (None, self.env.get_bound(name, binding.get())?),
+            Expr::Get { object, name, .. } => {
+                let object = self.evaluate(object)?;
+                let old = Self::get_property(object.clone(), name)?;
+                (Some(object), old)
+            }
+            _ => {
+                return Err(
+                    Error::Runtime(format!("Invalid target for {}", operator.kind)).at(operator),
+                )
+            }
+        };
+        let new = match (&operator.kind, &old) {
+            (TokenType::PlusPlus, Value::Number(n)) => Value::Number(n + 1.0),
+            (TokenType::MinusMinus, Value::Number(n)) => Value::Number(n - 1.0),
+            _ => {
+                return Err(Error::Runtime(format!(
+                    "Operand of '{}' must be a number, got {}",
+                    operator.lexeme,
+                    old.type_name()
+                ))
+                .at(operator))
+            }
+        };
+        match (target, object) {
+            (Expr::Var(name, _, binding), _) => {
+                self.env.assign_bound(name, binding.get(), new)?;
+            }
+            (Expr::Get { name, .. }, Some(object)) => Self::set_property(&object, name, new)?,
+            _ => (),
+        }
+        Ok(old)
+    }
+    fn visit_super(&mut self, method: &str, span: Span) -> IntResult {
+        trace!("visit_super {}", method);
+        let (super_class, this) = match (self.env.get("super"), self.env.get("this")) {
+            (Ok(Value::Init(super_class)), Ok(Value::Class(this))) => (super_class, this),
+            _ => {
+                return Err(Error::Runtime(
+                    "super can only be used in a method of a subclass".to_string(),
+                )
+                .at_span(span))
+            }
+        };
+        let func = super_class.bound_method(method).ok_or_else(|| {
+            Error::Runtime(format!(
+                "Undefined method {} on superclass {}",
+                method, super_class.name
+            ))
+            .at_span(span)
+        })?;
+        Ok(Value::Method(Method { func, this }))
+    }
+    fn visit_interpolation(&mut self, parts: &[Expr]) -> IntResult {
+        trace!("visit_interpolation {:?}", parts);
+        let mut ret = String::new();
+        for part in parts {
+            ret.push_str(&self.evaluate(part)?.stringify());
+        }
+        Ok(Value::String(ret))
+    }
 }
 
 impl StmtVisitor<()> for Interpreter {
-    fn visit_expr_stmt(&mut self, expr: &mut Expr) -> Result<(), Error> {
+    fn visit_expr_stmt(&mut self, expr: &Expr) -> Result<(), Error> {
         trace!("visit_expr_stmt {:?}", expr);
         self.evaluate(expr)?;
         Ok(())
     }
 
-    fn visit_print_stmt(&mut self, expr: &mut Expr) -> Result<(), Error> {
+    fn visit_print_stmt(&mut self, expr: &Expr) -> Result<(), Error> {
         trace!("visit_expr_stmt {:?}", expr);
-        println!("{}", self.evaluate(expr)?);
+        let value = self.evaluate(expr)?;
+        writeln!(self.output, "{}", value)
+            .map_err(|e| Error::Runtime(format!("IO Error: {}", e)))?;
         Ok(())
     }
 
-    fn visit_var_stmt(&mut self, name: &str, expr: &mut Option<Expr>) -> Result<(), Error> {
+    fn visit_var_stmt(&mut self, name: &str, _: Span, expr: &Option<Expr>) -> Result<(), Error> {
         trace!("visit_var_stmt {:?} {:?}", name, expr);
-        let value = if let Some(ref mut expr) = expr {
+        let value = if let Some(expr) = expr {
             let mut val = match expr.accept(self) {
                 Ok(val) | Err(Error::Return(val)) => val,
                 Err(e) => return Err(e),
             };
             trace!("defining {} with {}", name, val);
-            match val {
-                Value::Class(ref mut inst) => {
-                    for (_, meth) in inst.methods.iter_mut() {
-                        meth.this_name = name.to_string()
-                    }
-                }
-                Value::Func(ref mut f) => {
-                    f.name = name.to_string();
-                }
-                _ => (),
+            if let Value::Func(ref mut f) = val {
+                f.name = name.to_string();
             }
             Some(val)
         } else {
@@ -203,7 +278,7 @@ impl StmtVisitor<()> for Interpreter {
         Ok(())
     }
 
-    fn visit_block_stmt(&mut self, list: &mut [Stmt]) -> Result<(), Error> {
+    fn visit_block_stmt(&mut self, list: &[Stmt]) -> Result<(), Error> {
         trace!("visit_block_stmt {:?}", list);
         self.execute_block(list)?;
         Ok(())
@@ -211,50 +286,67 @@ impl StmtVisitor<()> for Interpreter {
 
     fn visit_if_stmt(
         &mut self,
-        test: &mut Expr,
-        cons: &mut Stmt,
-        alt: &mut Option<Box<Stmt>>,
+        test: &Expr,
+        cons: &Stmt,
+        alt: &Option<Box<Stmt>>,
     ) -> Result<(), Error> {
         trace!("visit_if_stmt {:?} {:?} {:?}", test, cons, alt);
         let boolean = self.evaluate(test)?;
         if Self::is_truthy(&boolean) {
-            self.interpret(cons)?;
+            self.execute(cons)?;
         } else if let Some(alt) = alt {
-            self.interpret(alt)?;
+            self.execute(alt)?;
         }
         Ok(())
     }
 
-    fn visit_while_stmt(&mut self, test: &mut Expr, body: &mut Stmt) -> Result<(), Error> {
+    fn visit_while_stmt(&mut self, test: &Expr, body: &Stmt) -> Result<(), Error> {
         trace!("visit_while_stmt {:?} {:?}", test, body);
         while Self::is_truthy(&self.evaluate(test)?) {
-            self.interpret(body)?;
+            self.execute(body)?;
         }
         Ok(())
     }
 
-    fn visit_func_decl(
+    fn visit_for_stmt(
         &mut self,
-        name: &str,
-        params: &[String],
-        body: &[Stmt],
+        init: &Option<Box<Stmt>>,
+        test: &Expr,
+        update: &Option<Expr>,
+        body: &Stmt,
     ) -> Result<(), Error> {
-        trace!("visit_func_decl {:?} {:?} {:?}", name, params, body);
-        let env = self.env.clone_to_base();
+        trace!(
+            "visit_for_stmt {:?} {:?} {:?} {:?}",
+            init,
+            test,
+            update,
+            body
+        );
+        self.env.descend();
+        let ret = self.run_for(init, test, update, body);
+        self.env.ascend();
+        ret
+    }
 
-        let func = Func {
-            name: name.to_string(),
-            params: params.to_vec(),
-            body: body.to_vec(),
-            env,
-            env_idx: self.env.depth() - 1,
+    fn visit_func_decl(&mut self, func: &Function) -> Result<(), Error> {
+        trace!(
+            "visit_func_decl {:?} {:?} {:?}",
+            func.name,
+            func.params,
+            func.body
+        );
+        let value = Func {
+            name: func.name.clone(),
+            params: func.params.clone(),
+            body: func.body.clone(),
+            env: self.env.clone(),
         };
 
-        self.env.define(name, Some(Value::Func(func)));
+        self.env.define(&func.name, Some(Value::Func(value)));
         Ok(())
     }
 
-    fn visit_return_stmt(&mut self, expr: &mut Option<Expr>) -> Result<(), Error> {
+    fn visit_return_stmt(&mut self, expr: &Option<Expr>, _: Span) -> Result<(), Error> {
         trace!("visit_return_stmt {:?}", expr);
         let ret = if let Some(expr) = expr {
             self.evaluate(expr)?
@@ -263,13 +355,43 @@ impl StmtVisitor<()> for Interpreter {
         };
         Err(Error::Return(ret))
     }
-    fn visit_class(&mut self, name: &str, methods: &mut [Function]) -> Result<(), Error> {
-        trace!("visit_return_stmt {} {:?}", name, methods.len());
+    fn visit_class(
+        &mut self,
+        name: &str,
+        _: Span,
+        super_class: Option<(&str, Span)>,
+        methods: &[Function],
+    ) -> Result<(), Error> {
+        trace!("visit_class {} {:?} {:?}", name, super_class, methods.len());
+        let super_class = match super_class {
+            Some((super_class, span)) => {
+                match self.env.get(super_class).map_err(|e| e.at_span(span))? {
+                    Value::Init(class) => Some(Rc::new(class)),
+                    other => {
+                        return Err(Error::Runtime(format!(
+                            "Superclass of {} must be a class, found {}",
+                            name,
+                            other.type_name()
+                        ))
+                        .at_span(span))
+                    }
+                }
+            }
+            None => None,
+        };
         self.env.define(name, None);
+        // methods of a subclass see `super` in a scope
+        // between the class and its methods
+        let mut env = self.env.clone();
+        if let Some(super_class) = &super_class {
+            env.descend();
+            env.define("super", Some(Value::Init(Class::clone(super_class))));
+        }
         let class = Class {
             name: name.to_string(),
-            methods: methods.to_vec(),
-            env_idx: self.env.depth(),
+            super_class,
+            methods: Rc::new(methods.to_vec()),
+            env,
         };
         let value = Value::Init(class);
         self.env.assign(name, value)?;
@@ -278,86 +400,342 @@ impl StmtVisitor<()> for Interpreter {
 }
 impl Default for Interpreter {
     fn default() -> Self {
-        let env = Env::root();
-        Self {
-            env,
-            closures: Vec::new(),
-        }
+        Self::new()
     }
 }
 impl Interpreter {
     pub fn new() -> Self {
-        let env = Env::root();
+        Self::with_io(BufReader::new(stdin()), stdout())
+    }
+
+    /// Create an interpreter that reads from `input` and
+    /// writes to `output` instead of stdin/stdout
+    pub fn with_io(input: impl BufRead + 'static, output: impl Write + 'static) -> Self {
         Self {
-            env,
-            closures: Vec::new(),
+            env: Env::root(),
+            input: Box::new(input),
+            output: Box::new(output),
+            depth: 0,
+            max_depth: MAX_DEPTH,
+            gas: None,
+            gas_left: None,
+            ieee_division: false,
+            clock: Box::new(crate::globals::system_clock),
         }
     }
 
-    pub fn interpret(&mut self, stmt: &mut Stmt) -> Result<(), Error> {
-        trace!("interpret: {:?}", stmt);
-        let ret = stmt.accept(self);
-        trace!("completing interpret {:?}", ret);
-        ret?;
-        Ok(())
+    /// Limit how deep calls can nest
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
     }
 
-    pub fn evaluate(&mut self, expr: &mut Expr) -> Result<Value, Error> {
-        expr.accept(self)
+    /// Limit how many statements can run in total, every
+    /// statement a loop body or call runs counts
+    pub fn with_gas(mut self, gas: usize) -> Self {
+        self.gas = Some(gas);
+        self.gas_left = Some(gas);
+        self
     }
 
-    pub fn evaluate_mut(&mut self, expr: &mut Expr) -> Result<&mut Value, Error> {
-        match expr {
-            Expr::Var(name) => self.env.get_mut(name),
-            Expr::Get { object, name } => {
-                if let Value::Class(inst) = self.evaluate_mut(object)? {
-                    inst.get_mut(name)
-                } else {
-                    Err(Error::Runtime(
-                        "Invalid left hand side of assignment".to_string(),
-                    ))
-                }
+    /// Restore the full gas budget, so each entry at the
+    /// prompt gets its own
+    pub fn refuel(&mut self) {
+        self.gas_left = self.gas;
+    }
+
+    /// Follow IEEE 754 when dividing by zero rather than
+    /// returning an error
+    pub fn with_ieee_division(mut self, ieee_division: bool) -> Self {
+        self.ieee_division = ieee_division;
+        self
+    }
+
+    /// Replace the time source `clock` reads, handy for
+    /// making time dependent programs reproducible
+    pub fn with_clock(mut self, clock: impl Fn() -> f64 + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// The current time in milliseconds, from the clock
+    /// source this interpreter was built with
+    pub(crate) fn now(&self) -> f64 {
+        (self.clock)()
+    }
+
+    /// Look up a global after a program has run, unlike
+    /// `env.get` this never sees a local of the same name
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.env.get_global(name)
+    }
+
+    /// Run a top level statement, a `return` can only
+    /// stop a function so one reaching this far is an error
+    pub fn interpret(&mut self, stmt: &Stmt) -> Result<(), Error> {
+        match self.execute(stmt) {
+            Err(Error::Return(_)) => {
+                Err(Error::Runtime("'return' outside of function".to_string()))
+            }
+            ret => ret,
+        }
+    }
+
+    fn execute(&mut self, stmt: &Stmt) -> Result<(), Error> {
+        trace!("execute: {:?}", stmt);
+        if let Some(gas) = &mut self.gas_left {
+            if *gas == 0 {
+                return Err(Error::Runtime("out of gas".to_string()));
             }
-            Expr::This => self.env.get_mut("this"),
-            _ => Err(Error::Runtime(
-                "Invalid left hand side of assignment".to_string(),
-            )),
+            *gas -= 1;
         }
+        let ret = stmt.accept(self);
+        trace!("completing execute {:?}", ret);
+        ret
+    }
+
+    pub fn evaluate(&mut self, expr: &Expr) -> Result<Value, Error> {
+        expr.accept(self)
     }
 
-    pub fn execute_block(&mut self, stmts: &mut [Stmt]) -> Result<(), Error> {
+    pub fn execute_block(&mut self, stmts: &[Stmt]) -> Result<(), Error> {
         self.env.descend();
+        let ret = self.execute_all(stmts);
+        self.env.ascend();
+        ret
+    }
+
+    /// Run `stmts` in the current scope, a function's body
+    /// shares the scope its parameters are defined in
+    pub fn execute_all(&mut self, stmts: &[Stmt]) -> Result<(), Error> {
         for stmt in stmts {
-            if let Err(e) = self.interpret(stmt) {
-                self.env.ascend();
-                return Err(e);
+            self.execute(stmt)?;
+        }
+        Ok(())
+    }
+
+    fn run_for(
+        &mut self,
+        init: &Option<Box<Stmt>>,
+        test: &Expr,
+        update: &Option<Expr>,
+        body: &Stmt,
+    ) -> Result<(), Error> {
+        if let Some(init) = init {
+            self.execute(init)?;
+        }
+        while Self::is_truthy(&self.evaluate(test)?) {
+            self.execute(body)?;
+            // closures from this iteration keep the old copy
+            // of the loop variables, the update only touches
+            // the next iteration's
+            self.env.fork();
+            if let Some(update) = update {
+                self.evaluate(update)?;
             }
         }
-        self.env.ascend();
         Ok(())
     }
 
-    fn handle_callable<T>(&mut self, f: &mut T, arguments: &[Value]) -> Result<Value, Error>
-    where
-        T: Callable + ?Sized,
-    {
-        if f.arity() != arguments.len() {
+    /// Apply a bitwise operator, both sides have to be
+    /// integers and are treated as `i64`s
+    fn bitwise(op: &Token, lhs: &Value, rhs: &Value) -> IntResult {
+        let (lhs, rhs) = match (Self::as_int(lhs), Self::as_int(rhs)) {
+            (Some(lhs), Some(rhs)) => (lhs, rhs),
+            _ => {
+                return Err(Error::Runtime(format!(
+                    "Operands of '{}' must be integers, got {} and {}",
+                    op.lexeme,
+                    lhs.type_name(),
+                    rhs.type_name()
+                )))
+            }
+        };
+        let shift = || {
+            if (0..64).contains(&rhs) {
+                Ok(rhs as u32)
+            } else {
+                Err(Error::Runtime(format!(
+                    "Cannot shift by {} with '{}', expected 0 to 63",
+                    rhs, op.lexeme
+                )))
+            }
+        };
+        let ret = match op.kind {
+            TokenType::Ampersand => lhs & rhs,
+            TokenType::Pipe => lhs | rhs,
+            TokenType::Caret => lhs ^ rhs,
+            TokenType::LessLess => lhs << shift()?,
+            TokenType::GreaterGreater => lhs >> shift()?,
+            _ => unreachable!("{} is not a bitwise operator", op.lexeme),
+        };
+        Ok(Value::Number(ret as f64))
+    }
+
+    fn as_int(value: &Value) -> Option<i64> {
+        match value {
+            // `i64::MAX` isn't exactly representable, as an
+            // `f64` it rounds up to 2^63 which is out of range
+            Value::Number(n)
+                if n.fract() == 0.0 && *n >= i64::MIN as f64 && *n < i64::MAX as f64 =>
+            {
+                Some(*n as i64)
+            }
+            _ => None,
+        }
+    }
+
+    /// Apply a binary operator to two values that have
+    /// already been evaluated
+    fn binary_op(&self, op: &Token, left: Value, right: Value) -> IntResult {
+        let ret = match (&op.kind, &left, &right) {
+            (TokenType::Minus, Value::Number(lhs), Value::Number(rhs)) => Value::Number(lhs - rhs),
+            (TokenType::Slash, Value::Number(_), Value::Number(rhs))
+                if *rhs == 0.0 && !self.ieee_division =>
+            {
+                return Err(Error::Runtime("division by zero".to_string()).at(op))
+            }
+            (TokenType::Slash, Value::Number(lhs), Value::Number(rhs)) => Value::Number(lhs / rhs),
+            (TokenType::Star, Value::Number(lhs), Value::Number(rhs)) => Value::Number(lhs * rhs),
+            (TokenType::Plus, Value::Number(lhs), Value::Number(rhs)) => Value::Number(lhs + rhs),
+            (TokenType::Greater, Value::Number(lhs), Value::Number(rhs)) => Value::Bool(lhs > rhs),
+            (TokenType::GreaterEqual, Value::Number(lhs), Value::Number(rhs)) => {
+                Value::Bool(lhs >= rhs)
+            }
+            (TokenType::Less, Value::Number(lhs), Value::Number(rhs)) => Value::Bool(lhs < rhs),
+            (TokenType::LessEqual, Value::Number(lhs), Value::Number(rhs)) => {
+                Value::Bool(lhs <= rhs)
+            }
+            (TokenType::Plus, Value::String(lhs), Value::String(rhs)) => {
+                Value::String(format!("{}{}", lhs, rhs))
+            }
+            (
+                TokenType::Ampersand
+                | TokenType::Pipe
+                | TokenType::Caret
+                | TokenType::LessLess
+                | TokenType::GreaterGreater,
+                l,
+                r,
+            ) => Self::bitwise(op, l, r).map_err(|e| e.at(op))?,
+            (TokenType::EqualEqual, l, r) => Value::Bool(Self::is_equal(l, r)),
+            (TokenType::BangEqual, l, r) => Value::Bool(!Self::is_equal(l, r)),
+            (
+                TokenType::Minus | TokenType::Slash | TokenType::Star | TokenType::Plus,
+                Value::Bool(_),
+                _,
+            )
+            | (
+                TokenType::Minus | TokenType::Slash | TokenType::Star | TokenType::Plus,
+                _,
+                Value::Bool(_),
+            ) => {
+                return Err(Error::Runtime(
+                    "Cannot do arithmetic on boolean; did you mean 'and'/'or'?".to_string(),
+                )
+                .at(op))
+            }
+            _ => {
+                return Err(Error::Runtime(format!(
+                    "Invalid binary operation: {} {} {}",
+                    left.type_name(),
+                    op.lexeme,
+                    right.type_name()
+                ))
+                .at(op))
+            }
+        };
+        Ok(ret)
+    }
+
+    /// Look up `name` on an instance or map
+    fn get_property(object: Value, name: &str) -> IntResult {
+        match object {
+            Value::Class(inst) => ClassInstance::get(&inst, name),
+            Value::WeakRef(weak) => ClassInstance::get(&Self::upgrade(&weak)?, name),
+            Value::Map(map) => Ok(map.borrow().get(name).cloned().unwrap_or(Value::Nil)),
+            other => Err(Error::Runtime(format!(
+                "cannot find property {} on {}",
+                name,
+                other.type_name()
+            ))),
+        }
+    }
+
+    /// Set `name` on an instance or map, anything
+    /// else is left alone
+    fn set_property(object: &Value, name: &str, value: Value) -> Result<(), Error> {
+        match object {
+            Value::Class(inst) => inst.borrow_mut().set(name, value),
+            Value::WeakRef(weak) => Self::upgrade(weak)?.borrow_mut().set(name, value),
+            Value::Map(map) => {
+                map.borrow_mut().insert(name.to_string(), value);
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Call any callable value with already evaluated arguments.
+    /// The callee is owned by the caller rather than borrowed from
+    /// the interpreter, so natives are free to call back in here
+    pub fn call_value(&mut self, callee: &mut Value, args: &[Value]) -> IntResult {
+        Self::check_call(callee, args.len())?;
+        if self.depth >= self.max_depth {
+            return Err(Error::Runtime(
+                "stack overflow: recursion depth exceeded".to_string(),
+            ));
+        }
+        self.depth += 1;
+        let ret = match callee.as_callable_mut() {
+            Some(f) => match f.call(self, args) {
+                Ok(val) => Ok(val),
+                Err(Error::Return(ret)) => Ok(ret),
+                Err(e) => Err(e),
+            },
+            None => Err(Error::Runtime("Attempt to call a non-callable".to_string())),
+        };
+        self.depth -= 1;
+        ret
+    }
+
+    /// Check that `callee` can be called with `arg_count` arguments
+    fn check_call(callee: &Value, arg_count: usize) -> Result<(), Error> {
+        if let Value::Nil = callee {
+            return Err(Error::Runtime(
+                "Attempt to call nil value as a function, is the function missing?".to_string(),
+            ));
+        }
+        let f = callee.as_callable().ok_or_else(|| {
+            Error::Runtime(format!(
+                "Attempt to call {} value as a function",
+                callee.type_name()
+            ))
+        })?;
+        if f.is_variadic() {
+            if arg_count < f.arity() {
+                return Err(Error::Runtime(format!(
+                    "{} was expecting at least {} arguments but {} were provided",
+                    f,
+                    f.arity(),
+                    arg_count
+                )));
+            }
+        } else if arg_count < f.arity() || arg_count > f.max_arity() {
+            let expected = if f.arity() == f.max_arity() {
+                f.arity().to_string()
+            } else {
+                format!("{} to {}", f.arity(), f.max_arity())
+            };
             return Err(Error::Runtime(format!(
                 "{} was expecting {} arguments but {} were provided",
-                f,
-                f.arity(),
-                arguments.len()
+                f, expected, arg_count
             )));
         }
-
-        match f.call(self, arguments) {
-            Ok(val) => Ok(val),
-            Err(Error::Return(ret)) => Ok(ret),
-            Err(e) => Err(e),
-        }
+        Ok(())
     }
 
-    fn is_truthy(lit: &Value) -> bool {
+    pub fn is_truthy(lit: &Value) -> bool {
         match lit {
             Value::Nil => false,
             Value::Bool(b) => *b,
@@ -365,20 +743,21 @@ impl Interpreter {
         }
     }
 
+    fn upgrade(weak: &Weak<RefCell<ClassInstance>>) -> Result<Rc<RefCell<ClassInstance>>, Error> {
+        weak.upgrade().ok_or_else(|| {
+            Error::Runtime("the instance behind this weak reference was dropped".to_string())
+        })
+    }
+
     fn is_equal(lhs: &Value, rhs: &Value) -> bool {
-        match (lhs, rhs) {
-            (Value::Nil, Value::Nil) => true,
-            (Value::String(l), Value::String(r)) => l == r,
-            (Value::Number(l), Value::Number(r)) => l.eq(r),
-            (Value::Bool(l), Value::Bool(r)) => l == r,
-            _ => false,
-        }
+        lhs == rhs
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::test_util::{output, run, run_err, run_with, SharedOutput};
     #[test]
     fn while_block() {
         let lox = "
@@ -388,12 +767,7 @@ while (i < 100) {
     i = i + 1;
 }
 ";
-        let mut int = Interpreter::new();
-        let mut parser =
-            crate::parser::Parser::new(crate::scanner::Scanner::new(lox.into()).unwrap());
-        while let Some(stmt) = parser.next() {
-            int.interpret(&mut stmt.unwrap()).unwrap();
-        }
+        run(lox);
     }
     #[test]
     fn for_loop() {
@@ -402,12 +776,7 @@ for (var i = 0; i < 10; i = i + 1) {
     print i;
 }
 ";
-        let mut int = Interpreter::new();
-        let mut parser =
-            crate::parser::Parser::new(crate::scanner::Scanner::new(lox.into()).unwrap());
-        while let Some(stmt) = parser.next() {
-            int.interpret(&mut stmt.unwrap()).unwrap();
-        }
+        run(lox);
     }
 
     #[test]
@@ -426,13 +795,721 @@ var junk = Junk();
 junk.things("hahah");
 print junk.one;
 "#;
-        let mut int = Interpreter::new();
-        let mut parser =
-            crate::parser::Parser::new(crate::scanner::Scanner::new(lox.into()).unwrap());
-        while let Some(stmt) = parser.next() {
-            int.interpret(&mut stmt.unwrap()).unwrap();
+        run(lox);
+    }
+    #[test]
+    fn runtime_error_line() {
+        let lox = "
+var a = \"text\";
+var b = a
+    / 2;
+";
+        let err = run_err(lox);
+        assert_eq!(err.position(), Some((4, 5)), "{}", err);
+        assert!(matches!(err.inner(), Error::Runtime(_)));
+    }
+
+    #[test]
+    fn error_positions() {
+        let located = |lox: &str| {
+            let err = run_err(lox);
+            (err.position(), err.inner().to_string())
+        };
+        assert_eq!(
+            located("print nope;\n\n\n\n"),
+            (
+                Some((1, 7)),
+                "Runtime error: variable \"nope\" is not yet defined".to_string()
+            )
+        );
+        assert_eq!(located("var a;\n  nope = 1;").0, Some((2, 3)));
+        assert_eq!(
+            located("var a;\nprint a.b;"),
+            (
+                Some((2, 7)),
+                "Runtime error: cannot find property b on nil".to_string()
+            )
+        );
+        assert_eq!(
+            located("class A {}\nprint  A().missing;"),
+            (
+                Some((2, 8)),
+                "Runtime error: Undefined propety on A instance: missing".to_string()
+            )
+        );
+        assert_eq!(located("var B = 1;\nclass A < B {}").0, Some((2, 11)));
+    }
+
+    #[test]
+    fn call_number() {
+        let err = run_err("var a = 1;\na();");
+        assert_eq!(
+            err.inner().to_string(),
+            "Runtime error: Attempt to call number value as a function"
+        );
+    }
+
+    #[test]
+    fn call_nil() {
+        let err = run_err("var a;\na();");
+        assert_eq!(
+            err.inner().to_string(),
+            "Runtime error: Attempt to call nil value as a function, is the function missing?"
+        );
+    }
+
+    #[test]
+    fn type_names() {
+        let int = run("
+fun f() {}
+class C { m() {} }
+var c = C();
+var m = c.m;
+var mp = new_map();
+");
+        let name = |n: &str| int.get_global(n).unwrap().type_name();
+        assert_eq!(Value::String("s".into()).type_name(), "string");
+        assert_eq!(Value::Number(1.0).type_name(), "number");
+        assert_eq!(Value::Bool(true).type_name(), "bool");
+        assert_eq!(Value::Nil.type_name(), "nil");
+        assert_eq!(name("f"), "function");
+        assert_eq!(name("C"), "class");
+        assert_eq!(name("clock"), "native function");
+        assert_eq!(name("c"), "instance");
+        assert_eq!(name("m"), "method");
+        assert_eq!(name("mp"), "map");
+    }
+
+    #[test]
+    fn type_names_in_errors() {
+        let err = run_err("var a = \"a\" - 1;");
+        assert_eq!(
+            err.inner().to_string(),
+            "Runtime error: Invalid binary operation: string - number"
+        );
+        let err = run_err("var a = 1;\nprint a.b;");
+        assert_eq!(
+            err.inner().to_string(),
+            "Runtime error: cannot find property b on number"
+        );
+    }
+
+    #[test]
+    fn get_global() {
+        let mut int = run("var answer = 42;");
+        assert_eq!(int.get_global("answer"), Some(Value::Number(42.0)));
+        assert_eq!(int.get_global("missing"), None);
+        int.env.descend();
+        int.env.define("answer", Some(Value::Nil));
+        assert_eq!(int.get_global("answer"), Some(Value::Number(42.0)));
+    }
+
+    #[test]
+    fn closures_capture_each_iteration() {
+        let int = run("
+var first;
+var second;
+var third;
+for (var i = 0; i < 3; i = i + 1) {
+    fun get() {
+        return i;
+    }
+    if (i == 0) first = get;
+    if (i == 1) second = get;
+    if (i == 2) third = get;
+}
+var a = first();
+var b = second();
+var c = third();
+");
+        assert_eq!(int.env.get("a").unwrap(), Value::Number(0.0));
+        assert_eq!(int.env.get("b").unwrap(), Value::Number(1.0));
+        assert_eq!(int.env.get("c").unwrap(), Value::Number(2.0));
+    }
+
+    #[test]
+    fn compound_assign_var() {
+        let int = run("
+var a = 10;
+a += 5;
+a -= 3;
+a *= 2 + 1;
+a /= 4;
+var s = \"a\";
+s += \"b\";
+");
+        assert_eq!(int.env.get("a").unwrap(), Value::Number(9.0));
+        assert_eq!(int.env.get("s").unwrap(), Value::String("ab".to_string()));
+    }
+
+    #[test]
+    fn compound_assign_property() {
+        let int = run("
+class Counter {
+  start() {
+    this.count = 1;
+    this.count += 1;
+  }
+}
+var counter = Counter();
+counter.start();
+counter.count *= 10;
+var result = counter.count;
+");
+        assert_eq!(int.env.get("result").unwrap(), Value::Number(20.0));
+        let lox = "
+var calls = 0;
+var m = new_map();
+m.count = 1;
+fun make() {
+    calls += 1;
+    return m;
+}
+make().count += 2 * 3;
+print make().count -= 1;
+print calls;
+";
+        assert_eq!(output(lox), "6\n2\n");
+    }
+
+    #[test]
+    fn weak_cycle() {
+        let int = run(r#"
+class Node {}
+var a = Node();
+a.name = "a";
+var b = Node();
+b.name = "b";
+a.next = weak(b);
+b.next = weak(a);
+b.next.seen = true;
+var name = a.next.next.next.name;
+var seen = a.seen;
+var same = a.next == b;
+var shown = to_string(a.next);
+"#);
+        assert_eq!(int.env.get("name").unwrap(), Value::String("b".into()));
+        assert_eq!(int.env.get("seen").unwrap(), Value::Bool(true));
+        assert_eq!(int.env.get("same").unwrap(), Value::Bool(true));
+        assert_eq!(
+            int.env.get("shown").unwrap(),
+            Value::String("[weak Node instance]".into())
+        );
+        let err = run_err(
+            "
+class Node {}
+fun make() {
+    var node = Node();
+    node.name = \"gone\";
+    return weak(node);
+}
+var dangling = make();
+print dangling.name;
+",
+        );
+        assert_eq!(
+            err.to_string(),
+            "[line 9:7] Runtime error: the instance behind this weak reference was dropped"
+        );
+    }
+
+    #[test]
+    fn number_output() {
+        // matches the output of the same values in roxc
+        assert_eq!(output("print 3.0;\nprint 0.5;"), "3\n0.5\n");
+    }
+
+    #[test]
+    fn computed_number_output() {
+        assert_eq!(output("print 10 / 2;"), "5\n");
+        assert_eq!(output("print 10 / 3;"), "3.3333333333333335\n");
+        assert_eq!(output("print 0.1 + 0.2;"), "0.30000000000000004\n");
+    }
+
+    #[test]
+    fn logical_operands() {
+        assert_eq!(output("print nil or \"default\";"), "\"default\"\n");
+        assert_eq!(output("print 1 or 2;"), "1\n");
+        assert_eq!(output("print false or nil;"), "nil\n");
+        assert_eq!(output("print 1 and 2;"), "2\n");
+        assert_eq!(output("print nil and 2;"), "nil\n");
+        assert_eq!(output("print false and nil;"), "false\n");
+        assert_eq!(output("print nil or 5;"), "5\n");
+        // 0 is truthy, only nil and false are not
+        assert_eq!(output("print 0 and 9;"), "9\n");
+        assert_eq!(output("print false or 0;"), "0\n");
+    }
+
+    #[test]
+    fn logical_short_circuit() {
+        // the right side would be a runtime error if it was evaluated
+        assert_eq!(output("print true or undefined;"), "true\n");
+        assert_eq!(output("print false and undefined;"), "false\n");
+    }
+
+    #[test]
+    fn boolean_arithmetic() {
+        for lox in &[
+            "var a = true + 1;",
+            "var a = false * 2;",
+            "var a = 1 - true;",
+        ] {
+            let err = run_err(lox);
+            assert_eq!(
+                err.inner().to_string(),
+                "Runtime error: Cannot do arithmetic on boolean; did you mean 'and'/'or'?"
+            );
         }
     }
+
+    #[test]
+    fn negate_non_number() {
+        let err = run_err("var a = -\"text\";");
+        assert_eq!(
+            err.inner().to_string(),
+            "Runtime error: Operand of '-' must be a number, got string"
+        );
+        let err = run_err("var a = -nil;");
+        assert_eq!(
+            err.inner().to_string(),
+            "Runtime error: Operand of '-' must be a number, got nil"
+        );
+    }
+
+    #[test]
+    fn inherited_methods() {
+        let lox = "
+class Animal {
+    init(name) { this.name = name; }
+    speak() { return this.name + \" makes a sound\"; }
+    describe() { return this.speak(); }
+}
+class Dog < Animal {
+    speak() { return this.name + \" barks\"; }
+}
+class Puppy < Dog {}
+print Animal(\"cat\").describe();
+print Dog(\"rex\").describe();
+print Puppy(\"bit\").speak();
+";
+        assert_eq!(
+            output(lox),
+            "\"cat makes a sound\"\n\"rex barks\"\n\"bit barks\"\n"
+        );
+        let err = run_err("var NotAClass = 1;\nclass A < NotAClass {}");
+        assert_eq!(
+            err.inner().to_string(),
+            "Runtime error: Superclass of A must be a class, found number"
+        );
+    }
+
+    #[test]
+    fn bound_method_keeps_receiver() {
+        let lox = "
+class Counter {
+    init() { this.count = 0; }
+    bump() {
+        this.count = this.count + 1;
+        return this.count;
+    }
+}
+var inst = Counter();
+var g = inst.bump;
+g();
+inst = nil;
+print g();
+var other = Counter();
+inst = other;
+print g();
+print other.count;
+";
+        assert_eq!(output(lox), "2\n3\n0\n");
+    }
+
+    #[test]
+    fn bound_method_equality() {
+        let lox = "
+class Base {
+    m() {}
+    n() {}
+}
+class Derived < Base {
+    n() {}
+}
+var a = Base();
+var m = a.m;
+print m == m;
+print a.m == a.m;
+print a.m == a.n;
+print a.m == Base().m;
+var d = Derived();
+print d.m == d.m;
+print d.n == d.n;
+print d.n == a.n;
+";
+        assert_eq!(output(lox), "true\ntrue\nfalse\nfalse\ntrue\ntrue\nfalse\n");
+    }
+
+    #[test]
+    fn super_calls() {
+        let lox = "
+class Animal {
+    init(name) { this.name = name; }
+    speak() { return this.name + \" makes a sound\"; }
+}
+class Dog < Animal {
+    init(name) { super.init(name + \" the dog\"); }
+    speak() { return super.speak() + \" and barks\"; }
+}
+class Puppy < Dog {
+    speak() { return super.speak() + \" quietly\"; }
+}
+print Dog(\"rex\").speak();
+print Puppy(\"bit\").speak();
+var speak = Puppy(\"pip\").speak;
+print speak();
+";
+        assert_eq!(
+            output(lox),
+            "\"rex the dog makes a sound and barks\"\n\
+             \"bit the dog makes a sound and barks quietly\"\n\
+             \"pip the dog makes a sound and barks quietly\"\n"
+        );
+        let err = run_err("class A {}\nclass B < A { f() { return super.missing(); } }\nB().f();");
+        assert_eq!(
+            err.inner().to_string(),
+            "Runtime error: Undefined method missing on superclass A"
+        );
+    }
+
+    #[test]
+    fn postfix_increment() {
+        let lox = "
+var i = 0;
+print i++;
+print i;
+print i-- + 10;
+print i;
+class Counter {
+    init() { this.n = 5; }
+}
+var counter = Counter();
+var calls = 0;
+fun get() {
+    calls = calls + 1;
+    return counter;
+}
+print get().n++;
+print counter.n;
+print calls;
+var total = 0;
+for (var j = 0; j < 3; j++) total += j;
+print total;
+";
+        assert_eq!(output(lox), "0\n1\n11\n0\n5\n6\n1\n3\n");
+        let err = run_err("var s = \"a\";\ns++;");
+        assert_eq!(
+            err.inner().to_string(),
+            "Runtime error: Operand of '++' must be a number, got string"
+        );
+    }
+
+    #[test]
+    fn optional_get() {
+        let lox = "
+class Point {
+    init(x) { this.x = x; }
+}
+var p = Point(1);
+var missing;
+print p?.x;
+print missing?.x;
+print missing?.x?.y;
+";
+        assert_eq!(output(lox), "1\nnil\nnil\n");
+        let err = run_err("var n = 1;\nprint n?.x;");
+        assert_eq!(
+            err.inner().to_string(),
+            "Runtime error: cannot find property x on number"
+        );
+        let err = run_err("class A {}\nprint A()?.x;");
+        assert!(matches!(err.inner(), Error::Runtime(_)), "{}", err);
+    }
+
+    #[test]
+    fn division_by_zero() {
+        let err = run_err("var a = 1;\nprint a / 0;");
+        assert_eq!(err.inner().to_string(), "Runtime error: division by zero");
+        assert_eq!(err.position(), Some((2, 9)));
+        assert!(run_err("print 0 / -0;")
+            .to_string()
+            .contains("division by zero"));
+        assert_eq!(output("print 1 / 4;"), "0.25\n");
+
+        let out = SharedOutput::default();
+        let mut int = Interpreter::with_io(std::io::empty(), out.clone()).with_ieee_division(true);
+        run_with(
+            &mut int,
+            "print 1 / 0;\nprint -1 / 0;\nprint 0 / 0 == 0 / 0;",
+        )
+        .unwrap();
+        assert_eq!(out.contents(), "inf\n-inf\nfalse\n");
+    }
+
+    #[test]
+    fn bitwise() {
+        assert_eq!(output("print 6 & 3;"), "2\n");
+        assert_eq!(output("print 1 << 4;"), "16\n");
+        assert_eq!(output("print 6 | 3;"), "7\n");
+        assert_eq!(output("print 6 ^ 3;"), "5\n");
+        assert_eq!(output("print -16 >> 2;"), "-4\n");
+        assert_eq!(output("print 1 | 6 & 3;"), "3\n");
+        assert_eq!(output("print 1 ^ 3 | 4;"), "6\n");
+        assert_eq!(output("print 1 << 2 + 1 < 9;"), "true\n");
+        assert_eq!(output("print 6 & 1 == 0;"), "true\n");
+        assert_eq!(output("print 5 | 2 > 6;"), "true\n");
+        assert_eq!(output("print -9223372036854775808 & 1;"), "0\n");
+        let err = run_err("print \"a\" & 1;");
+        assert_eq!(
+            err.inner().to_string(),
+            "Runtime error: Operands of '&' must be integers, got string and number"
+        );
+        assert_eq!(err.position(), Some((1, 11)));
+        let err = run_err("print 1.5 | 1;");
+        assert_eq!(
+            err.inner().to_string(),
+            "Runtime error: Operands of '|' must be integers, got number and number"
+        );
+        let err = run_err("print 9223372036854775808 | 0;");
+        assert_eq!(
+            err.inner().to_string(),
+            "Runtime error: Operands of '|' must be integers, got number and number"
+        );
+        let err = run_err("print 1 << 64;");
+        assert_eq!(
+            err.inner().to_string(),
+            "Runtime error: Cannot shift by 64 with '<<', expected 0 to 63"
+        );
+    }
+
+    #[test]
+    fn instances_are_shared() {
+        let out = output(
+            "
+class Counter {
+    init() { this.count = 0; }
+    bump() { this.count = this.count + 1; }
+}
+var a = Counter();
+var b = a;
+b.x = 1;
+print a.x;
+var bump = a.bump;
+bump();
+b.bump();
+print a.count;
+print a == b;
+print a == Counter();
+class Node {}
+var node = Node();
+node.me = node;
+node.me.me.value = 2;
+print node.value;
+",
+        );
+        assert_eq!(out, "1\n2\ntrue\nfalse\n2\n");
+    }
+
+    #[test]
+    fn bodies_are_shared() {
+        let int = run("
+class Base {
+    m() {}
+}
+class Derived < Base {}
+fun make() {
+    fun inner() {}
+    return inner;
+}
+var first = Derived().m;
+var second = Derived().m;
+var one = make();
+var two = make();
+");
+        let body = |name: &str| match int.env.get(name).unwrap() {
+            Value::Method(method) => method.func.body,
+            Value::Func(func) => func.body,
+            other => panic!("{} is not a function, found {:?}", name, other),
+        };
+        assert!(Rc::ptr_eq(&body("first"), &body("second")));
+        assert!(Rc::ptr_eq(&body("one"), &body("two")));
+        assert_ne!(int.env.get("one").unwrap(), int.env.get("two").unwrap());
+    }
+
+    #[test]
+    fn nested_calls_read_outer() {
+        let out = output(
+            "
+var outer = \"global\";
+fun first(n) {
+    var mine = n;
+    fun second(m) {
+        var deeper = m * 2;
+        fun third() {
+            return outer + \" \" + to_string(mine) + \" \" + to_string(deeper);
+        }
+        var before = third();
+        var recursed = 0;
+        if (m < 3) recursed = second(m + 1);
+        return before + \" | \" + third();
+    }
+    var ret = second(1);
+    print mine;
+    return ret;
+}
+print first(5);
+print outer;
+",
+        );
+        assert_eq!(out, "5\n\"global 5 2 | global 5 2\"\n\"global\"\n");
+    }
+
+    #[test]
+    fn instance_arguments_are_shared() {
+        let out = output(
+            "
+class Point {
+    init() { this.x = 0; }
+}
+fun moveRight(point, by) {
+    point.x = point.x + by;
+}
+var p = Point();
+moveRight(p, 3);
+moveRight(p, 4);
+print p.x;
+",
+        );
+        assert_eq!(out, "7\n");
+    }
+
+    #[test]
+    fn natives_reenter() {
+        #[derive(Debug)]
+        struct Twice;
+        impl std::fmt::Display for Twice {
+            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "[native fn twice]")
+            }
+        }
+        impl crate::callable::Callable for Twice {
+            fn arity(&self) -> usize {
+                1
+            }
+            fn call(&mut self, int: &mut Interpreter, args: &[Value]) -> IntResult {
+                let mut f = args[0].clone();
+                int.call_value(&mut f, &[])?;
+                int.call_value(&mut f, &[])
+            }
+        }
+        let mut int = run("
+var count = 0;
+fun bump() {
+    count = count + 1;
+    return count;
+}
+");
+        let mut bump = int.get_global("bump").unwrap();
+        let ret = crate::callable::Callable::call(&mut Twice, &mut int, &[bump.clone()]);
+        assert_eq!(ret.unwrap(), Value::Number(2.0));
+        assert_eq!(int.get_global("count"), Some(Value::Number(2.0)));
+        assert_eq!(int.call_value(&mut bump, &[]).unwrap(), Value::Number(3.0));
+    }
+
+    #[test]
+    fn maps() {
+        let out = output(
+            "
+var m = new_map();
+print m;
+m.name = \"lox\";
+m.count = 1;
+m.count = m.count + 1;
+print m.count;
+print m.missing;
+print m;
+var alias = m;
+alias.extra = true;
+print m.extra;
+var other = new_map();
+other.count = 2;
+other.name = \"lox\";
+print m == other;
+other.extra = true;
+print m == other;
+other.extra = false;
+print m == other;
+",
+        );
+        assert_eq!(
+            out,
+            "{}\n2\nnil\n{name: \"lox\", count: 2}\ntrue\nfalse\ntrue\nfalse\n"
+        );
+    }
+
+    #[test]
+    fn identity_equality() {
+        let out = output(
+            "
+fun make() {
+    fun inner() {}
+    return inner;
+}
+fun other() {}
+var f = make;
+print f == make;
+print make == other;
+print make() == make();
+var g = make();
+var h = g;
+print g == h;
+class Foo {}
+class Bar {}
+var Baz = Foo;
+print Baz == Foo;
+print Foo == Bar;
+var a = Foo();
+var b = a;
+print a == b;
+print a == Foo();
+",
+        );
+        assert_eq!(out, "true\nfalse\nfalse\ntrue\ntrue\nfalse\ntrue\nfalse\n");
+    }
+
+    #[test]
+    fn interpolation() {
+        let int = run(r#"
+var a = 1;
+var b = 2;
+var single = "total: ${a + b}";
+var multiple = "${a} + ${b} = ${a + b}!";
+var nested = "outer ${"inner ${a} " + "done"} end";
+"#);
+        assert_eq!(
+            int.env.get("single").unwrap(),
+            Value::String("total: 3".to_string())
+        );
+        assert_eq!(
+            int.env.get("multiple").unwrap(),
+            Value::String("1 + 2 = 3!".to_string())
+        );
+        assert_eq!(
+            int.env.get("nested").unwrap(),
+            Value::String("outer inner 1 done end".to_string())
+        );
+    }
+
     #[test]
     fn func_if() {
         let _ = pretty_env_logger::try_init();
@@ -448,13 +1525,7 @@ var test1 = isEven(1);
 var pre2 = mod(2, 2) == 0;
 var test2 = isEven(2);
 ";
-        let mut int = Interpreter::new();
-        let mut parser =
-            crate::parser::Parser::new(crate::scanner::Scanner::new(lox.into()).unwrap());
-        while let Some(stmt) = parser.next() {
-            int.interpret(&mut stmt.unwrap()).unwrap();
-            dbg!(&int.env);
-        }
+        let int = run(lox);
         // dbg!(&int.env);
         let test1 = int.env.get("test1").expect("Unable to get test1");
         // dbg!(&int.env);
@@ -464,4 +1535,104 @@ var test2 = isEven(2);
         let pre2 = int.env.get("pre2").expect("Unable to get pre2");
         assert_eq!(test2, pre2);
     }
+
+    fn branch(vars: &str, body: &str) -> String {
+        output(&format!("{}\n{}", vars, body))
+    }
+
+    #[test]
+    fn dangling_else() {
+        let body = r#"if (a) if (b) print "x"; else print "y";"#;
+        assert_eq!(branch("var a = true; var b = true;", body), "\"x\"\n");
+        assert_eq!(branch("var a = true; var b = false;", body), "\"y\"\n");
+        assert_eq!(branch("var a = false; var b = true;", body), "");
+        assert_eq!(branch("var a = false; var b = false;", body), "");
+    }
+
+    #[test]
+    fn braces_bind_else_to_outer_if() {
+        let body = r#"if (a) { if (b) print "x"; } else print "y";"#;
+        assert_eq!(branch("var a = true; var b = true;", body), "\"x\"\n");
+        assert_eq!(branch("var a = true; var b = false;", body), "");
+        assert_eq!(branch("var a = false; var b = true;", body), "\"y\"\n");
+    }
+
+    #[test]
+    fn else_if_chain() {
+        let body = r#"
+if (n < 0) print "negative";
+else if (n == 0) print "zero";
+else if (n < 10) print "small";
+else print "large";
+"#;
+        assert_eq!(branch("var n = -1;", body), "\"negative\"\n");
+        assert_eq!(branch("var n = 0;", body), "\"zero\"\n");
+        assert_eq!(branch("var n = 5;", body), "\"small\"\n");
+        assert_eq!(branch("var n = 50;", body), "\"large\"\n");
+    }
+
+    #[test]
+    fn deeply_nested_dangling_else() {
+        let body = r#"
+if (a) if (b) if (c) print "abc"; else print "ab"; else print "a";
+"#;
+        let vars = |a, b, c| format!("var a = {}; var b = {}; var c = {};", a, b, c);
+        assert_eq!(branch(&vars(true, true, true), body), "\"abc\"\n");
+        assert_eq!(branch(&vars(true, true, false), body), "\"ab\"\n");
+        assert_eq!(branch(&vars(true, false, true), body), "\"a\"\n");
+        assert_eq!(branch(&vars(false, true, true), body), "");
+    }
+
+    #[test]
+    fn top_level_return() {
+        let err = run_err("print 1;\nreturn 1;");
+        assert_eq!(
+            err.to_string(),
+            "Runtime error: 'return' outside of function"
+        );
+        let err = run_err("{ return; }");
+        assert_eq!(
+            err.to_string(),
+            "Runtime error: 'return' outside of function"
+        );
+    }
+
+    #[test]
+    fn arity_mismatch_line() {
+        let lox = "
+fun add(a, b) {
+    return a + b;
+}
+var sum = add(1, 2);
+var short = add(1);
+";
+        let err = run_err(lox);
+        assert_eq!(err.position().map(|(line, _)| line), Some(6), "{}", err);
+        assert_eq!(
+            err.to_string(),
+            "[line 6:18] Runtime error: [fn add] was expecting 2 arguments but 1 were provided"
+        );
+        let err = run_err("class Point {\n  move(x, y) {}\n}\nPoint().move(\n  1\n);");
+        assert_eq!(err.position().map(|(line, _)| line), Some(6), "{}", err);
+    }
+
+    #[test]
+    fn recursion_limit() {
+        let lox = "
+fun forever(n) {
+    return forever(n + 1);
+}
+forever(0);
+";
+        let mut int = Interpreter::new().with_max_depth(50);
+        let err = run_with(&mut int, lox).expect_err("expected recursion to fail");
+        // reported at the call that went too deep
+        assert_eq!(
+            err.to_string(),
+            "[line 3:25] Runtime error: stack overflow: recursion depth exceeded"
+        );
+        // the interpreter is still usable afterwards
+        run_with(&mut int, "var ok = 1;").unwrap();
+        assert_eq!(int.env.get("ok").unwrap(), Value::Number(1.0));
+    }
 }
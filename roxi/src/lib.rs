@@ -1,5 +1,12 @@
+#![allow(clippy::result_large_err)]
 use log::{error, trace};
-use std::{fs::read_to_string, io::stdin, path::Path};
+use rox_shared::{ScannerError, ScannerErrorKind, TokenType};
+use std::{
+    fs::read_to_string,
+    io::{stdin, BufRead},
+    path::Path,
+};
+pub mod ast;
 mod callable;
 mod class;
 mod env;
@@ -8,106 +15,421 @@ mod expr;
 mod func;
 mod globals;
 mod interpreter;
+mod ordered_map;
 mod parser;
+mod printer;
+mod resolver;
 mod stmt;
+#[cfg(test)]
+mod test_util;
 mod value;
 
 pub use error::Error;
-use interpreter::Interpreter;
+pub use interpreter::{Interpreter, MAX_DEPTH, STACK_PER_CALL};
+pub use parser::Parser;
+use resolver::Resolver;
 pub use rox_shared::Scanner;
+use stmt::Stmt;
+pub use value::Value;
 
 type SimpleResult<T> = Result<T, Error>;
+
+/// How errors and warnings are reported
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ErrorFormat {
+    /// `[line N:C] Error: ...` as each one is found
+    #[default]
+    Human,
+    /// A JSON array of every report, written as the last
+    /// line of stdout once the file has finished running.
+    /// The prompt writes one to stderr after each entry
+    /// that reported anything
+    Json,
+}
+
+/// An error or warning held back for `ErrorFormat::Json`
+#[derive(Debug)]
+struct Report {
+    /// The line and column, if it is known
+    position: Option<(usize, usize)>,
+    severity: &'static str,
+    message: String,
+}
+
+#[derive(Default)]
 pub struct Lox {
-    had_error: bool,
+    /// The first error reported by the current run
+    error: Option<Error>,
+    format: ErrorFormat,
+    reports: Vec<Report>,
+    max_depth: Option<usize>,
+    gas: Option<usize>,
+    ieee_division: bool,
+}
+
+/// Collects the settings for a `Lox`, see `Lox::builder`
+#[derive(Default)]
+pub struct LoxBuilder {
+    format: ErrorFormat,
+    max_depth: Option<usize>,
+    gas: Option<usize>,
+    ieee_division: bool,
 }
-impl Default for Lox {
-    fn default() -> Self {
-        Self { had_error: false }
+
+impl LoxBuilder {
+    /// How errors and warnings are reported
+    pub fn error_format(mut self, format: ErrorFormat) -> Self {
+        self.format = format;
+        self
+    }
+    /// How deep calls can nest, see `Interpreter::with_max_depth`
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+    /// How many statements a run can execute before it
+    /// fails, see `Interpreter::with_gas`
+    pub fn gas(mut self, gas: usize) -> Self {
+        self.gas = Some(gas);
+        self
+    }
+    /// Let dividing by zero give `inf` or `NaN`, see
+    /// `Interpreter::with_ieee_division`
+    pub fn ieee_division(mut self, ieee_division: bool) -> Self {
+        self.ieee_division = ieee_division;
+        self
+    }
+    pub fn build(self) -> Lox {
+        Lox {
+            format: self.format,
+            max_depth: self.max_depth,
+            gas: self.gas,
+            ieee_division: self.ieee_division,
+            ..Lox::default()
+        }
     }
 }
+
 impl Lox {
+    /// A `Lox` with the default settings
     pub fn new() -> Self {
-        Self { had_error: false }
+        Self::default()
+    }
+    pub fn builder() -> LoxBuilder {
+        LoxBuilder::default()
+    }
+    pub fn with_error_format(format: ErrorFormat) -> Self {
+        Self::builder().error_format(format).build()
     }
+    /// An interpreter with this `Lox`'s settings
+    fn interpreter(&self) -> Interpreter {
+        let mut int = Interpreter::new().with_ieee_division(self.ieee_division);
+        if let Some(max_depth) = self.max_depth {
+            int = int.with_max_depth(max_depth);
+        }
+        if let Some(gas) = self.gas {
+            int = int.with_gas(gas);
+        }
+        int
+    }
+    /// Run the file at `path`, every error is reported and
+    /// the first one is returned
     pub fn run_file<T>(&mut self, path: T) -> SimpleResult<()>
     where
         T: AsRef<Path>,
     {
         trace!("Running a file");
-        let mut lox =
-            read_to_string(path).map_err(|e| Error::Runtime(format!("IO Error: {}", e)))?;
-        if !lox.ends_with('\n') {
-            lox.push('\n');
+        let ret = match read_to_string(path) {
+            Ok(lox) => {
+                let mut int = self.interpreter();
+                self.run(lox, &mut int, false)
+            }
+            Err(e) => {
+                let e = Error::Runtime(format!("IO Error: {}", e));
+                self.error(&e);
+                Err(e)
+            }
+        };
+        if self.format == ErrorFormat::Json {
+            println!("{}", self.take_json_reports());
         }
-        let mut int = Interpreter::new();
-        self.run(lox, &mut int)?;
-        if self.had_error {
-            ::std::process::exit(65);
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => ret,
         }
-        Ok(())
     }
     pub fn run_prompt(&mut self) -> SimpleResult<()> {
         trace!("Running a prompt");
-        let reader = stdin();
-        let mut int = Interpreter::new();
-        let mut indent = 0;
+        let mut reader = stdin().lock();
+        let mut int = self.interpreter();
         loop {
-            let mut line = String::new();
-            write_prompt(indent);
-            loop {
-                reader
-                    .read_line(&mut line)
-                    .map_err(|e| Error::Runtime(format!("IO Error: {}", e)))?;
-                if line.ends_with("\r\n") {
-                    line.pop();
-                    line.pop();
-                    line.push('\n');
-                }
-                if indent == 0 && line.ends_with(";\n") {
-                    break;
-                }
-                if line.ends_with("{\n") {
-                    indent += 1;
-                }
-                if line.ends_with("}\n") {
-                    indent = indent.saturating_sub(1);
-                }
-                if indent == 0 {
-                    break;
-                }
-                write_prompt(indent);
+            let line = Self::read_entry(&mut reader)?;
+            if line.is_empty() {
+                // the input has been closed
+                return Ok(());
+            }
+            if let Err(Error::Exit(code)) = self.run_line(line, &mut int) {
+                return Err(Error::Exit(code));
+            }
+            if let Some(reports) = self.finish_entry() {
+                eprintln!("{}", reports);
             }
-            let _ = self.run(line, &mut int);
-            self.had_error = false;
         }
     }
-    fn run(&mut self, s: String, int: &mut Interpreter) -> SimpleResult<()> {
-        let scanner = Scanner::new(s).map_err(|e| Error::Scanner(e))?;
 
-        let mut parser = parser::Parser::new(scanner);
+    /// Forget the errors from the last prompt entry, with
+    /// `ErrorFormat::Json` anything it reported is returned
+    fn finish_entry(&mut self) -> Option<String> {
+        self.error = None;
+        if self.format == ErrorFormat::Json && !self.reports.is_empty() {
+            Some(self.take_json_reports())
+        } else {
+            None
+        }
+    }
 
-        while let Some(stmt) = parser.next() {
-            match stmt {
-                Ok(mut stmt) => {
-                    int.interpret(&mut stmt)?;
-                }
-                Err(e) => {
-                    error!("Error on line {}: {}", parser.line(), e);
-                    self.error(parser.line(), e.clone());
-                    parser.sync();
+    /// Read lines from `reader` until every `{` they open
+    /// has been closed, or the input runs out. See `next_line`
+    /// for how to override the brace counting
+    fn read_entry(reader: &mut impl BufRead) -> SimpleResult<String> {
+        let mut line = String::new();
+        write_prompt(0);
+        loop {
+            let start = line.len();
+            let read = reader
+                .read_line(&mut line)
+                .map_err(|e| Error::Runtime(format!("IO Error: {}", e)))?;
+            if line.ends_with("\r\n") {
+                line.pop();
+                line.pop();
+                line.push('\n');
+            }
+            match Self::next_line(&mut line, start) {
+                Some(indent) if read != 0 => write_prompt(indent),
+                _ => return Ok(line),
+            }
+        }
+    }
+
+    /// The indentation to prompt for the next line of `entry`
+    /// with, or `None` when it is ready to run. `start` is
+    /// where its last line begins. A line ending in `\` always
+    /// asks for another line (and the `\` is dropped), an empty
+    /// line always submits, otherwise the entry continues while
+    /// it has open braces
+    fn next_line(entry: &mut String, start: usize) -> Option<usize> {
+        let line = &entry[start..];
+        if line.ends_with("\\\n") {
+            entry.truncate(entry.len() - 2);
+            entry.push('\n');
+            // continued lines are always indented so they
+            // don't look like a new entry
+            return Some(Self::open_braces(entry).max(1));
+        }
+        if line == "\n" {
+            return None;
+        }
+        match Self::open_braces(entry) {
+            0 => None,
+            open => Some(open),
+        }
+    }
+
+    /// How many `{`s in `source` are still waiting on a `}`.
+    /// Braces in strings and comments don't count, and a
+    /// string left open counts as one more so that it can
+    /// be finished on the next line
+    fn open_braces(source: &str) -> usize {
+        let scanner = match Scanner::new(source.to_string()) {
+            Ok(scanner) => scanner,
+            Err(e) => return Self::unterminated_string(&e) as usize,
+        };
+        let mut open = 0usize;
+        for token in scanner {
+            match token.map(|t| t.kind) {
+                Ok(TokenType::LeftBrace) => open += 1,
+                Ok(TokenType::RightBrace) => open = open.saturating_sub(1),
+                Ok(_) => (),
+                // any other error is reported once the input is run
+                Err(e) if Self::unterminated_string(&e) => return open + 1,
+                Err(_) => return 0,
+            }
+        }
+        open
+    }
+
+    fn unterminated_string(e: &ScannerError) -> bool {
+        e.kind == ScannerErrorKind::UnterminatedString
+    }
+    /// Run a line typed at the prompt. Expression statements
+    /// have their value printed, and an expression on its own
+    /// doesn't need a trailing `;`
+    fn run_line(&mut self, mut line: String, int: &mut Interpreter) -> SimpleResult<()> {
+        int.refuel();
+        if Self::is_expression(&line) {
+            line.push(';');
+        }
+        self.run(line, int, true)
+    }
+
+    fn is_expression(line: &str) -> bool {
+        match Scanner::new(line.to_string()) {
+            Ok(scanner) => {
+                let mut parser = Parser::new(scanner);
+                parser.expression().is_ok() && parser.is_at_end()
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Run `s` with `int`, when `echo` is set expression
+    /// statements print their value. Everything is parsed and
+    /// resolved first, if any of that fails nothing is run
+    fn run(&mut self, s: String, int: &mut Interpreter, echo: bool) -> SimpleResult<()> {
+        let source = s.clone();
+        let scanner = match Scanner::new(s) {
+            Ok(scanner) => scanner,
+            Err(e) => {
+                let e = Error::Scanner(e).at_position(1, 1);
+                self.error(&e);
+                return Err(e);
+            }
+        };
+
+        let (stmts, errors) = Parser::new(scanner).parse_all();
+        let mut failed = !errors.is_empty();
+        for e in errors {
+            error!("{}", e);
+            self.error(&e);
+        }
+        let stmts: Vec<Stmt> = stmts
+            .into_iter()
+            .map(|stmt| match stmt {
+                Stmt::Expr(expr) if echo => Stmt::Print(expr),
+                stmt => stmt,
+            })
+            .collect();
+        let mut resolver = Resolver::new();
+        for stmt in &stmts {
+            let resolved = resolver.resolve_stmt(stmt);
+            for warning in resolver.take_warnings() {
+                let (line, column) = line_column(&source, warning.span.start);
+                self.warn(line, column, &warning.message);
+            }
+            if let Err(e) = resolved {
+                failed = true;
+                self.error(&e);
+            }
+        }
+        if failed {
+            return Ok(());
+        }
+
+        for stmt in &stmts {
+            if let Err(e) = int.interpret(stmt) {
+                if let Error::Exit(_) = e {
+                    // not a failure, so nothing is reported
+                    return Err(e);
                 }
+                self.error(&e);
+                return Err(e);
             }
         }
         Ok(())
     }
 
-    fn error(&mut self, line: usize, e: Error) {
-        self.report(line, "", &format!("{}", e));
+    /// Report `e` at the position it carries, errors found
+    /// without one are reported without a position
+    fn error(&mut self, e: &Error) {
+        self.report(e.position(), "", &format!("{}", e.inner()));
+        if self.error.is_none() {
+            self.error = Some(e.clone());
+        }
+    }
+    fn warn(&mut self, line: usize, column: usize, msg: &str) {
+        match self.format {
+            ErrorFormat::Human => eprintln!("[line {}:{}] Warning: {}", line, column, msg),
+            ErrorFormat::Json => self.reports.push(Report {
+                position: Some((line, column)),
+                severity: "warning",
+                message: msg.to_string(),
+            }),
+        }
+    }
+    fn report(&mut self, position: Option<(usize, usize)>, file: &str, msg: &str) {
+        match (self.format, position) {
+            (ErrorFormat::Human, Some((line, column))) => {
+                println!("[line {}:{}] Error {}: {}", line, column, file, msg)
+            }
+            (ErrorFormat::Human, None) => println!("Error {}: {}", file, msg),
+            (ErrorFormat::Json, _) => self.reports.push(Report {
+                position,
+                severity: "error",
+                message: msg.to_string(),
+            }),
+        }
     }
-    fn report(&mut self, line: usize, file: &str, msg: &str) {
-        println!("[line {}] Error {}: {}", line, file, msg);
-        self.had_error = true;
+
+    /// Everything reported so far as a JSON array of
+    /// `{line, column, severity, message}` objects, the
+    /// reports are cleared. `line` and `column` are `null`
+    /// for errors found without a position
+    fn take_json_reports(&mut self) -> String {
+        let entries: Vec<String> = self
+            .reports
+            .iter()
+            .map(|r| {
+                let (line, column) = match r.position {
+                    Some((line, column)) => (line.to_string(), column.to_string()),
+                    None => ("null".to_string(), "null".to_string()),
+                };
+                format!(
+                    "{{\"line\":{},\"column\":{},\"severity\":\"{}\",\"message\":{}}}",
+                    line,
+                    column,
+                    r.severity,
+                    json_string(&r.message)
+                )
+            })
+            .collect();
+        self.reports.clear();
+        format!("[{}]", entries.join(","))
+    }
+}
+
+/// Quote `s` as a JSON string
+pub(crate) fn json_string(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len() + 2);
+    ret.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            '\n' => ret.push_str("\\n"),
+            '\r' => ret.push_str("\\r"),
+            '\t' => ret.push_str("\\t"),
+            c if (c as u32) < 0x20 => ret.push_str(&format!("\\u{:04x}", c as u32)),
+            c => ret.push(c),
+        }
+    }
+    ret.push('"');
+    ret
+}
+
+/// The line and column of the character at `offset` in
+/// `source`, counting from 1 like the scanner does. Spans
+/// count characters rather than bytes
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let (mut line, mut column) = (1, 1);
+    for c in source.chars().take(offset) {
+        if c == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
     }
+    (line, column)
 }
 
 fn write_prompt(indent: usize) {
@@ -120,3 +442,295 @@ fn write_prompt(indent: usize) {
     }
     let _ = out.flush();
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run_source(name: &str, lox: &str) -> SimpleResult<()> {
+        let path = std::env::temp_dir().join(format!("roxi-{}-{}.lox", name, std::process::id()));
+        std::fs::write(&path, lox).unwrap();
+        let ret = Lox::new().run_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        ret
+    }
+
+    #[test]
+    fn run_file_syntax_error() {
+        let err = run_source("syntax", "var a = 1;\nvar b = ;\nprint a;").unwrap_err();
+        assert!(matches!(err.inner(), Error::Parser(_)), "{}", err);
+        assert_eq!(err.position().map(|(line, _)| line), Some(2));
+    }
+
+    #[test]
+    fn run_file_shebang() {
+        run_source("shebang", "#!/usr/bin/env roxi\nprint 1;").unwrap();
+        let err = run_source("shebang-err", "#!/usr/bin/env roxi\nprint 1;\nvar = 2;").unwrap_err();
+        assert!(matches!(err.inner(), Error::Parser(_)), "{}", err);
+        assert_eq!(err.position().map(|(line, _)| line), Some(3));
+    }
+
+    #[test]
+    fn static_errors_stop_the_run() {
+        let out = test_util::SharedOutput::default();
+        let mut int = Interpreter::with_io(std::io::empty(), out.clone());
+        let mut lox = Lox::with_error_format(ErrorFormat::Json);
+        let src = "print 1;\nfun f(a, a) {}\nclass A < A {}\nprint 2;\n";
+        assert!(lox.run(src.to_string(), &mut int, false).is_ok());
+        assert_eq!(out.contents(), "");
+        assert_eq!(
+            lox.take_json_reports(),
+            concat!(
+                r#"[{"line":2,"column":10,"severity":"error","#,
+                r#""message":"Resolution error: a has already been declared in this scope"},"#,
+                r#"{"line":3,"column":11,"severity":"error","#,
+                r#""message":"Resolution error: A class can't inherit from itself (A)"}]"#
+            )
+        );
+        assert_eq!(lox.error.as_ref().and_then(|e| e.position()), Some((2, 10)));
+    }
+
+    #[test]
+    fn run_file_runtime_error() {
+        let err = run_source("runtime", "print -\"a\";").unwrap_err();
+        assert!(matches!(err.inner(), Error::Runtime(_)), "{}", err);
+    }
+
+    #[test]
+    fn prompt_echoes_expressions() {
+        let out = test_util::SharedOutput::default();
+        let mut int = Interpreter::with_io(std::io::empty(), out.clone());
+        let mut lox = Lox::new();
+        for line in ["40 + 2\n", "var a = 1;\n", "a\n", "a + 1;\n", "print a;\n"] {
+            lox.run_line(line.to_string(), &mut int).unwrap();
+        }
+        assert_eq!(out.contents(), "42\n1\n2\n1\n");
+        assert!(lox.run_line("print a\n".to_string(), &mut int).is_ok());
+        assert!(lox.error.is_some(), "statements still need a ';'");
+    }
+
+    #[test]
+    fn prompt_json_reports() {
+        let out = test_util::SharedOutput::default();
+        let mut int = Interpreter::with_io(std::io::empty(), out.clone());
+        let mut lox = Lox::with_error_format(ErrorFormat::Json);
+        lox.run_line("var a = ;\n".to_string(), &mut int).unwrap();
+        assert_eq!(
+            lox.finish_entry().as_deref(),
+            Some(concat!(
+                r#"[{"line":1,"column":9,"severity":"error","#,
+                r#""message":"Parser error: Unexpected token ';' while parsing expression"}]"#
+            ))
+        );
+        assert!(lox.error.is_none());
+        lox.run_line("print 1;\n".to_string(), &mut int).unwrap();
+        assert_eq!(lox.finish_entry(), None);
+        assert_eq!(out.contents(), "1\n");
+    }
+
+    #[test]
+    fn warning_positions() {
+        let mut int = Interpreter::with_io(std::io::empty(), std::io::sink());
+        let mut lox = Lox::with_error_format(ErrorFormat::Json);
+        let src = "{\n  fun helper() {}\n  class Unused {}\n  var é = 1; var unused = 2;\n}\n";
+        lox.run(src.to_string(), &mut int, false).unwrap();
+        assert_eq!(
+            lox.take_json_reports(),
+            concat!(
+                r#"[{"line":4,"column":7,"severity":"warning","#,
+                r#""message":"Local variable é is never used"},"#,
+                r#"{"line":4,"column":18,"severity":"warning","#,
+                r#""message":"Local variable unused is never used"}]"#
+            )
+        );
+        assert_eq!(line_column("ab\ncd", 0), (1, 1));
+        assert_eq!(line_column("ab\ncd", 4), (2, 2));
+    }
+
+    #[test]
+    fn resolved_scopes() {
+        let out = test_util::SharedOutput::default();
+        let mut int = Interpreter::with_io(std::io::empty(), out.clone());
+        let mut lox = Lox::new();
+        let src = r#"
+var a = "global";
+{
+  fun showA() {
+    print a;
+  }
+  showA();
+  var a = "block";
+  showA();
+  print a;
+}
+fun makeCounter() {
+  var i = 0;
+  fun count() {
+    i = i + 1;
+    return i;
+  }
+  return count;
+}
+var counter = makeCounter();
+counter();
+print counter();
+class Box {
+  init(value) {
+    this.value = value;
+  }
+  getter() {
+    fun get() {
+      return this.value;
+    }
+    return get;
+  }
+}
+print Box(3).getter()();
+{
+  fun isEven(n) {
+    if (n == 0) return true;
+    return isOdd(n - 1);
+  }
+  fun isOdd(n) {
+    if (n == 0) return false;
+    return isEven(n - 1);
+  }
+  print isEven(4);
+}
+"#;
+        lox.run(src.to_string(), &mut int, false).unwrap();
+        assert!(lox.error.is_none(), "{:?}", lox.error);
+        assert_eq!(
+            out.contents(),
+            "\"global\"\n\"global\"\n\"block\"\n2\n3\ntrue\n"
+        );
+    }
+
+    #[test]
+    fn open_braces() {
+        assert_eq!(Lox::open_braces("print 1;\n"), 0);
+        assert_eq!(Lox::open_braces("{\n  print \"}\";\n"), 1);
+        assert_eq!(Lox::open_braces("fun f() { // }\n"), 1);
+        assert_eq!(Lox::open_braces("print \"a {\n"), 1);
+        assert_eq!(Lox::open_braces("print \"${1}\";\n"), 0);
+        assert_eq!(Lox::open_braces("}\n"), 0);
+    }
+
+    #[test]
+    fn read_entry() {
+        let input = "{\n  print \"}\";\n  print \"{\";\n}\nprint 1; // {\nprint \"a\nb\";\n";
+        let mut reader = std::io::Cursor::new(input);
+        let entries: Vec<String> = (0..3)
+            .map(|_| Lox::read_entry(&mut reader).unwrap())
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                "{\n  print \"}\";\n  print \"{\";\n}\n",
+                "print 1; // {\n",
+                "print \"a\nb\";\n",
+            ]
+        );
+        assert_eq!(Lox::read_entry(&mut reader).unwrap(), "");
+        let input = "print 1 +\\\n  2;\nfun f() {\n  print 1;\n\nprint 3;\n";
+        let mut reader = std::io::Cursor::new(input);
+        assert_eq!(Lox::read_entry(&mut reader).unwrap(), "print 1 +\n  2;\n");
+        assert_eq!(
+            Lox::read_entry(&mut reader).unwrap(),
+            "fun f() {\n  print 1;\n\n"
+        );
+        assert_eq!(Lox::read_entry(&mut reader).unwrap(), "print 3;\n");
+    }
+
+    #[test]
+    fn next_line() {
+        let check = |entry: &str, start: usize| {
+            let mut entry = entry.to_string();
+            let indent = Lox::next_line(&mut entry, start);
+            (indent, entry)
+        };
+        assert_eq!(check("print 1;\n", 0), (None, "print 1;\n".to_string()));
+        assert_eq!(check("{\n", 0), (Some(1), "{\n".to_string()));
+        assert_eq!(
+            check("print 1 \\\n", 0),
+            (Some(1), "print 1 \n".to_string())
+        );
+        assert_eq!(check("{ {\n}\\\n", 4), (Some(1), "{ {\n}\n".to_string()));
+        assert_eq!(check("{ {\n\n", 4), (None, "{ {\n\n".to_string()));
+        assert_eq!(check("\n", 0), (None, "\n".to_string()));
+    }
+
+    #[test]
+    fn run_file_failed_assert() {
+        let err = run_source(
+            "assert",
+            "assert(1 < 2, \"fine\");\nassert(false, \"boom\");",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.inner().to_string(),
+            "Runtime error: assertion failed: boom"
+        );
+        assert_eq!(err.position().map(|(line, _)| line), Some(2));
+    }
+
+    #[test]
+    fn builder() {
+        let path = std::env::temp_dir().join(format!("roxi-gas-{}.lox", std::process::id()));
+        std::fs::write(&path, "var i = 0;\nwhile (true) {\n  i = i + 1;\n}\n").unwrap();
+        let err = Lox::builder()
+            .gas(100)
+            .max_depth(10)
+            .build()
+            .run_file(&path)
+            .unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(err.inner().to_string(), "Runtime error: out of gas");
+
+        let mut lox = Lox::builder().gas(5).build();
+        let mut int = lox.interpreter();
+        assert!(lox
+            .run("var a = 1;\nvar b = 2;".to_string(), &mut int, false)
+            .is_ok());
+        let mut int = lox.interpreter();
+        let lox_src = "fun f() { return 1; }\nf(); f(); f(); f();".to_string();
+        assert!(lox.run(lox_src, &mut int, false).is_err());
+        // each prompt entry starts with a full tank
+        let mut int = lox.interpreter();
+        for _ in 0..3 {
+            assert!(lox
+                .run_line("var a = 1;\nvar b = 2;\n".to_string(), &mut int)
+                .is_ok());
+        }
+
+        let mut lox = Lox::builder().max_depth(10).build();
+        let mut int = lox.interpreter();
+        let recurse = "fun f(n) { if (n > 0) f(n - 1); }\nf(9);".to_string();
+        assert!(lox.run(recurse, &mut int, false).is_ok());
+        let err = lox.run("f(10);".to_string(), &mut int, false).unwrap_err();
+        assert_eq!(
+            err.inner().to_string(),
+            "Runtime error: stack overflow: recursion depth exceeded"
+        );
+        assert!(Lox::new().interpreter().get_global("clock").is_some());
+    }
+
+    #[test]
+    fn exit() {
+        let out = test_util::SharedOutput::default();
+        let mut int = Interpreter::with_io(std::io::empty(), out.clone());
+        let mut lox = Lox::new();
+        let ret = lox.run("exit(3);\nprint 1;".to_string(), &mut int, false);
+        assert!(matches!(ret, Err(Error::Exit(3))), "{:?}", ret);
+        assert!(lox.error.is_none());
+        assert_eq!(out.contents(), "");
+        let err = run_source("exit", "print 1;\nexit(0);\nprint 2;").unwrap_err();
+        assert!(matches!(err, Error::Exit(0)), "{:?}", err);
+    }
+
+    #[test]
+    fn run_file_ok() {
+        assert!(run_source("ok", "var a = 1;\nprint a + 1;").is_ok());
+        assert!(Lox::new().run_file("does/not/exist.lox").is_err());
+    }
+}
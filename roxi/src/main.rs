@@ -1,17 +1,88 @@
-use roxi::Lox;
+use roxi::{Error, ErrorFormat, Lox, STACK_PER_CALL};
 use std::env::args;
 
+/// How deep calls can nest when running from the command
+/// line, unless `--max-depth` says otherwise
+const CLI_MAX_DEPTH: usize = 10_000;
+
+/// The deepest `--max-depth` allowed, the interpreter
+/// thread's stack grows with it
+const CLI_DEPTH_LIMIT: usize = 100_000;
+
 fn main() {
     let _ = pretty_env_logger::try_init();
     let mut args = args();
     let _ = args.next();
-    let args: Vec<String> = args.collect();
-    let mut lox = Lox::new();
-    match args.len() {
-        0 => lox.run_prompt().expect("failed to run prompt"),
-        1 => lox.run_file(&args[0]).expect("Failed to run file"),
+    let mut format = ErrorFormat::Human;
+    let mut max_depth = CLI_MAX_DEPTH;
+    let mut paths = Vec::new();
+    for arg in args {
+        if let Some(depth) = arg.strip_prefix("--max-depth=") {
+            max_depth = match depth.parse() {
+                Ok(depth) if depth <= CLI_DEPTH_LIMIT => depth,
+                _ => {
+                    eprintln!(
+                        "Invalid max depth {}, expected a number up to {}",
+                        depth, CLI_DEPTH_LIMIT
+                    );
+                    ::std::process::exit(64);
+                }
+            };
+            continue;
+        }
+        match arg.strip_prefix("--errors=") {
+            Some("human") => format = ErrorFormat::Human,
+            Some("json") => format = ErrorFormat::Json,
+            Some(other) => {
+                eprintln!("Unknown error format {}, expected human or json", other);
+                ::std::process::exit(64);
+            }
+            None => paths.push(arg),
+        }
+    }
+    // the main thread's stack only has room for a few
+    // hundred calls, so run on one sized for `max_depth`
+    let stack_size = max_depth
+        .saturating_mul(STACK_PER_CALL)
+        .saturating_add(1 << 20);
+    let runner = ::std::thread::Builder::new()
+        .stack_size(stack_size)
+        .spawn(move || {
+            let lox = Lox::builder()
+                .error_format(format)
+                .max_depth(max_depth)
+                .build();
+            run(lox, paths)
+        })
+        .unwrap_or_else(|e| {
+            eprintln!("Unable to start the interpreter: {}", e);
+            ::std::process::exit(70);
+        });
+    if runner.join().is_err() {
+        ::std::process::exit(70);
+    }
+}
+
+fn run(mut lox: Lox, paths: Vec<String>) {
+    match paths.len() {
+        0 => match lox.run_prompt() {
+            Err(Error::Exit(code)) => ::std::process::exit(code),
+            ret => ret.expect("failed to run prompt"),
+        },
+        1 => {
+            if let Err(e) = lox.run_file(&paths[0]) {
+                if let Error::Exit(code) = e {
+                    ::std::process::exit(code);
+                }
+                let code = match e.inner() {
+                    Error::Runtime(_) => 70,
+                    _ => 65,
+                };
+                ::std::process::exit(code);
+            }
+        }
         _ => {
-            eprintln!("Usage roxc [script]");
+            eprintln!("Usage roxi [--errors=human|json] [--max-depth=n] [script]");
             ::std::process::exit(64);
         }
     }
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+
+/// A map that remembers the order keys were first
+/// inserted in, so walking it gives the same order
+/// on every run
+#[derive(Debug, Clone)]
+pub struct OrderedMap<V> {
+    entries: Vec<(String, V)>,
+    /// Where each key's entry is in `entries`
+    indices: HashMap<String, usize>,
+}
+
+impl<V> Default for OrderedMap<V> {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+            indices: HashMap::new(),
+        }
+    }
+}
+
+impl<V> OrderedMap<V> {
+    pub fn get(&self, key: &str) -> Option<&V> {
+        self.indices.get(key).map(|&idx| &self.entries[idx].1)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.indices.contains_key(key)
+    }
+
+    /// Set `key` to `value`, a key that is already
+    /// present keeps its original position
+    pub fn insert(&mut self, key: String, value: V) -> Option<V> {
+        if let Some(&idx) = self.indices.get(&key) {
+            return Some(std::mem::replace(&mut self.entries[idx].1, value));
+        }
+        self.indices.insert(key.clone(), self.entries.len());
+        self.entries.push((key, value));
+        None
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<V> {
+        let idx = self.indices.remove(key)?;
+        let (_, value) = self.entries.remove(idx);
+        for (key, _) in &self.entries[idx..] {
+            if let Some(i) = self.indices.get_mut(key) {
+                *i -= 1;
+            }
+        }
+        Some(value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The entries in insertion order
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &V)> {
+        self.entries.iter().map(|(k, v)| (k, v))
+    }
+}
+
+/// Two maps are equal when they hold the same entries,
+/// no matter what order they were inserted in
+impl<V: PartialEq> PartialEq for OrderedMap<V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn insertion_order() {
+        let mut map = OrderedMap::default();
+        for key in ["zebra", "apple", "mango", "kiwi"] {
+            map.insert(key.to_string(), key.len());
+        }
+        assert_eq!(map.insert("apple".to_string(), 0), Some(5));
+        assert_eq!(map.remove("zebra"), Some(5));
+        assert_eq!(map.remove("zebra"), None);
+        map.insert("zebra".to_string(), 1);
+        let keys: Vec<&str> = map.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, ["apple", "mango", "kiwi", "zebra"]);
+        assert_eq!(map.get("mango"), Some(&5));
+        assert_eq!(map.get("kiwi"), Some(&4));
+        assert_eq!(map.get("apple"), Some(&0));
+        let mut other = OrderedMap::default();
+        for (key, value) in map.iter().collect::<Vec<_>>().into_iter().rev() {
+            other.insert(key.clone(), *value);
+        }
+        assert_eq!(map, other);
+    }
+}
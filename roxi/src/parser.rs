@@ -1,12 +1,17 @@
 use super::error::Error;
-use super::expr::{Expr, Literal};
+use super::expr::{Expr, Literal, Span};
 use super::stmt::{Function, Stmt};
 use super::Scanner;
 use super::SimpleResult;
 use rox_shared::{Token, TokenType};
+use std::{cell::Cell, rc::Rc};
 
 type ParserItem = Result<Stmt, Error>;
 
+/// The most parameters a function can declare and
+/// the most arguments a call can pass
+const MAX_ARGS: usize = 255;
+
 pub struct Parser {
     pub scanner: Scanner,
     tokens: Vec<Token>,
@@ -24,6 +29,10 @@ impl Parser {
         self.scanner.line
     }
 
+    pub fn column(&self) -> usize {
+        self.scanner.token_column()
+    }
+
     pub fn decl(&mut self) -> SimpleResult<Stmt> {
         if self.at(TokenType::Var)? {
             self.var_decl()
@@ -56,6 +65,7 @@ impl Parser {
 
     pub fn var_decl(&mut self) -> SimpleResult<Stmt> {
         let name = self.expect_ident()?;
+        let span = Span::from(&self.previous()?);
         let value = if self.at(TokenType::Equal)? {
             Some(self.expression()?)
         } else {
@@ -65,7 +75,7 @@ impl Parser {
             TokenType::Semicolon,
             "Expect ';' after variable declaration.",
         )?;
-        Ok(Stmt::Var { name, value })
+        Ok(Stmt::Var { name, value, span })
     }
 
     pub fn fun_decl(&mut self, kind: &str) -> SimpleResult<Stmt> {
@@ -75,20 +85,23 @@ impl Parser {
 
     pub fn bare_func(&mut self, kind: &str) -> SimpleResult<Function> {
         let name = self.expect_ident()?;
+        let span = Span::from(&self.previous()?);
         self.consume(
             TokenType::LeftParen,
             &format!("Expected ( after {} identifier", kind),
         )?;
         let mut params = vec![];
+        let mut param_spans = vec![];
         if !self.check(TokenType::RightParen) {
-            params.push(self.expect_ident()?);
-            while self.at(TokenType::Comma)? {
+            loop {
                 params.push(self.expect_ident()?);
-                if params.len() > 255 {
-                    return Err(Error::Parser(format!(
-                        "{} {:?} has too many parameters",
-                        kind, name
-                    )));
+                param_spans.push(Span::from(&self.previous()?));
+                if params.len() > MAX_ARGS {
+                    let err = Error::Parser(format!("Too many parameters in {} {:?}", kind, name));
+                    return Err(err.at(&self.previous()?));
+                }
+                if !self.at(TokenType::Comma)? {
+                    break;
                 }
             }
         }
@@ -101,11 +114,24 @@ impl Parser {
             &format!("Expected {{ after {} arguments", kind),
         )?;
         let body = self.bare_block()?;
-        Ok(Function { name, params, body })
+        Ok(Function {
+            name,
+            span,
+            params,
+            param_spans,
+            body: Rc::new(body),
+        })
     }
 
     pub fn class_decl(&mut self) -> SimpleResult<Stmt> {
         let ident = self.expect_ident()?;
+        let span = Span::from(&self.previous()?);
+        let super_class = if self.at(TokenType::Less)? {
+            let super_class = self.expect_ident()?;
+            Some((super_class, Span::from(&self.previous()?)))
+        } else {
+            None
+        };
         self.consume(
             TokenType::LeftBrace,
             &format!("Expected {{ after class name: {}", ident),
@@ -120,6 +146,8 @@ impl Parser {
         )?;
         Ok(Stmt::Class {
             name: ident,
+            span,
+            super_class,
             methods,
         })
     }
@@ -134,13 +162,14 @@ impl Parser {
     }
 
     pub fn return_stmt(&mut self) -> SimpleResult<Stmt> {
+        let span = Span::from(&self.previous()?);
         let val = if !self.check(TokenType::Semicolon) {
             Some(self.expression()?)
         } else {
             None
         };
         self.consume(TokenType::Semicolon, "Expected ; after return")?;
-        Ok(Stmt::Return(val))
+        Ok(Stmt::Return(val, span))
     }
 
     pub fn while_stmt(&mut self) -> SimpleResult<Stmt> {
@@ -169,7 +198,7 @@ impl Parser {
         let cond = if !self.check(TokenType::Semicolon) {
             self.expression()?
         } else {
-            Expr::Literal(Literal::Bool(true))
+            Expr::Literal(Literal::Bool(true), Span::from(&self.previous()?))
         };
         self.consume(TokenType::Semicolon, "Expected ';' after for loop test")?;
         let update = if !self.check(TokenType::RightParen) {
@@ -179,20 +208,12 @@ impl Parser {
         };
         self.consume(TokenType::RightParen, "Expected ')' after if (...")?;
         let body = self.statement()?;
-        let mut block = vec![];
-        if let Some(init) = init {
-            block.push(init);
-        }
-        let mut w_body = vec![body];
-        if let Some(expr) = update {
-            w_body.push(Stmt::Expr(expr));
-        }
-        let w = Stmt::While {
+        Ok(Stmt::For {
+            init: init.map(Box::new),
             test: cond,
-            body: Box::new(Stmt::Block(w_body)),
-        };
-        block.push(w);
-        Ok(Stmt::Block(block))
+            update,
+            body: Box::new(body),
+        })
     }
     pub fn block_stmt(&mut self) -> SimpleResult<Stmt> {
         Ok(Stmt::Block(self.bare_block()?))
@@ -241,26 +262,66 @@ impl Parser {
     fn assignment(&mut self) -> SimpleResult<Expr> {
         let expr = self.logical_or()?;
         if self.at(TokenType::Equal)? {
+            let equals = self.previous()?;
             let value = self.assignment()?;
-            if let Expr::Var(name) = expr {
-                Ok(Expr::assign(name, value))
-            } else if let Expr::Get { object, name } = expr {
+            if let Expr::Var(name, span, _) = expr {
+                Ok(Expr::assign(name, span, value))
+            } else if let Expr::Get { object, name, span } = expr {
                 Ok(Expr::Set {
                     object,
                     name,
+                    span: span.to(value.span()),
                     value: Box::new(value),
                 })
             } else {
-                Err(Error::Parser(format!(
-                    "Expected ident before equals found {:?}",
-                    expr
-                )))
+                Err(
+                    Error::Parser(format!("Expected ident before equals found {:?}", expr))
+                        .at(&equals),
+                )
+            }
+        } else if let Some(op) = self.compound_operator()? {
+            let value = self.assignment()?;
+            match expr {
+                Expr::Var(name, span, _) => {
+                    let value = Expr::grouping(value.clone(), value.span());
+                    let current = Expr::Var(name.clone(), span, Cell::default());
+                    Ok(Expr::assign(name, span, Expr::binary(current, value, op)))
+                }
+                Expr::Get { object, name, span } => Ok(Expr::CompoundSet {
+                    object,
+                    name,
+                    operator: op,
+                    span: span.to(value.span()),
+                    value: Box::new(value),
+                }),
+                _ => Err(Error::Parser(format!(
+                    "Expected ident or property before {}= found {:?}",
+                    op.lexeme, expr
+                ))
+                .at(&op)),
             }
         } else {
             Ok(expr)
         }
     }
 
+    /// Consume a compound assignment operator, returning the
+    /// binary operator it applies
+    fn compound_operator(&mut self) -> SimpleResult<Option<Token>> {
+        let kind = match self.scanner.lookahead().as_ref().map(|t| &t.kind) {
+            Some(TokenType::PlusEqual) => TokenType::Plus,
+            Some(TokenType::MinusEqual) => TokenType::Minus,
+            Some(TokenType::StarEqual) => TokenType::Star,
+            Some(TokenType::SlashEqual) => TokenType::Slash,
+            _ => return Ok(None),
+        };
+        self.advance()?;
+        let mut op = self.previous()?;
+        op.kind = kind;
+        op.lexeme.pop();
+        Ok(Some(op))
+    }
+
     fn logical_or(&mut self) -> SimpleResult<Expr> {
         let mut expr = self.logical_and()?;
         while self.at(TokenType::Or)? {
@@ -292,12 +353,54 @@ impl Parser {
     }
 
     fn comparison(&mut self) -> SimpleResult<Expr> {
-        let mut expr = self.addition()?;
+        let mut expr = self.bit_or()?;
         while self.at(TokenType::Greater)?
             || self.at(TokenType::GreaterEqual)?
             || self.at(TokenType::Less)?
             || self.at(TokenType::LessEqual)?
         {
+            let op = self.previous()?;
+            let right = self.bit_or()?;
+            expr = Expr::binary(expr, right, op);
+        }
+        Ok(expr)
+    }
+
+    /// The bitwise operators bind tighter than comparisons
+    /// so `x & 1 == 0` tests the low bit of `x`
+    fn bit_or(&mut self) -> SimpleResult<Expr> {
+        let mut expr = self.bit_xor()?;
+        while self.at(TokenType::Pipe)? {
+            let op = self.previous()?;
+            let right = self.bit_xor()?;
+            expr = Expr::binary(expr, right, op);
+        }
+        Ok(expr)
+    }
+
+    fn bit_xor(&mut self) -> SimpleResult<Expr> {
+        let mut expr = self.bit_and()?;
+        while self.at(TokenType::Caret)? {
+            let op = self.previous()?;
+            let right = self.bit_and()?;
+            expr = Expr::binary(expr, right, op);
+        }
+        Ok(expr)
+    }
+
+    fn bit_and(&mut self) -> SimpleResult<Expr> {
+        let mut expr = self.shift()?;
+        while self.at(TokenType::Ampersand)? {
+            let op = self.previous()?;
+            let right = self.shift()?;
+            expr = Expr::binary(expr, right, op);
+        }
+        Ok(expr)
+    }
+
+    fn shift(&mut self) -> SimpleResult<Expr> {
+        let mut expr = self.addition()?;
+        while self.at(TokenType::LessLess)? || self.at(TokenType::GreaterGreater)? {
             let op = self.previous()?;
             let right = self.addition()?;
             expr = Expr::binary(expr, right, op);
@@ -343,6 +446,14 @@ impl Parser {
             } else if self.at(TokenType::Dot)? {
                 let name = self.expect_ident()?;
                 expr = Expr::Get {
+                    span: expr.span().to(Span::from(&self.previous()?)),
+                    object: Box::new(expr),
+                    name,
+                };
+            } else if self.at(TokenType::QuestionDot)? {
+                let name = self.expect_ident()?;
+                expr = Expr::OptionalGet {
+                    span: expr.span().to(Span::from(&self.previous()?)),
                     object: Box::new(expr),
                     name,
                 };
@@ -350,20 +461,44 @@ impl Parser {
                 break;
             }
         }
+        if self.at(TokenType::PlusPlus)? || self.at(TokenType::MinusMinus)? {
+            let operator = self.previous()?;
+            if !matches!(expr, Expr::Var(..) | Expr::Get { .. }) {
+                return Err(Error::Parser(format!(
+                    "Expected a variable or property before {}",
+                    operator.kind
+                ))
+                .at(&operator));
+            }
+            expr = Expr::Postfix {
+                span: expr.span().to(Span::from(&operator)),
+                target: Box::new(expr),
+                operator,
+            };
+        }
         Ok(expr)
     }
 
     fn finish_call(&mut self, expr: Expr) -> SimpleResult<Expr> {
         let mut args = vec![];
         if !self.check(TokenType::RightParen) {
-            args.push(self.expression()?);
-            while self.at(TokenType::Comma)? {
+            loop {
                 args.push(self.expression()?);
+                if args.len() > MAX_ARGS {
+                    let err = Error::Parser("Call has too many arguments".to_string());
+                    return Err(err.at(&self.previous()?));
+                }
+                if !self.at(TokenType::Comma)? {
+                    break;
+                }
             }
         }
         self.consume(TokenType::RightParen, "Expected ) at end of function call")?;
+        let paren = self.previous()?;
         Ok(Expr::Call {
+            span: expr.span().to(Span::from(&paren)),
             callee: Box::new(expr),
+            paren,
             arguments: args,
         })
     }
@@ -375,24 +510,70 @@ impl Parser {
                 || self.at(TokenType::Nil)?
                 || self.at_literal()?
             {
-                Expr::Literal(self.previous_literal()?)
+                Expr::Literal(self.previous_literal()?, Span::from(&self.previous()?))
+            } else if let Some(part) = self.at_interpolation()? {
+                self.interpolation(part)?
             } else if self.at(TokenType::This)? {
-                Expr::This
+                Expr::This(Span::from(&self.previous()?))
+            } else if self.at(TokenType::Super)? {
+                let start = Span::from(&self.previous()?);
+                self.consume(TokenType::Dot, "Expected '.' after 'super'")?;
+                let method = self.expect_ident()?;
+                Expr::Super {
+                    method,
+                    span: start.to(Span::from(&self.previous()?)),
+                }
             } else if self.at_ident()? {
-                Expr::Var(self.previous_ident()?)
+                Expr::Var(
+                    self.previous_ident()?,
+                    Span::from(&self.previous()?),
+                    Cell::default(),
+                )
             } else if self.at(TokenType::LeftParen)? {
+                let start = Span::from(&self.previous()?);
                 let expr = self.expression()?;
                 self.consume(TokenType::RightParen, "Expect ')' after expression")?;
-                Expr::grouping(expr)
+                Expr::grouping(expr, start.to(Span::from(&self.previous()?)))
             } else {
-                return Err(Error::Parser(format!(
-                    "Unexpected expression: {:?}",
-                    self.scanner.lookahead()
-                )));
+                return Err(self.unexpected("expression").unwrap_or_else(|| {
+                    self.located(Error::Parser(format!(
+                        "Unexpected {} while parsing expression",
+                        self.found()
+                    )))
+                }));
             },
         )
     }
 
+    /// Parse the rest of an interpolated string, `first` is the
+    /// string part before the first `${`
+    fn interpolation(&mut self, first: String) -> SimpleResult<Expr> {
+        let start = Span::from(&self.previous()?);
+        let mut parts = vec![Expr::Literal(Literal::String(first), start)];
+        loop {
+            parts.push(self.expression()?);
+            let next = self.scanner.lookahead().as_ref().map(|t| t.kind.clone());
+            match next {
+                Some(TokenType::Interpolation(part)) => {
+                    self.advance()?;
+                    let span = Span::from(&self.previous()?);
+                    parts.push(Expr::Literal(Literal::String(part), span));
+                }
+                Some(TokenType::String(part)) => {
+                    self.advance()?;
+                    let span = Span::from(&self.previous()?);
+                    parts.push(Expr::Literal(Literal::String(part), span));
+                    return Ok(Expr::Interpolation(parts, start.to(span)));
+                }
+                _ => {
+                    return Err(self.located(Error::Parser(
+                        "Expected } to close string interpolation".to_string(),
+                    )))
+                }
+            }
+        }
+    }
+
     fn previous_literal(&mut self) -> SimpleResult<Literal> {
         Ok(match self.previous()?.kind {
             TokenType::String(s) => Literal::String(s),
@@ -400,7 +581,7 @@ impl Parser {
             TokenType::True => Literal::Bool(true),
             TokenType::False => Literal::Bool(false),
             TokenType::Nil => Literal::Nil,
-            _ => return Err(Error::Parser("expected literal".into())),
+            _ => return Err(Error::Parser("Expected literal".into())),
         })
     }
 
@@ -438,6 +619,19 @@ impl Parser {
             Ok(false)
         }
     }
+    fn at_interpolation(&mut self) -> Result<Option<String>, Error> {
+        if let Some(Token {
+            kind: TokenType::Interpolation(part),
+            ..
+        }) = self.scanner.lookahead()
+        {
+            let part = part.clone();
+            self.advance()?;
+            Ok(Some(part))
+        } else {
+            Ok(None)
+        }
+    }
     fn at_ident(&mut self) -> Result<bool, Error> {
         if let Some(tok) = self.scanner.lookahead() {
             if let TokenType::Identifier(_) = tok.kind {
@@ -460,20 +654,23 @@ impl Parser {
     }
 
     fn expect_ident(&mut self) -> Result<String, Error> {
+        if let Some(err) = self.unexpected("identifier") {
+            return Err(err);
+        }
         let name = if let Some(tok) = self.scanner.lookahead() {
             if let TokenType::Identifier(name) = &tok.kind {
                 name.to_string()
             } else {
-                return Err(Error::Parser(format!(
-                    "Expected identifier found: {:?}",
-                    self.scanner.lookahead()
-                )));
+                return Err(self.located(Error::Parser(format!(
+                    "Expected identifier, found {}",
+                    self.found()
+                ))));
             }
         } else {
-            return Err(Error::Parser(format!(
-                "Expected identifier found: {:?}",
-                self.scanner.lookahead()
-            )));
+            return Err(self.located(Error::Parser(format!(
+                "Expected identifier, found {}",
+                self.found()
+            ))));
         };
         self.advance()?;
         Ok(name)
@@ -482,7 +679,10 @@ impl Parser {
     fn advance(&mut self) -> Result<(), Error> {
         if !self.is_at_end() {
             if let Some(res) = self.scanner.next() {
-                self.tokens.push(res.map_err(|e| Error::Scanner(e))?)
+                let tok = res.map_err(|e| {
+                    Error::Scanner(e).at_position(self.scanner.line, self.scanner.token_column())
+                })?;
+                self.tokens.push(tok)
             }
         }
         Ok(())
@@ -501,14 +701,83 @@ impl Parser {
     }
 
     fn consume(&mut self, tok: TokenType, msg: &str) -> SimpleResult<()> {
-        if self.check(tok) {
+        if self.check(tok.clone()) {
             self.advance()?;
             Ok(())
+        } else if let Some(err) = self.unexpected(&tok.to_string()) {
+            Err(err)
+        } else {
+            Err(self.located(Error::Parser(format!(
+                "{}, found {}",
+                msg.trim_end_matches('.'),
+                self.found()
+            ))))
+        }
+    }
+
+    /// Describe the next token for an error message, its
+    /// kind along with the text it was scanned from
+    fn found(&self) -> String {
+        let tok = match self.scanner.lookahead() {
+            Some(tok) => tok,
+            None => return TokenType::Eof.to_string(),
+        };
+        match &tok.kind {
+            TokenType::Identifier(_)
+            | TokenType::String(_)
+            | TokenType::Interpolation(_)
+            | TokenType::Number(_) => format!("{} '{}'", tok.kind, tok.lexeme),
+            TokenType::Eof => tok.kind.to_string(),
+            kind => format!("token {}", kind),
+        }
+    }
+
+    /// If the input has run out, an error explaining that `expected`
+    /// was expected, distinguishing end of input from a wrong token
+    fn unexpected(&self, expected: &str) -> Option<Error> {
+        if self.is_at_end() {
+            Some(self.located(Error::Parser(format!(
+                "Unexpected end of input, expected {}",
+                expected
+            ))))
+        } else {
+            None
+        }
+    }
+
+    /// Attach the position of the lookahead token (the one that
+    /// caused the error) or the scanner's position if there isn't one
+    fn located(&self, err: Error) -> Error {
+        if let Some(tok) = self.scanner.lookahead() {
+            err.at(tok)
         } else {
-            Err(Error::Parser(msg.to_string()))
+            err.at_position(self.scanner.line, self.scanner.token_column())
         }
     }
 
+    /// Parse the whole input, recovering after each error
+    /// so every syntax problem is reported, not just the first.
+    /// Every error carries the position it was found at
+    pub fn parse_all(&mut self) -> (Vec<Stmt>, Vec<Error>) {
+        let mut stmts = Vec::new();
+        let mut errors = Vec::new();
+        while let Some(res) = self.next() {
+            match res {
+                Ok(stmt) => stmts.push(stmt),
+                Err(e) => {
+                    let e = if e.position().is_some() {
+                        e
+                    } else {
+                        e.at_position(self.line(), self.column())
+                    };
+                    errors.push(e);
+                    self.sync();
+                }
+            }
+        }
+        (stmts, errors)
+    }
+
     pub fn sync(&mut self) {
         let _ = self.advance();
         while !self.is_at_end() {
@@ -543,3 +812,235 @@ impl Iterator for Parser {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse_err(lox: &str) -> Error {
+        let parser = Parser::new(Scanner::new(lox.to_string()).unwrap());
+        parser
+            .filter_map(Result::err)
+            .next()
+            .expect("expected a parser error")
+    }
+
+    #[test]
+    fn unterminated_block() {
+        let err = parse_err("{ var a = 1;\n print a;");
+        assert_eq!(
+            err.inner().to_string(),
+            "Parser error: Unexpected end of input, expected '}'"
+        );
+        assert_eq!(err.position().map(|(line, _)| line), Some(2));
+    }
+
+    #[test]
+    fn too_many_params() {
+        let names = |n: usize| (0..n).map(|i| format!("a{}", i)).collect::<Vec<_>>();
+        let decl = |n| format!("fun f({}) {{}}", names(n).join(", "));
+        let call = |n| format!("f({});", names(n).join(", "));
+        let mut parser = Parser::new(Scanner::new(decl(MAX_ARGS) + &call(MAX_ARGS)).unwrap());
+        assert!(parser.all(|stmt| stmt.is_ok()));
+        assert_eq!(
+            parse_err(&decl(MAX_ARGS + 1)).inner().to_string(),
+            "Parser error: Too many parameters in function \"f\""
+        );
+        let lox = call(MAX_ARGS + 1);
+        let err = parse_err(&lox);
+        assert_eq!(
+            err.inner().to_string(),
+            "Parser error: Call has too many arguments"
+        );
+        let last = lox.find(&format!("a{}", MAX_ARGS)).unwrap();
+        assert_eq!(err.position(), Some((1, last + 1)));
+    }
+
+    #[test]
+    fn unterminated_call() {
+        let err = parse_err("print clock(1, 2");
+        assert_eq!(
+            err.inner().to_string(),
+            "Parser error: Unexpected end of input, expected ')'"
+        );
+    }
+
+    #[test]
+    fn unterminated_class() {
+        let err = parse_err("class Thing {\n  method() {}\n");
+        assert_eq!(
+            err.inner().to_string(),
+            "Parser error: Unexpected end of input, expected '}'"
+        );
+        let err = parse_err("class Thing {\n  method(");
+        assert_eq!(
+            err.inner().to_string(),
+            "Parser error: Unexpected end of input, expected identifier"
+        );
+    }
+
+    #[test]
+    fn compound_assign_call_target() {
+        let mut parser = Parser::new(Scanner::new("make().count += 1;".to_string()).unwrap());
+        match parser.next() {
+            Some(Ok(Stmt::Expr(Expr::CompoundSet { object, name, .. }))) => {
+                assert!(matches!(*object, Expr::Call { .. }), "{:?}", object);
+                assert_eq!(name, "count");
+            }
+            other => panic!("expected a compound set, found {:?}", other),
+        }
+        let err = parse_err("make() += 1;");
+        assert!(
+            err.inner().to_string().contains("before += found"),
+            "{}",
+            err
+        );
+    }
+
+    fn number_literal(lox: &str) -> f64 {
+        let mut parser = Parser::new(Scanner::new(format!("{};", lox)).unwrap());
+        match parser.expression().unwrap() {
+            Expr::Literal(Literal::Number(n), _) => n,
+            expr => panic!("expected a number literal found {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn number_literals() {
+        assert_eq!(number_literal("0xFF"), 255.0);
+        assert_eq!(number_literal("0b1010"), 10.0);
+        assert_eq!(number_literal("1e3"), 1000.0);
+        assert_eq!(number_literal("2.5e-3"), 0.0025);
+        assert_eq!(number_literal("1_000"), 1000.0);
+        assert_eq!(number_literal("0987.321"), 987.321);
+    }
+
+    #[test]
+    fn no_unary_plus() {
+        let err = parse_err("print +1;");
+        assert!(
+            err.inner()
+                .to_string()
+                .starts_with("Parser error: Unexpected token '+' while parsing expression"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn parse_all_collects_errors() {
+        let lox = "var a = ;\nprint 1;\nvar = 2;\nprint 3;\n";
+        let mut parser = Parser::new(Scanner::new(lox.to_string()).unwrap());
+        let (stmts, errors) = parser.parse_all();
+        assert_eq!(stmts.len(), 2);
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        let lines: Vec<usize> = errors
+            .iter()
+            .filter_map(|e| e.position().map(|(line, _)| line))
+            .collect();
+        assert_eq!(lines, vec![1, 3]);
+    }
+
+    #[test]
+    fn wrong_token_is_not_eof() {
+        let err = parse_err("print clock(1, 2;");
+        assert_eq!(
+            err.inner().to_string(),
+            "Parser error: Expected ) at end of function call, found token ';'"
+        );
+    }
+
+    #[test]
+    fn postfix_targets() {
+        for lox in ["1++;", "f()--;", "(a)++;"] {
+            let err = parse_err(lox);
+            assert!(
+                err.inner()
+                    .to_string()
+                    .starts_with("Parser error: Expected a variable or property before '"),
+                "{}: {}",
+                lox,
+                err
+            );
+        }
+        assert!(parse_err("a++++;")
+            .inner()
+            .to_string()
+            .ends_with("found token '++'"));
+        let parser = Parser::new(Scanner::new("a.b++;\nc--;".to_string()).unwrap());
+        assert!(parser.collect::<Result<Vec<_>, _>>().is_ok());
+    }
+
+    #[test]
+    fn unexpected_token_message() {
+        let err = parse_err("var a = 1;\n\n\nprint (1 + );");
+        assert_eq!(
+            err.to_string(),
+            "[line 4:12] Parser error: Unexpected token ')' while parsing expression"
+        );
+        let err = parse_err("var a = 1\nprint a;");
+        assert_eq!(
+            err.to_string(),
+            "[line 2:1] Parser error: Expect ';' after variable declaration, found token 'print'"
+        );
+        let err = parse_err("var 12 = 1;");
+        assert_eq!(
+            err.inner().to_string(),
+            "Parser error: Expected identifier, found number '12'"
+        );
+    }
+
+    #[test]
+    fn error_line_in_block() {
+        let lox = "
+fun f() {
+    if (true) {
+        while (false) {
+            print 1 + ;
+        }
+    }
+}
+";
+        let mut parser = Parser::new(Scanner::new(lox.to_string()).unwrap());
+        let err = parser.next().unwrap().unwrap_err();
+        assert_eq!(err.position(), Some((5, 23)), "{}", err);
+        assert!(matches!(err.inner(), Error::Parser(_)));
+    }
+
+    #[test]
+    fn error_line_missing_semicolon() {
+        let lox = "
+{
+    var a = 1;
+    var b = 2
+}
+";
+        let mut parser = Parser::new(Scanner::new(lox.to_string()).unwrap());
+        let err = parser.next().unwrap().unwrap_err();
+        assert_eq!(err.position().map(|(line, _)| line), Some(5), "{}", err);
+    }
+
+    fn span_of(lox: &str) -> (usize, usize) {
+        let mut parser = Parser::new(Scanner::new(format!("{};", lox)).unwrap());
+        let span = parser.expression().unwrap().span();
+        (span.start, span.end)
+    }
+
+    #[test]
+    fn expression_spans() {
+        assert_eq!(span_of("12 + 345"), (0, 8));
+        assert_eq!(span_of("a.b.c"), (0, 5));
+        assert_eq!(span_of("f(1, (2))"), (0, 9));
+        assert_eq!(span_of("-(x)"), (0, 4));
+        assert_eq!(span_of("x += 1"), (0, 6));
+        assert_eq!(span_of(r#""a${b}c""#), (0, 8));
+        let mut parser = Parser::new(Scanner::new("1 * 2 + 3;".to_string()).unwrap());
+        match parser.expression().unwrap() {
+            Expr::Binary { left, right, .. } => {
+                assert_eq!((left.span().start, left.span().end), (0, 5));
+                assert_eq!((right.span().start, right.span().end), (8, 9));
+            }
+            expr => panic!("expected a binary expression found {:?}", expr),
+        }
+    }
+}
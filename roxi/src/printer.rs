@@ -0,0 +1,419 @@
+use crate::{
+    error::Error,
+    expr::{Binding, Expr, ExprVisitor, Literal, Span},
+    stmt::{Function, Stmt, StmtVisitor},
+};
+use rox_shared::{Token, TokenType};
+use std::cell::Cell;
+
+type PrintResult = Result<String, Error>;
+
+/// Turns a parse tree back into Lox source. Groupings
+/// from the original source are kept, any other
+/// parentheses are only added where precedence needs
+/// them, blocks are indented with two spaces
+#[derive(Default)]
+pub struct Printer {
+    indent: usize,
+}
+
+impl Printer {
+    pub fn print(stmts: &[Stmt]) -> String {
+        let mut printer = Self::default();
+        let mut ret = String::new();
+        for stmt in stmts {
+            if let Ok(s) = stmt.accept(&mut printer) {
+                ret.push_str(&s);
+                ret.push('\n');
+            }
+        }
+        ret
+    }
+
+    fn expr(&mut self, expr: &Expr, min_prec: u8) -> PrintResult {
+        let s = expr.accept(self)?;
+        if Self::precedence(expr) < min_prec {
+            Ok(format!("({})", s))
+        } else {
+            Ok(s)
+        }
+    }
+
+    /// How tightly an expression binds, following
+    /// the levels in the parser
+    fn precedence(expr: &Expr) -> u8 {
+        match expr {
+            Expr::Assign { .. } | Expr::Set { .. } | Expr::CompoundSet { .. } => 1,
+            Expr::Log { operator, .. } | Expr::Binary { operator, .. } => {
+                Self::op_precedence(operator)
+            }
+            Expr::Unary { .. } => 12,
+            _ => 13,
+        }
+    }
+
+    fn op_precedence(op: &Token) -> u8 {
+        match op.kind {
+            TokenType::Or => 2,
+            TokenType::And => 3,
+            TokenType::EqualEqual | TokenType::BangEqual => 4,
+            TokenType::Greater
+            | TokenType::GreaterEqual
+            | TokenType::Less
+            | TokenType::LessEqual => 5,
+            TokenType::Pipe => 6,
+            TokenType::Caret => 7,
+            TokenType::Ampersand => 8,
+            TokenType::LessLess | TokenType::GreaterGreater => 9,
+            TokenType::Plus | TokenType::Minus => 10,
+            _ => 11,
+        }
+    }
+
+    fn binary(&mut self, left: &Expr, op: &Token, right: &Expr) -> PrintResult {
+        let prec = Self::op_precedence(op);
+        let left = self.expr(left, prec)?;
+        let right = self.expr(right, prec + 1)?;
+        Ok(format!("{} {} {}", left, op.lexeme, right))
+    }
+
+    fn block(&mut self, list: &[Stmt]) -> PrintResult {
+        if list.is_empty() {
+            return Ok("{}".to_string());
+        }
+        self.indent += 1;
+        let mut ret = String::from("{\n");
+        for stmt in list {
+            let s = stmt.accept(self)?;
+            ret.push_str(&"  ".repeat(self.indent));
+            ret.push_str(&s);
+            ret.push('\n');
+        }
+        self.indent -= 1;
+        ret.push_str(&"  ".repeat(self.indent));
+        ret.push('}');
+        Ok(ret)
+    }
+
+    fn function(&mut self, name: &str, params: &[String], body: &[Stmt]) -> PrintResult {
+        let body = self.block(body)?;
+        Ok(format!("{}({}) {}", name, params.join(", "), body))
+    }
+}
+
+impl ExprVisitor<String> for Printer {
+    fn visit_bin(&mut self, left: &Expr, op: &Token, right: &Expr) -> PrintResult {
+        self.binary(left, op, right)
+    }
+    fn visit_group(&mut self, group: &Expr) -> PrintResult {
+        if let Expr::Grouping(inner, _) = group {
+            Ok(format!("({})", self.expr(inner, 1)?))
+        } else {
+            Err(Error::Runtime("Visited group unexpectedly".into()))
+        }
+    }
+    fn visit_lit(&self, lit: &Literal) -> PrintResult {
+        Ok(lit.to_string())
+    }
+    fn visit_un(&mut self, op: &Token, ex: &Expr) -> PrintResult {
+        let operand = self.expr(ex, 12)?;
+        // `- -x` would scan as `--x` without the space
+        if op.kind == TokenType::Minus && operand.starts_with('-') {
+            Ok(format!("{} {}", op.lexeme, operand))
+        } else {
+            Ok(format!("{}{}", op.lexeme, operand))
+        }
+    }
+    fn visit_var(&mut self, name: &str, _: Span, _: &Cell<Binding>) -> PrintResult {
+        Ok(name.to_string())
+    }
+    fn visit_assign(
+        &mut self,
+        name: &str,
+        value: &Expr,
+        _: Span,
+        _: &Cell<Binding>,
+    ) -> PrintResult {
+        Ok(format!("{} = {}", name, self.expr(value, 1)?))
+    }
+    fn visit_log(&mut self, left: &Expr, op: &Token, right: &Expr) -> PrintResult {
+        self.binary(left, op, right)
+    }
+    fn visit_call(&mut self, callee: &Expr, _: &Token, arguments: &[Expr]) -> PrintResult {
+        let callee = self.expr(callee, 13)?;
+        let args = arguments
+            .iter()
+            .map(|arg| self.expr(arg, 1))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(format!("{}({})", callee, args.join(", ")))
+    }
+    fn visit_get(&mut self, object: &Expr, name: &str, _: Span) -> PrintResult {
+        Ok(format!("{}.{}", self.expr(object, 13)?, name))
+    }
+    fn visit_optional_get(&mut self, object: &Expr, name: &str) -> PrintResult {
+        Ok(format!("{}?.{}", self.expr(object, 13)?, name))
+    }
+    fn visit_set(&mut self, object: &Expr, name: &str, value: &Expr) -> PrintResult {
+        let object = self.expr(object, 13)?;
+        Ok(format!("{}.{} = {}", object, name, self.expr(value, 1)?))
+    }
+    fn visit_compound_set(
+        &mut self,
+        object: &Expr,
+        name: &str,
+        operator: &Token,
+        value: &Expr,
+    ) -> PrintResult {
+        let object = self.expr(object, 13)?;
+        let value = self.expr(value, 1)?;
+        Ok(format!(
+            "{}.{} {}= {}",
+            object, name, operator.lexeme, value
+        ))
+    }
+    fn visit_this(&mut self) -> PrintResult {
+        Ok("this".to_string())
+    }
+    fn visit_postfix(&mut self, target: &Expr, operator: &Token) -> PrintResult {
+        Ok(format!("{}{}", self.expr(target, 13)?, operator.lexeme))
+    }
+    fn visit_super(&mut self, method: &str, _: Span) -> PrintResult {
+        Ok(format!("super.{}", method))
+    }
+    fn visit_interpolation(&mut self, parts: &[Expr]) -> PrintResult {
+        let mut ret = String::from("\"");
+        for (i, part) in parts.iter().enumerate() {
+            match part {
+                Expr::Literal(Literal::String(s), _) if i % 2 == 0 => ret.push_str(s),
+                _ => ret.push_str(&format!("${{{}}}", self.expr(part, 1)?)),
+            }
+        }
+        ret.push('"');
+        Ok(ret)
+    }
+}
+
+impl StmtVisitor<String> for Printer {
+    fn visit_print_stmt(&mut self, expr: &Expr) -> PrintResult {
+        Ok(format!("print {};", self.expr(expr, 1)?))
+    }
+    fn visit_expr_stmt(&mut self, expr: &Expr) -> PrintResult {
+        Ok(format!("{};", self.expr(expr, 1)?))
+    }
+    fn visit_var_stmt(&mut self, name: &str, _: Span, expr: &Option<Expr>) -> PrintResult {
+        match expr {
+            Some(expr) => Ok(format!("var {} = {};", name, self.expr(expr, 1)?)),
+            None => Ok(format!("var {};", name)),
+        }
+    }
+    fn visit_block_stmt(&mut self, list: &[Stmt]) -> PrintResult {
+        self.block(list)
+    }
+    fn visit_if_stmt(&mut self, test: &Expr, cons: &Stmt, alt: &Option<Box<Stmt>>) -> PrintResult {
+        let mut ret = format!("if ({}) {}", self.expr(test, 1)?, cons.accept(self)?);
+        if let Some(alt) = alt {
+            ret.push_str(" else ");
+            ret.push_str(&alt.accept(self)?);
+        }
+        Ok(ret)
+    }
+    fn visit_while_stmt(&mut self, test: &Expr, body: &Stmt) -> PrintResult {
+        Ok(format!(
+            "while ({}) {}",
+            self.expr(test, 1)?,
+            body.accept(self)?
+        ))
+    }
+    fn visit_for_stmt(
+        &mut self,
+        init: &Option<Box<Stmt>>,
+        test: &Expr,
+        update: &Option<Expr>,
+        body: &Stmt,
+    ) -> PrintResult {
+        let init = match init {
+            Some(init) => init.accept(self)?,
+            None => ";".to_string(),
+        };
+        let update = match update {
+            Some(update) => self.expr(update, 1)?,
+            None => String::new(),
+        };
+        Ok(format!(
+            "for ({} {}; {}) {}",
+            init,
+            self.expr(test, 1)?,
+            update,
+            body.accept(self)?
+        ))
+    }
+    fn visit_func_decl(&mut self, func: &Function) -> PrintResult {
+        Ok(format!(
+            "fun {}",
+            self.function(&func.name, &func.params, &func.body)?
+        ))
+    }
+    fn visit_return_stmt(&mut self, expr: &Option<Expr>, _: Span) -> PrintResult {
+        match expr {
+            Some(expr) => Ok(format!("return {};", self.expr(expr, 1)?)),
+            None => Ok("return;".to_string()),
+        }
+    }
+    fn visit_class(
+        &mut self,
+        name: &str,
+        _: Span,
+        super_class: Option<(&str, Span)>,
+        methods: &[Function],
+    ) -> PrintResult {
+        let name = match super_class {
+            Some((super_class, _)) => format!("{} < {}", name, super_class),
+            None => name.to_string(),
+        };
+        if methods.is_empty() {
+            return Ok(format!("class {} {{}}", name));
+        }
+        self.indent += 1;
+        let mut ret = format!("class {} {{\n", name);
+        for method in methods {
+            ret.push_str(&"  ".repeat(self.indent));
+            ret.push_str(&self.function(&method.name, &method.params, &method.body)?);
+            ret.push('\n');
+        }
+        self.indent -= 1;
+        ret.push_str(&"  ".repeat(self.indent));
+        ret.push('}');
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parser::Parser, Scanner};
+
+    fn parse(lox: &str) -> Vec<Stmt> {
+        let (stmts, errors) = Parser::new(Scanner::new(lox.to_string()).unwrap()).parse_all();
+        assert!(errors.is_empty(), "{:?}\n{}", errors, lox);
+        stmts
+    }
+
+    /// The debug output of `stmts` with every source position
+    /// left out, so trees parsed from differently laid out
+    /// source can be compared
+    fn without_positions(stmts: &[Stmt]) -> String {
+        let debug = format!("{:?}", stmts);
+        let mut ret = String::with_capacity(debug.len());
+        let mut rest = debug.as_str();
+        while !rest.is_empty() {
+            let marker = ["line: ", "column: ", "span: (", "start: ", "end: "]
+                .iter()
+                .find(|marker| rest.starts_with(**marker));
+            if let Some(marker) = marker {
+                ret.push_str(marker);
+                rest = rest[marker.len()..]
+                    .trim_start_matches(|c: char| c.is_ascii_digit() || c == ',' || c == ' ');
+                continue;
+            }
+            let c = rest.chars().next().unwrap();
+            ret.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+        ret
+    }
+
+    #[test]
+    fn round_trip() {
+        let lox = r#"
+var count = 0;
+var name;
+fun add(a, b) {
+    return a + b;
+}
+class Base {}
+class Counter < Base {
+    init() { this.count = 0; }
+    bump(by) {
+        this.count += by;
+        return;
+    }
+    empty() {}
+    next() { return this.count++; }
+}
+var c = Counter();
+c.bump(2 * (3 + 4));
+print c?.count;
+for (var i = 0; i < 10; i++) {
+    print (i & 1 | 2) ^ (1 << i >> 1);
+    if (i == 2 or !(i > 5 and i != 7)) print "${i} is ${-i * 2}!"; else {
+        count = count - -1;
+    }
+}
+while (false) {}
+print add(1, 2) / 0.5;
+"#;
+        let first = parse(lox);
+        let printed = Printer::print(&first);
+        let second = parse(&printed);
+        assert_eq!(
+            without_positions(&first),
+            without_positions(&second),
+            "{}",
+            printed
+        );
+        assert_eq!(printed, Printer::print(&second));
+    }
+
+    #[test]
+    fn super_round_trip() {
+        let lox = r#"
+class Base {
+    greet() { return "hi"; }
+}
+class Derived < Base {
+    greet() { return super.greet() + "!"; }
+    method() { return super.greet; }
+}
+"#;
+        let first = parse(lox);
+        let printed = Printer::print(&first);
+        assert!(printed.contains("super.greet()"), "{}", printed);
+        let second = parse(&printed);
+        assert_eq!(
+            without_positions(&first),
+            without_positions(&second),
+            "{}",
+            printed
+        );
+        assert_eq!(printed, Printer::print(&second));
+    }
+
+    #[test]
+    fn nested_unary() {
+        let first = parse("print - -x;\nprint !!x;\nprint -(-1);\nprint - - -1;\n");
+        let printed = Printer::print(&first);
+        assert_eq!(
+            printed,
+            "print - -x;\nprint !!x;\nprint -(-1);\nprint - - -1;\n"
+        );
+        let second = parse(&printed);
+        assert_eq!(without_positions(&first), without_positions(&second));
+    }
+
+    #[test]
+    fn indentation() {
+        let printed = Printer::print(&parse("fun f(a) { if (a) { print a; } }"));
+        assert_eq!(printed, "fun f(a) {\n  if (a) {\n    print a;\n  }\n}\n");
+    }
+
+    #[test]
+    fn precedence() {
+        let op = |kind, lexeme: &str| Token::new(kind, lexeme.to_string(), 1, 1, 0, 0);
+        let num = |n| Expr::Literal(Literal::Number(n), Span::default());
+        let sum = Expr::binary(num(1.0), num(2.0), op(TokenType::Plus, "+"));
+        let product = Expr::binary(sum.clone(), num(3.0), op(TokenType::Star, "*"));
+        let difference = Expr::binary(num(3.0), sum, op(TokenType::Minus, "-"));
+        let printed = Printer::print(&[Stmt::Print(product), Stmt::Print(difference)]);
+        assert_eq!(printed, "print (1 + 2) * 3;\nprint 3 - (1 + 2);\n");
+    }
+}
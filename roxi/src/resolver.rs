@@ -1,51 +1,93 @@
 use crate::{
-    error::{Error},
-    expr::{Expr, ExprVisitor, Literal, ScopedExpr},
-    interpreter::Interpreter,
-    parser::Parser,
-    stmt::{Stmt, StmtVisitor, ScopedStmt},
-    token::Token,
-};
-use std::{cell::Cell,
-        collections::HashMap,
+    error::Error,
+    expr::{Binding, Expr, ExprVisitor, Literal, Span},
+    stmt::{Function, Stmt, StmtVisitor},
 };
 use log::trace;
+use rox_shared::Token;
+use std::cell::Cell;
+use std::collections::HashMap;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Default)]
 enum FuncType {
+    #[default]
     None,
     Func,
     Init,
-    Method
+    Method,
+}
+
+/// The kind of class being resolved, so `super` can
+/// only be used where there is a superclass
+#[derive(Clone, Copy, Default)]
+enum ClassType {
+    #[default]
+    None,
+    Class,
+    Subclass,
+}
+
+/// What the resolver knows about a local variable, locals
+/// declared later in a block are in its scope but not
+/// yet `declared`
+#[derive(Clone, Copy, Default)]
+struct Local {
+    declared: bool,
+    defined: bool,
+    used: bool,
+    /// Functions and classes can be referred to from a
+    /// function declared above them in the same block
+    hoisted: bool,
+    /// Where a `var` statement declared this local, only
+    /// those are reported when they go unused
+    var_span: Option<Span>,
+}
+
+/// A problem worth mentioning that doesn't stop the program
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub message: String,
+    pub span: Span,
 }
+
+/// A static pass over the parse tree that checks how local
+/// variables are used before anything is run
+#[derive(Default)]
 pub struct Resolver {
-    pub scopes: Vec<HashMap<String, bool>>,
+    scopes: Vec<HashMap<String, Local>>,
     current_func: FuncType,
+    current_class: ClassType,
+    pub warnings: Vec<Warning>,
 }
 
 impl StmtVisitor<()> for Resolver {
     fn visit_print_stmt(&mut self, expr: &Expr) -> Result<(), Error> {
-        trace!("Resolver::visit_expr_stmt {:?}", expr);
-        self.resolve_expr(expr)?;
-        Ok(())
+        trace!("Resolver::visit_print_stmt {:?}", expr);
+        self.resolve_expr(expr)
     }
     fn visit_expr_stmt(&mut self, expr: &Expr) -> Result<(), Error> {
         trace!("Resolver::visit_expr_stmt {:?}", expr);
-        self.resolve_expr(expr)?;
-        Ok(())
+        self.resolve_expr(expr)
     }
-    fn visit_var_stmt(&mut self, name: &str, expr: &Option<Expr>) -> Result<(), Error> {
+    fn visit_var_stmt(&mut self, name: &str, span: Span, expr: &Option<Expr>) -> Result<(), Error> {
         trace!("Resolver::visit_var_stmt {:?} {:?}", name, expr);
-        self.declare(name)?;
+        self.declare(name, span)?;
+        if let Some(local) = self.scopes.last_mut().and_then(|scope| scope.get_mut(name)) {
+            local.var_span = Some(span);
+        }
         if let Some(expr) = expr {
             self.resolve_expr(expr)?;
         }
+        self.define(name);
         Ok(())
     }
     fn visit_block_stmt(&mut self, list: &[Stmt]) -> Result<(), Error> {
         trace!("Resolver::visit_block_stmt {:?}", list);
-        self.resolve_stmt_list(list)?;
-        Ok(())
+        self.begin_scope();
+        self.forward_declare(list);
+        let ret = self.resolve_stmt_list(list);
+        self.end_scope();
+        ret
     }
     fn visit_if_stmt(
         &mut self,
@@ -64,35 +106,105 @@ impl StmtVisitor<()> for Resolver {
     fn visit_while_stmt(&mut self, test: &Expr, body: &Stmt) -> Result<(), Error> {
         trace!("Resolver::visit_while_stmt {:?} {:?}", test, body);
         self.resolve_expr(test)?;
-        self.resolve_stmt(body)?;
-        Ok(())
+        self.resolve_stmt(body)
     }
-    fn visit_func_decl(&mut self, name: &str, params: &[String], body: &[Stmt]) -> Result<(), Error> {
-        trace!("Resolver::visit_func_decl {:?} {:?} {:?}", name, params, body);
-        self.declare(name)?;
-        self.define(name);
-        self.resolve_func(params, body, FuncType::Func)?;
-        Ok(())
+    fn visit_for_stmt(
+        &mut self,
+        init: &Option<Box<Stmt>>,
+        test: &Expr,
+        update: &Option<Expr>,
+        body: &Stmt,
+    ) -> Result<(), Error> {
+        trace!(
+            "Resolver::visit_for_stmt {:?} {:?} {:?}",
+            init,
+            test,
+            update
+        );
+        self.begin_scope();
+        let ret = self.resolve_for(init, test, update, body);
+        self.end_scope();
+        ret
+    }
+    fn visit_func_decl(&mut self, func: &Function) -> Result<(), Error> {
+        trace!(
+            "Resolver::visit_func_decl {:?} {:?} {:?}",
+            func.name,
+            func.params,
+            func.body
+        );
+        self.declare(&func.name, func.span)?;
+        self.define(&func.name);
+        self.resolve_func(func, FuncType::Func)
     }
-    fn visit_return_stmt(&mut self, expr: &Option<Expr>) -> Result<(), Error> {
+    fn visit_return_stmt(&mut self, expr: &Option<Expr>, span: Span) -> Result<(), Error> {
         trace!("Resolver::visit_return_stmt {:?}", expr);
         if let FuncType::None = self.current_func {
-            return Err(Error::Parser(format!("cannot return from outside of a function or method")));
+            return Err(Error::Resolution(
+                "cannot return from outside of a function or method".to_string(),
+            )
+            .at_span(span));
         }
         if let Some(expr) = expr {
             if let FuncType::Init = self.current_func {
-                return Err(Error::Parser(format!("Cannot return a value from an initializer")));
+                return Err(Error::Resolution(
+                    "Cannot return a value from an initializer".to_string(),
+                )
+                .at_span(span));
             }
             self.resolve_expr(expr)?;
         }
         Ok(())
     }
-    fn visit_class(&mut self, name: &str, methods: &[Function]) -> Result<(), Error> {
+    fn visit_class(
+        &mut self,
+        name: &str,
+        span: Span,
+        super_class: Option<(&str, Span)>,
+        methods: &[Function],
+    ) -> Result<(), Error> {
+        trace!("Resolver::visit_class {:?} < {:?}", name, super_class);
+        self.declare(name, span)?;
         self.define(name);
-        for meth in methods {
-            self.resolve_func(&meth.params, &meth.body, FuncType::Method)?;
+        let enclosing = self.current_class;
+        self.current_class = ClassType::Class;
+        // the scopes are opened even after an error, so
+        // they can all be closed again on the way out
+        let ret = match super_class {
+            Some((super_class, super_span)) if super_class == name => Err(Error::Resolution(
+                format!("A class can't inherit from itself ({})", name),
+            )
+            .at_span(super_span)),
+            Some((super_class, super_span)) => {
+                self.visit_var(super_class, super_span, &Cell::default())
+            }
+            None => Ok(()),
+        };
+        if super_class.is_some() {
+            self.current_class = ClassType::Subclass;
+            self.begin_scope();
+            self.define_implicit("super");
         }
-        Ok(())
+        // matches the scope `this` is bound in when a
+        // method is looked up
+        self.begin_scope();
+        self.define_implicit("this");
+        let ret = ret.and_then(|_| {
+            methods.iter().try_for_each(|meth| {
+                let ty = if meth.name == "init" {
+                    FuncType::Init
+                } else {
+                    FuncType::Method
+                };
+                self.resolve_func(meth, ty)
+            })
+        });
+        self.end_scope();
+        if super_class.is_some() {
+            self.end_scope();
+        }
+        self.current_class = enclosing;
+        ret
     }
 }
 
@@ -100,13 +212,15 @@ impl ExprVisitor<()> for Resolver {
     fn visit_bin(&mut self, left: &Expr, _: &Token, right: &Expr) -> Result<(), Error> {
         trace!("Resolver::visit_bin {:?}  {:?}", left, right);
         self.resolve_expr(left)?;
-        self.resolve_expr(right)?;
-        Ok(())
+        self.resolve_expr(right)
     }
     fn visit_group(&mut self, group: &Expr) -> Result<(), Error> {
         trace!("Resolver::visit_group {:?}", group);
-        self.resolve_expr(group)?;
-        Ok(())
+        if let Expr::Grouping(inner, _) = group {
+            self.resolve_expr(inner)
+        } else {
+            Err(Error::Resolution("Visited group unexpectedly".into()))
+        }
     }
     fn visit_lit(&self, _: &Literal) -> Result<(), Error> {
         trace!("Resolver::visit_lit");
@@ -114,33 +228,49 @@ impl ExprVisitor<()> for Resolver {
     }
     fn visit_un(&mut self, _: &Token, ex: &Expr) -> Result<(), Error> {
         trace!("Resolver::visit_unary {:?}", ex);
-        self.resolve_expr(ex)?;
-        Ok(())
+        self.resolve_expr(ex)
     }
-    fn visit_var(&mut self, name: &str) -> Result<(), Error> {
+    fn visit_var(&mut self, name: &str, span: Span, binding: &Cell<Binding>) -> Result<(), Error> {
         trace!("Resolver::visit_var {}", name);
         if let Some(scope) = self.scopes.last() {
             if let Some(entry) = scope.get(name) {
-                if entry == &false {
-                    return Err(Error::Parser(format!("Cannot read local variable in its own initializer ({})", name)));
+                if !entry.declared {
+                    return Err(Error::Parser(format!(
+                        "Cannot read local variable {} before it is declared",
+                        name
+                    ))
+                    .at_span(span));
+                }
+                if !entry.defined {
+                    return Err(Error::Resolution(format!(
+                        "Cannot read local variable in its own initializer ({})",
+                        name
+                    ))
+                    .at_span(span));
                 }
             }
         }
+        binding.set(self.binding(name));
         Ok(())
     }
-    fn visit_assign(&mut self, name: &str, value: &Expr, scope: &Cell<Option<usize>>) -> Result<(), Error> {
-        trace!("Resolver::visit_assign {:?} {:?} {:?}", name, value, scope);
+    fn visit_assign(
+        &mut self,
+        name: &str,
+        value: &Expr,
+        _: Span,
+        binding: &Cell<Binding>,
+    ) -> Result<(), Error> {
+        trace!("Resolver::visit_assign {:?} {:?}", name, value);
         self.resolve_expr(value)?;
-        scope.set(self.resolve_local(name));
+        binding.set(self.binding(name));
         Ok(())
     }
     fn visit_log(&mut self, left: &Expr, _: &Token, right: &Expr) -> Result<(), Error> {
         trace!("Resolver::visit_log {:?} {:?}", left, right);
         self.resolve_expr(left)?;
-        self.resolve_expr(right)?;
-        Ok(())
+        self.resolve_expr(right)
     }
-    fn visit_call(&mut self, callee: &Expr, arguments: &[Expr]) -> Result<(), Error> {
+    fn visit_call(&mut self, callee: &Expr, _: &Token, arguments: &[Expr]) -> Result<(), Error> {
         trace!("Resolver::visit_call {:?} {:?}", callee, arguments);
         self.resolve_expr(callee)?;
         for arg in arguments {
@@ -148,24 +278,69 @@ impl ExprVisitor<()> for Resolver {
         }
         Ok(())
     }
-    fn visit_get(&mut self, object: &Expr, name: &str) -> Result<(), Error> {
+    fn visit_get(&mut self, object: &Expr, name: &str, _: Span) -> Result<(), Error> {
         trace!("Resolver::visit_get {:?} {:?}", object, name);
-        self.resolve_expr(object)?;
-        Ok(())
+        self.resolve_expr(object)
+    }
+    fn visit_optional_get(&mut self, object: &Expr, name: &str) -> Result<(), Error> {
+        trace!("Resolver::visit_optional_get {:?} {:?}", object, name);
+        self.resolve_expr(object)
     }
     fn visit_set(&mut self, object: &Expr, name: &str, value: &Expr) -> Result<(), Error> {
+        trace!("Resolver::visit_set {:?} {:?} {:?}", object, name, value);
         self.resolve_expr(object)?;
-        self.resolve_expr(value)?;
+        self.resolve_expr(value)
+    }
+    fn visit_compound_set(
+        &mut self,
+        object: &Expr,
+        name: &str,
+        _: &Token,
+        value: &Expr,
+    ) -> Result<(), Error> {
+        trace!(
+            "Resolver::visit_compound_set {:?} {:?} {:?}",
+            object,
+            name,
+            value
+        );
+        self.resolve_expr(object)?;
+        self.resolve_expr(value)
+    }
+    fn visit_this(&mut self) -> Result<(), Error> {
+        trace!("Resolver::visit_this");
+        Ok(())
+    }
+    fn visit_postfix(&mut self, target: &Expr, _: &Token) -> Result<(), Error> {
+        trace!("Resolver::visit_postfix {:?}", target);
+        self.resolve_expr(target)
+    }
+    fn visit_super(&mut self, method: &str, span: Span) -> Result<(), Error> {
+        trace!("Resolver::visit_super {:?}", method);
+        match self.current_class {
+            ClassType::Subclass => Ok(()),
+            ClassType::Class => Err(Error::Resolution(
+                "Can't use 'super' in a class with no superclass".to_string(),
+            )
+            .at_span(span)),
+            ClassType::None => Err(Error::Resolution(
+                "Can't use 'super' outside of a class".to_string(),
+            )
+            .at_span(span)),
+        }
+    }
+    fn visit_interpolation(&mut self, parts: &[Expr]) -> Result<(), Error> {
+        trace!("Resolver::visit_interpolation {:?}", parts);
+        for part in parts {
+            self.resolve_expr(part)?;
+        }
         Ok(())
     }
 }
 
 impl Resolver {
     pub fn new() -> Self {
-        Self {
-            current_func: FuncType::None,
-            scopes: Vec::new(),
-        }
+        Self::default()
     }
 
     pub fn resolve_stmt_list(&mut self, stmts: &[Stmt]) -> Result<(), Error> {
@@ -174,60 +349,310 @@ impl Resolver {
         }
         Ok(())
     }
+    fn resolve_for(
+        &mut self,
+        init: &Option<Box<Stmt>>,
+        test: &Expr,
+        update: &Option<Expr>,
+        body: &Stmt,
+    ) -> Result<(), Error> {
+        if let Some(init) = init {
+            self.resolve_stmt(init)?;
+        }
+        self.resolve_expr(test)?;
+        if let Some(update) = update {
+            self.resolve_expr(update)?;
+        }
+        self.resolve_stmt(body)
+    }
     pub fn resolve_stmt(&mut self, stmt: &Stmt) -> Result<(), Error> {
-        stmt.accept(self)?;
-        Ok(())
+        stmt.accept(self)
     }
     pub fn resolve_expr(&mut self, expr: &Expr) -> Result<(), Error> {
-        expr.accept(self)?;
-        Ok(())
+        expr.accept(self)
     }
-    fn resolve_func(&mut self, params: &[String], body: &[Stmt], ty: FuncType) -> Result<(), Error> {
+    fn resolve_func(&mut self, func: &Function, ty: FuncType) -> Result<(), Error> {
         let enclosing = self.current_func;
         self.current_func = ty;
         self.begin_scope();
-        for param in params {
-            self.declare(param)?;
-            self.define(param);
-        }
-        self.resolve_stmt_list(body)?;
+        let ret = func
+            .params
+            .iter()
+            .zip(&func.param_spans)
+            .try_for_each(|(param, span)| {
+                self.declare(param, *span)?;
+                self.define(param);
+                // parameters are part of a function's signature
+                // so they are never reported as unused
+                self.resolve_local(param);
+                Ok(())
+            })
+            .and_then(|_| {
+                self.forward_declare(&func.body);
+                self.resolve_stmt_list(&func.body)
+            });
         self.end_scope();
         self.current_func = enclosing;
-        Ok(())
+        ret
     }
     pub fn begin_scope(&mut self) {
         self.scopes.push(HashMap::new());
     }
     pub fn end_scope(&mut self) {
-        self.scopes.pop();
+        if let Some(scope) = self.scopes.pop() {
+            let mut unused: Vec<(&String, Span)> = scope
+                .iter()
+                .filter(|(_, local)| local.defined && !local.used)
+                .filter_map(|(name, local)| local.var_span.map(|span| (name, span)))
+                .collect();
+            unused.sort_by_key(|(_, span)| span.start);
+            for (name, span) in unused {
+                self.warnings.push(Warning {
+                    message: format!("Local variable {} is never used", name),
+                    span,
+                });
+            }
+        }
     }
-    pub fn declare(&mut self, name: &str) -> Result<(), Error> {
+    /// Add the names a block declares to the current scope
+    /// so reading one before its declaration can be caught
+    fn forward_declare(&mut self, stmts: &[Stmt]) {
         if let Some(scope) = self.scopes.last_mut() {
-            if scope.contains_key(name) {
-                return Err(Error::Parser(format!("{} has already been declared in this scope", name)));
-            } else {
-                scope.insert(name.to_string(), false);
+            for stmt in stmts {
+                let (name, hoisted) = match stmt {
+                    Stmt::Var { name, .. } => (name, false),
+                    Stmt::Func(Function { name, .. }) | Stmt::Class { name, .. } => (name, true),
+                    _ => continue,
+                };
+                scope.entry(name.clone()).or_default().hoisted = hoisted;
             }
         }
-        
+    }
+    /// Declare `name` in the current scope, `span` is where
+    /// an error is reported if it was already declared there
+    pub fn declare(&mut self, name: &str, span: Span) -> Result<(), Error> {
+        if let Some(scope) = self.scopes.last_mut() {
+            let entry = scope.entry(name.to_string()).or_default();
+            if entry.declared {
+                return Err(Error::Resolution(format!(
+                    "{} has already been declared in this scope",
+                    name
+                ))
+                .at_span(span));
+            }
+            entry.declared = true;
+        }
         Ok(())
     }
 
     pub fn define(&mut self, name: &str) {
         if let Some(scope) = self.scopes.last_mut() {
             if let Some(entry) = scope.get_mut(name) {
-                *entry = true;
+                entry.defined = true;
             }
         }
     }
 
+    /// How many scopes out `name` is defined, marking it
+    /// as used. Variables declared further down a block
+    /// aren't in scope yet, so a closure declared above
+    /// one keeps referring to the outer variable
     pub fn resolve_local(&mut self, name: &str) -> Option<usize> {
         let scope_len = self.scopes.len().saturating_sub(1);
         for (i, scope) in self.scopes.iter_mut().enumerate().rev() {
-            if scope.contains_key(name) {
-                return Some(scope_len - i);
+            match scope.get_mut(name) {
+                Some(local) if local.declared || local.hoisted => {
+                    local.used = true;
+                    return Some(scope_len - i);
+                }
+                _ => (),
             }
         }
-        return None;
+        None
+    }
+
+    /// Where the interpreter should look `name` up
+    fn binding(&mut self, name: &str) -> Binding {
+        match self.resolve_local(name) {
+            Some(distance) => Binding::Local(distance),
+            None => Binding::Global,
+        }
+    }
+
+    /// Define a name the interpreter adds on its own, like
+    /// `this`, which isn't reported when it goes unused
+    fn define_implicit(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(
+                name.to_string(),
+                Local {
+                    declared: true,
+                    defined: true,
+                    used: true,
+                    ..Local::default()
+                },
+            );
+        }
     }
-}
\ No newline at end of file
+
+    /// Take the warnings collected so far
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.warnings)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parser::Parser, Scanner};
+
+    fn resolve(lox: &str) -> Result<Vec<String>, Error> {
+        let mut resolver = Resolver::new();
+        for stmt in Parser::new(Scanner::new(lox.to_string()).unwrap()) {
+            resolver.resolve_stmt(&stmt.unwrap())?;
+        }
+        Ok(resolver
+            .take_warnings()
+            .into_iter()
+            .map(|warning| warning.message)
+            .collect())
+    }
+
+    #[test]
+    fn unused_local() {
+        let warnings = resolve("{ var a = 1; var b = 2; print b; }").unwrap();
+        assert_eq!(warnings, vec!["Local variable a is never used".to_string()]);
+        // only variables are reported, not local functions or classes
+        let warnings = resolve("{ fun f() {} class C {} var a; }").unwrap();
+        assert_eq!(warnings, vec!["Local variable a is never used".to_string()]);
+    }
+
+    #[test]
+    fn exempt_from_unused() {
+        let lox = "
+var global = 1;
+fun add(a, b) {
+    return 1;
+}
+fun outer() {
+    var captured = 1;
+    fun inner() {
+        return captured;
+    }
+    return inner;
+}
+for (var i = 0; i < 2; i = i + 1) {}
+";
+        assert_eq!(resolve(lox).unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn own_initializer() {
+        let err = resolve("{ var a = 1; { var a = a; } }").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[line 1:24] Resolution error: Cannot read local variable in its own initializer (a)"
+        );
+    }
+
+    #[test]
+    fn already_declared() {
+        let err = resolve("fun f(a, a) {}").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[line 1:10] Resolution error: a has already been declared in this scope"
+        );
+        let err = resolve("{ var a;\n  class a {} }").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[line 2:9] Resolution error: a has already been declared in this scope"
+        );
+    }
+
+    #[test]
+    fn state_restored_after_error() {
+        let lox = "fun f(a, a) { return; }\nclass A < A {}\nreturn 1;\nfun g() { super.g(); }";
+        let (stmts, errors) = Parser::new(Scanner::new(lox.to_string()).unwrap()).parse_all();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let mut resolver = Resolver::new();
+        let errors: Vec<String> = stmts
+            .iter()
+            .filter_map(|stmt| resolver.resolve_stmt(stmt).err())
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                "[line 1:10] Resolution error: a has already been declared in this scope",
+                "[line 2:11] Resolution error: A class can't inherit from itself (A)",
+                "[line 3:1] Resolution error: cannot return from outside of a function or method",
+                "[line 4:11] Resolution error: Can't use 'super' outside of a class",
+            ]
+        );
+        assert!(resolver.scopes.is_empty());
+    }
+
+    #[test]
+    fn inherit_from_self() {
+        let err = resolve("class A < A {}").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[line 1:11] Resolution error: A class can't inherit from itself (A)"
+        );
+    }
+
+    #[test]
+    fn super_without_superclass() {
+        let err = resolve("class A { f() { return super.f(); } }").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[line 1:24] Resolution error: Can't use 'super' in a class with no superclass"
+        );
+        let err = resolve("fun f() { return super.f(); }").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[line 1:18] Resolution error: Can't use 'super' outside of a class"
+        );
+        let err =
+            resolve("class A {}\nclass B < A {}\nclass C { f() { super.f(); } }").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[line 3:17] Resolution error: Can't use 'super' in a class with no superclass"
+        );
+        assert!(resolve("class A {}\nclass B < A { f() { fun g() { return super.f; } } }").is_ok());
+    }
+
+    #[test]
+    fn read_before_declaration() {
+        let err = resolve("{ print a; var a = 1; }").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[line 1:9] Parser error: Cannot read local variable a before it is declared"
+        );
+        let err = resolve("fun f() { var b = later(); fun later() {} }").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[line 1:19] Parser error: Cannot read local variable later before it is declared"
+        );
+    }
+
+    #[test]
+    fn read_after_declaration() {
+        let lox = "
+var a = 1;
+{
+    print a;
+    var b = a;
+    print b;
+}
+{
+    fun fib(n) {
+        if (n < 2) return n;
+        return fib(n - 1) + fib(n - 2);
+    }
+    print fib(5);
+}
+";
+        assert_eq!(resolve(lox).unwrap(), Vec::<String>::new());
+    }
+}
@@ -1,11 +1,17 @@
-use super::{error::Error, expr::Expr};
-#[derive(Debug, Clone)]
+use super::{
+    error::Error,
+    expr::{Expr, Span},
+};
+use std::rc::Rc;
+#[derive(Debug, Clone, PartialEq)]
 pub enum Stmt {
     Print(Expr),
     Expr(Expr),
     Var {
         name: String,
         value: Option<Expr>,
+        /// Where `name` is in the source
+        span: Span,
     },
     Block(Vec<Stmt>),
     If {
@@ -17,26 +23,45 @@ pub enum Stmt {
         test: Expr,
         body: Box<Stmt>,
     },
+    /// Kept apart from `While` so each iteration can
+    /// get its own copy of the loop variables
+    For {
+        init: Option<Box<Stmt>>,
+        test: Expr,
+        update: Option<Expr>,
+        body: Box<Stmt>,
+    },
     Func(Function),
-    Return(Option<Expr>),
+    /// The span is the `return` keyword
+    Return(Option<Expr>, Span),
     Class {
         name: String,
+        /// Where `name` is in the source
+        span: Span,
+        /// The name after `<` and where it is, if this
+        /// class inherits from another
+        super_class: Option<(String, Span)>,
         methods: Vec<Function>,
     },
 }
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Function {
     pub name: String,
+    /// Where `name` is in the source
+    pub span: Span,
     pub params: Vec<String>,
-    pub body: Vec<Stmt>,
+    /// Where each of `params` is in the source
+    pub param_spans: Vec<Span>,
+    /// Shared with every `Func` made from this declaration
+    pub body: Rc<Vec<Stmt>>,
 }
 
 impl Stmt {
-    pub fn accept<T>(&mut self, visitor: &mut impl StmtVisitor<T>) -> Result<T, Error> {
+    pub fn accept<T>(&self, visitor: &mut impl StmtVisitor<T>) -> Result<T, Error> {
         match self {
             Stmt::Print(inner) => visitor.visit_print_stmt(inner),
             Stmt::Expr(inner) => visitor.visit_expr_stmt(inner),
-            Stmt::Var { name, value } => visitor.visit_var_stmt(name, value),
+            Stmt::Var { name, value, span } => visitor.visit_var_stmt(name, *span, value),
             Stmt::Block(list) => visitor.visit_block_stmt(list),
             Stmt::If {
                 test,
@@ -44,29 +69,57 @@ impl Stmt {
                 alternate,
             } => visitor.visit_if_stmt(test, consequence, alternate),
             Stmt::While { test, body } => visitor.visit_while_stmt(test, body),
-            Stmt::Func(Function { name, params, body }) => {
-                visitor.visit_func_decl(name, params, body)
-            }
-            Stmt::Return(expr) => visitor.visit_return_stmt(expr),
-            Stmt::Class { name, methods } => visitor.visit_class(name, methods),
+            Stmt::For {
+                init,
+                test,
+                update,
+                body,
+            } => visitor.visit_for_stmt(init, test, update, body),
+            Stmt::Func(func) => visitor.visit_func_decl(func),
+            Stmt::Return(expr, span) => visitor.visit_return_stmt(expr, *span),
+            Stmt::Class {
+                name,
+                span,
+                super_class,
+                methods,
+            } => visitor.visit_class(
+                name,
+                *span,
+                super_class
+                    .as_ref()
+                    .map(|(name, span)| (name.as_str(), *span)),
+                methods,
+            ),
         }
     }
 }
 
 pub trait StmtVisitor<T> {
-    fn visit_print_stmt(&mut self, expr: &mut Expr) -> Result<T, Error>;
-    fn visit_expr_stmt(&mut self, expr: &mut Expr) -> Result<T, Error>;
-    fn visit_var_stmt(&mut self, name: &str, expr: &mut Option<Expr>) -> Result<T, Error>;
-    fn visit_block_stmt(&mut self, list: &mut [Stmt]) -> Result<T, Error>;
+    fn visit_print_stmt(&mut self, expr: &Expr) -> Result<T, Error>;
+    fn visit_expr_stmt(&mut self, expr: &Expr) -> Result<T, Error>;
+    fn visit_var_stmt(&mut self, name: &str, span: Span, expr: &Option<Expr>) -> Result<T, Error>;
+    fn visit_block_stmt(&mut self, list: &[Stmt]) -> Result<T, Error>;
     fn visit_if_stmt(
         &mut self,
-        test: &mut Expr,
-        cons: &mut Stmt,
-        alt: &mut Option<Box<Stmt>>,
+        test: &Expr,
+        cons: &Stmt,
+        alt: &Option<Box<Stmt>>,
+    ) -> Result<T, Error>;
+    fn visit_while_stmt(&mut self, test: &Expr, body: &Stmt) -> Result<T, Error>;
+    fn visit_for_stmt(
+        &mut self,
+        init: &Option<Box<Stmt>>,
+        test: &Expr,
+        update: &Option<Expr>,
+        body: &Stmt,
+    ) -> Result<T, Error>;
+    fn visit_func_decl(&mut self, func: &Function) -> Result<T, Error>;
+    fn visit_return_stmt(&mut self, expr: &Option<Expr>, span: Span) -> Result<T, Error>;
+    fn visit_class(
+        &mut self,
+        name: &str,
+        span: Span,
+        super_class: Option<(&str, Span)>,
+        methods: &[Function],
     ) -> Result<T, Error>;
-    fn visit_while_stmt(&mut self, test: &mut Expr, body: &mut Stmt) -> Result<T, Error>;
-    fn visit_func_decl(&mut self, name: &str, params: &[String], body: &[Stmt])
-        -> Result<T, Error>;
-    fn visit_return_stmt(&mut self, expr: &mut Option<Expr>) -> Result<T, Error>;
-    fn visit_class(&mut self, name: &str, methods: &mut [Function]) -> Result<T, Error>;
 }
@@ -0,0 +1,57 @@
+//! Helpers shared by the interpreter's unit tests
+use crate::{parser::Parser, Error, Interpreter, Scanner};
+use std::{cell::RefCell, io::Write, rc::Rc};
+
+/// A cloneable `Write` target so tests can inspect
+/// what an interpreter printed
+#[derive(Clone, Default)]
+pub struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+impl SharedOutput {
+    pub fn contents(&self) -> String {
+        String::from_utf8(self.0.borrow().clone()).expect("output was not utf8")
+    }
+}
+
+impl Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Run each statement of `lox` on `int` until one fails,
+/// `lox` is expected to parse
+pub fn run_with(int: &mut Interpreter, lox: &str) -> Result<(), Error> {
+    for stmt in Parser::new(Scanner::new(lox.to_string()).unwrap()) {
+        int.interpret(&stmt.expect("failed to parse"))?;
+    }
+    Ok(())
+}
+
+/// Run `lox` on a new interpreter, which is returned so
+/// its globals can be checked
+pub fn run(lox: &str) -> Interpreter {
+    let mut int = Interpreter::new();
+    run_with(&mut int, lox).unwrap();
+    int
+}
+
+/// The error from running `lox`, which has to fail
+pub fn run_err(lox: &str) -> Error {
+    let mut int = Interpreter::new();
+    match run_with(&mut int, lox) {
+        Ok(()) => panic!("expected {} to fail", lox),
+        Err(e) => e,
+    }
+}
+
+/// Everything `lox` printed
+pub fn output(lox: &str) -> String {
+    let out = SharedOutput::default();
+    let mut int = Interpreter::with_io(std::io::empty(), out.clone());
+    run_with(&mut int, lox).unwrap();
+    out.contents()
+}
@@ -4,9 +4,15 @@ use crate::{
     expr::Literal,
     func::Func,
     globals::NativeFunc,
+    ordered_map::OrderedMap,
+};
+use rox_shared::format_number;
+use std::{
+    cell::RefCell,
+    rc::{Rc, Weak},
 };
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub enum Value {
     String(String),
     Number(f64),
@@ -15,8 +21,14 @@ pub enum Value {
     Func(Func),
     Init(Class),
     NativeFunc(NativeFunc),
-    Class(ClassInstance),
+    Class(Rc<RefCell<ClassInstance>>),
+    /// An instance that is only usable while something else
+    /// keeps it alive, made with the `weak` native
+    WeakRef(Weak<RefCell<ClassInstance>>),
     Method(Method),
+    /// Like instances, maps are shared by every value
+    /// referring to them
+    Map(Rc<RefCell<OrderedMap<Value>>>),
 }
 impl From<Literal> for Value {
     fn from(other: Literal) -> Self {
@@ -31,38 +43,459 @@ impl From<Literal> for Value {
 
 impl ::std::fmt::Display for Value {
     fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        self.write_display(f, &mut Vec::new())
+    }
+}
+
+impl ::std::fmt::Debug for Value {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        self.write_debug_fmt(f, &mut Vec::new())
+    }
+}
+
+impl ::std::cmp::PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        self.eq_inner(other, &mut Vec::new())
+    }
+}
+
+impl Value {
+    /// `parents` holds the maps currently being written,
+    /// finding one again means a cycle
+    fn write_display(
+        &self,
+        f: &mut ::std::fmt::Formatter,
+        parents: &mut Vec<*const ()>,
+    ) -> ::std::fmt::Result {
         match self {
             Value::String(s) => write!(f, "\"{}\"", s),
-            Value::Number(n) => n.fmt(f),
-            Value::Bool(b) => b.fmt(f),
+            Value::Number(n) => f.write_str(&format_number(*n)),
+            Value::Bool(b) => write!(f, "{}", b),
             Value::Nil => write!(f, "nil"),
             Value::Func(func) => write!(f, "{}", func),
-            Value::Class(class) => write!(f, "[{} instance]", class.class.name),
+            Value::Class(inst) => write!(f, "[{} instance]", inst.borrow().class.name),
+            Value::WeakRef(weak) => match weak.upgrade() {
+                Some(inst) => write!(f, "[weak {} instance]", inst.borrow().class.name),
+                None => write!(f, "[weak dropped instance]"),
+            },
             Value::Init(class) => write!(f, "[ctor {}]", class.name()),
-            Value::NativeFunc(c) => write!(f, "[native fn {}]", c.name()),
+            Value::NativeFunc(c) => write!(f, "{}", c),
             Value::Method(m) => write!(f, "{}", m),
+            Value::Map(map) => {
+                let ptr = map.as_ptr() as *const ();
+                if parents.contains(&ptr) {
+                    return f.write_str("{...}");
+                }
+                parents.push(ptr);
+                f.write_str("{")?;
+                for (i, (key, value)) in map.borrow().iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}: ", key)?;
+                    value.write_display(f, parents)?;
+                }
+                parents.pop();
+                f.write_str("}")
+            }
         }
     }
-}
 
-impl ::std::cmp::PartialEq for Value {
-    fn eq(&self, other: &Self) -> bool {
+    /// Like `write_display` but with each variant's name,
+    /// a map found inside itself is written as `<cycle>`
+    fn write_debug_fmt(
+        &self,
+        f: &mut ::std::fmt::Formatter,
+        parents: &mut Vec<*const ()>,
+    ) -> ::std::fmt::Result {
+        match self {
+            Value::String(s) => write!(f, "String({:?})", s),
+            Value::Number(n) => write!(f, "Number({:?})", n),
+            Value::Bool(b) => write!(f, "Bool({:?})", b),
+            Value::Nil => f.write_str("Nil"),
+            Value::Func(func) => write!(f, "Func({:?})", func),
+            Value::Init(class) => write!(f, "Init({:?})", class),
+            Value::NativeFunc(native) => write!(f, "NativeFunc({:?})", native),
+            Value::Class(inst) => write!(f, "Class({:?})", inst),
+            Value::Method(method) => write!(f, "Method({:?})", method),
+            Value::WeakRef(weak) => write!(f, "WeakRef({:?})", weak),
+            Value::Map(map) => {
+                let ptr = map.as_ptr() as *const ();
+                if parents.contains(&ptr) {
+                    return f.write_str("Map(<cycle>)");
+                }
+                parents.push(ptr);
+                f.write_str("Map({")?;
+                for (i, (key, value)) in map.borrow().iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{:?}: ", key)?;
+                    value.write_debug_fmt(f, parents)?;
+                }
+                parents.pop();
+                f.write_str("})")
+            }
+        }
+    }
+
+    /// `pairs` holds the maps currently being compared,
+    /// comparing the same two again means they only differ
+    /// if something outside the cycle does
+    fn eq_inner(&self, other: &Self, pairs: &mut Vec<(*const (), *const ())>) -> bool {
         match (self, other) {
             (Value::String(l), Value::String(r)) => l == r,
             (Value::Number(l), Value::Number(r)) => l == r,
             (Value::Bool(l), Value::Bool(r)) => l == r,
             (Value::Nil, Value::Nil) => true,
-            (Value::Func(l), Value::Func(r)) => l.name() == r.name(),
+            (Value::Func(l), Value::Func(r)) => {
+                Rc::ptr_eq(&l.body, &r.body) && l.env.ptr_eq(&r.env)
+            }
+            // the same method looked up on the same instance
+            (Value::Method(l), Value::Method(r)) => {
+                Rc::ptr_eq(&l.this, &r.this) && Rc::ptr_eq(&l.func.body, &r.func.body)
+            }
+            (Value::Init(l), Value::Init(r)) => Rc::ptr_eq(&l.methods, &r.methods),
+            (Value::Class(l), Value::Class(r)) => Rc::ptr_eq(l, r),
+            (Value::WeakRef(l), Value::WeakRef(r)) => Weak::ptr_eq(l, r),
+            (Value::WeakRef(weak), Value::Class(inst))
+            | (Value::Class(inst), Value::WeakRef(weak)) => {
+                std::ptr::eq(weak.as_ptr(), Rc::as_ptr(inst))
+            }
+            (Value::Map(l), Value::Map(r)) => {
+                if Rc::ptr_eq(l, r) {
+                    return true;
+                }
+                let pair = (l.as_ptr() as *const (), r.as_ptr() as *const ());
+                if pairs.contains(&pair) {
+                    return true;
+                }
+                pairs.push(pair);
+                let (l, r) = (l.borrow(), r.borrow());
+                let eq = l.len() == r.len()
+                    && l.iter().all(|(key, value)| {
+                        r.get(key)
+                            .map(|other| value.eq_inner(other, pairs))
+                            .unwrap_or(false)
+                    });
+                pairs.pop();
+                eq
+            }
             _ => false,
         }
     }
-}
 
-impl Value {
+    /// The text for this value when it is converted to a
+    /// string, unlike `Display` strings are not quoted
+    pub fn stringify(&self) -> String {
+        if let Value::String(s) = self {
+            s.clone()
+        } else {
+            self.to_string()
+        }
+    }
+    /// How many items this holds, characters for a string
+    /// and entries for a map. Other values have no length
+    pub fn length(&self) -> Option<usize> {
+        match self {
+            Value::String(s) => Some(s.chars().count()),
+            Value::Map(map) => Some(map.borrow().len()),
+            _ => None,
+        }
+    }
+    /// A human readable name for the type of this value,
+    /// used in error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::Number(_) => "number",
+            Value::Bool(_) => "bool",
+            Value::Nil => "nil",
+            Value::Func(_) => "function",
+            Value::Init(_) => "class",
+            Value::NativeFunc(_) => "native function",
+            Value::Class(_) => "instance",
+            Value::WeakRef(_) => "weak reference",
+            Value::Method(_) => "method",
+            Value::Map(_) => "map",
+        }
+    }
+    /// A JSON description of this value for tooling, every
+    /// value is an object with its `type`. Unlike `Display`
+    /// strings are escaped and instances list their fields.
+    /// A map or instance found inside itself is marked as
+    /// a `cycle` rather than written again
+    pub fn to_debug(&self) -> String {
+        let mut out = String::new();
+        self.write_debug(&mut out, &mut Vec::new());
+        out
+    }
+
+    fn write_debug(&self, out: &mut String, parents: &mut Vec<*const ()>) {
+        match self {
+            Value::String(s) => out.push_str(&format!(
+                r#"{{"type":"string","value":{}}}"#,
+                crate::json_string(s)
+            )),
+            Value::Number(n) if n.is_finite() => out.push_str(&format!(
+                r#"{{"type":"number","value":{}}}"#,
+                format_number(*n)
+            )),
+            // JSON has no infinity or NaN
+            Value::Number(n) => out.push_str(&format!(
+                r#"{{"type":"number","value":"{}"}}"#,
+                format_number(*n)
+            )),
+            Value::Bool(b) => out.push_str(&format!(r#"{{"type":"bool","value":{}}}"#, b)),
+            Value::Nil => out.push_str(r#"{"type":"nil"}"#),
+            Value::Func(_) | Value::NativeFunc(_) | Value::Method(_) => {
+                let name = self.as_callable().map(|c| c.name()).unwrap_or_default();
+                out.push_str(&format!(
+                    r#"{{"type":"function","name":{}}}"#,
+                    crate::json_string(name)
+                ))
+            }
+            Value::Init(class) => out.push_str(&format!(
+                r#"{{"type":"class","name":{}}}"#,
+                crate::json_string(&class.name)
+            )),
+            Value::Class(inst) => {
+                let inst_ref = inst.borrow();
+                out.push_str(&format!(
+                    r#"{{"type":"instance","class":{},"#,
+                    crate::json_string(&inst_ref.class.name)
+                ));
+                Self::write_debug_entries(
+                    "fields",
+                    inst.as_ptr() as *const (),
+                    &inst_ref.fields,
+                    out,
+                    parents,
+                );
+            }
+            // only the class is written, following the
+            // reference could lead straight back here
+            Value::WeakRef(weak) => match weak.upgrade() {
+                Some(inst) => out.push_str(&format!(
+                    r#"{{"type":"weak","class":{}}}"#,
+                    crate::json_string(&inst.borrow().class.name)
+                )),
+                None => out.push_str(r#"{"type":"weak","dropped":true}"#),
+            },
+            Value::Map(map) => {
+                out.push_str(r#"{"type":"map","#);
+                Self::write_debug_entries(
+                    "entries",
+                    map.as_ptr() as *const (),
+                    &map.borrow(),
+                    out,
+                    parents,
+                );
+            }
+        }
+    }
+
+    /// Finish an object opened by `write_debug` with its
+    /// entries under `key`
+    fn write_debug_entries(
+        key: &str,
+        ptr: *const (),
+        entries: &OrderedMap<Value>,
+        out: &mut String,
+        parents: &mut Vec<*const ()>,
+    ) {
+        if parents.contains(&ptr) {
+            out.push_str(r#""cycle":true}"#);
+            return;
+        }
+        parents.push(ptr);
+        out.push_str(&format!(r#""{}":{{"#, key));
+        for (i, (key, value)) in entries.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push_str(&crate::json_string(key));
+            out.push(':');
+            value.write_debug(out, parents);
+        }
+        out.push_str("}}");
+        parents.pop();
+    }
+    pub fn as_callable(&self) -> Option<&dyn Callable> {
+        match self {
+            Value::Func(f) => Some(f),
+            Value::Init(c) => Some(c),
+            Value::NativeFunc(n) => Some(n),
+            Value::Method(m) => Some(m),
+            _ => None,
+        }
+    }
+    pub fn as_callable_mut(&mut self) -> Option<&mut dyn Callable> {
+        match self {
+            Value::Func(f) => Some(f),
+            Value::Init(c) => Some(c),
+            Value::NativeFunc(n) => Some(n),
+            Value::Method(m) => Some(m),
+            _ => None,
+        }
+    }
     pub fn clock() -> Self {
         Value::NativeFunc(NativeFunc::Clock(crate::globals::Clock))
     }
     pub fn modulo() -> Self {
         Value::NativeFunc(NativeFunc::Mod(crate::globals::Mod))
     }
+    pub fn to_number() -> Self {
+        Value::NativeFunc(NativeFunc::ToNumber(crate::globals::ToNumber))
+    }
+    pub fn to_string() -> Self {
+        Value::NativeFunc(NativeFunc::ToString(crate::globals::ToString))
+    }
+    pub fn floor() -> Self {
+        Value::NativeFunc(NativeFunc::Floor(crate::globals::Floor))
+    }
+    pub fn ceil() -> Self {
+        Value::NativeFunc(NativeFunc::Ceil(crate::globals::Ceil))
+    }
+    pub fn round() -> Self {
+        Value::NativeFunc(NativeFunc::Round(crate::globals::Round))
+    }
+    pub fn abs() -> Self {
+        Value::NativeFunc(NativeFunc::Abs(crate::globals::Abs))
+    }
+    pub fn weak() -> Self {
+        Value::NativeFunc(NativeFunc::Weak(crate::globals::Weak))
+    }
+    pub fn read_line() -> Self {
+        Value::NativeFunc(NativeFunc::ReadLine(crate::globals::ReadLine))
+    }
+    pub fn assert() -> Self {
+        Value::NativeFunc(NativeFunc::Assert(crate::globals::Assert))
+    }
+    pub fn to_base() -> Self {
+        Value::NativeFunc(NativeFunc::ToBase(crate::globals::ToBase))
+    }
+    pub fn partial_right() -> Self {
+        Value::NativeFunc(NativeFunc::PartialRight(crate::globals::PartialRight))
+    }
+    pub fn has_field() -> Self {
+        Value::NativeFunc(NativeFunc::HasField(crate::globals::HasField))
+    }
+    pub fn delete_field() -> Self {
+        Value::NativeFunc(NativeFunc::DeleteField(crate::globals::DeleteField))
+    }
+    pub fn exit() -> Self {
+        Value::NativeFunc(NativeFunc::Exit(crate::globals::Exit))
+    }
+    pub fn type_of() -> Self {
+        Value::NativeFunc(NativeFunc::TypeOf(crate::globals::TypeOf))
+    }
+    pub fn fields() -> Self {
+        Value::NativeFunc(NativeFunc::Fields(crate::globals::Fields))
+    }
+    pub fn to_json() -> Self {
+        Value::NativeFunc(NativeFunc::ToJson(crate::globals::ToJson))
+    }
+    pub fn times() -> Self {
+        Value::NativeFunc(NativeFunc::Times(crate::globals::Times))
+    }
+    pub fn len() -> Self {
+        Value::NativeFunc(NativeFunc::Len(crate::globals::Len))
+    }
+    pub fn printf() -> Self {
+        Value::NativeFunc(NativeFunc::Printf(crate::globals::Printf))
+    }
+    pub fn new_map() -> Self {
+        Value::NativeFunc(NativeFunc::NewMap(crate::globals::NewMap))
+    }
+    pub fn source() -> Self {
+        Value::NativeFunc(NativeFunc::Source(crate::globals::Source))
+    }
+    pub fn memoize() -> Self {
+        Value::NativeFunc(NativeFunc::Memoize(crate::globals::Memoize))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn eval(lox: &str, name: &str) -> Value {
+        crate::test_util::run(lox)
+            .get_global(name)
+            .expect("missing global")
+    }
+
+    #[test]
+    fn to_debug() {
+        assert_eq!(
+            Value::Number(3.0).to_debug(),
+            r#"{"type":"number","value":3}"#
+        );
+        assert_eq!(
+            Value::Number(f64::INFINITY).to_debug(),
+            r#"{"type":"number","value":"inf"}"#
+        );
+        assert_eq!(
+            Value::String("say \"hi\"\n".to_string()).to_debug(),
+            r#"{"type":"string","value":"say \"hi\"\n"}"#
+        );
+        assert_eq!(Value::Nil.to_debug(), r#"{"type":"nil"}"#);
+        assert_eq!(
+            Value::len().to_debug(),
+            r#"{"type":"function","name":"len"}"#
+        );
+        let lox = "
+class Point {
+    init(x, y) {
+        this.x = x;
+        this.y = y;
+    }
+    norm() {}
+}
+var p = Point(1, true);
+var norm = p.norm;
+p.self = p;
+";
+        assert_eq!(
+            eval(lox, "p").to_debug(),
+            concat!(
+                r#"{"type":"instance","class":"Point","fields":{"#,
+                r#""x":{"type":"number","value":1},"#,
+                r#""y":{"type":"bool","value":true},"#,
+                r#""self":{"type":"instance","class":"Point","cycle":true}}}"#,
+            )
+        );
+        assert_eq!(
+            eval(lox, "norm").to_debug(),
+            r#"{"type":"function","name":"norm"}"#
+        );
+        assert_eq!(
+            eval(lox, "Point").to_debug(),
+            r#"{"type":"class","name":"Point"}"#
+        );
+    }
+
+    #[test]
+    fn cyclic_maps() {
+        let lox = "
+var a = new_map();
+a.name = \"a\";
+a.self = a;
+var b = new_map();
+b.name = \"a\";
+b.self = b;
+var c = new_map();
+c.name = \"c\";
+c.self = c;
+";
+        let (a, b, c) = (eval(lox, "a"), eval(lox, "b"), eval(lox, "c"));
+        assert_eq!(a.to_string(), r#"{name: "a", self: {...}}"#);
+        assert_eq!(
+            format!("{:?}", a),
+            r#"Map({"name": String("a"), "self": Map(<cycle>)})"#
+        );
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
 }
@@ -0,0 +1,79 @@
+use std::process::Command;
+
+#[test]
+fn reports_every_error() {
+    let path = std::env::temp_dir().join(format!("roxi-json-errors-{}.lox", std::process::id()));
+    std::fs::write(&path, "var a = 1;\nvar b = ;\nprint a;\nprint (a;\n").unwrap();
+    let out = Command::new(env!("CARGO_BIN_EXE_roxi"))
+        .arg("--errors=json")
+        .arg(&path)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(out.status.code(), Some(65));
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    // a script with syntax errors doesn't run, so
+    // the report is all that is written
+    let report = stdout.trim_end();
+    assert!(!report.contains('\n'), "{}", stdout);
+    let reports: serde_json::Value = serde_json::from_str(report).unwrap();
+    let reports = reports.as_array().unwrap();
+    assert_eq!(reports.len(), 2, "{}", stdout);
+    let lines: Vec<u64> = reports
+        .iter()
+        .map(|r| r["line"].as_u64().unwrap())
+        .collect();
+    assert_eq!(lines, [2, 4]);
+    for report in reports {
+        assert_eq!(report["severity"], "error");
+        assert!(report["column"].is_u64());
+        assert!(report["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("Parser error"));
+    }
+}
+
+#[test]
+fn reports_static_errors_before_running() {
+    let path = std::env::temp_dir().join(format!("roxi-json-static-{}.lox", std::process::id()));
+    std::fs::write(
+        &path,
+        "print \"side effect\";\nprint nope;\nvar = 1;\nvar x = @;\nvar = 2;\nfun f(a, a) {}\n",
+    )
+    .unwrap();
+    let out = Command::new(env!("CARGO_BIN_EXE_roxi"))
+        .arg("--errors=json")
+        .arg(&path)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(out.status.code(), Some(65));
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let report = stdout.trim_end();
+    assert!(!report.contains('\n'), "{}", stdout);
+    let reports: serde_json::Value = serde_json::from_str(report).unwrap();
+    let found: Vec<(u64, u64, &str)> = reports
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| {
+            let message = r["message"].as_str().unwrap();
+            let kind = message.split(" error").next().unwrap();
+            (
+                r["line"].as_u64().unwrap(),
+                r["column"].as_u64().unwrap(),
+                kind,
+            )
+        })
+        .collect();
+    assert_eq!(
+        found,
+        [
+            (3, 5, "Parser"),
+            (4, 9, "Scanning"),
+            (5, 5, "Parser"),
+            (6, 10, "Resolution"),
+        ]
+    );
+}
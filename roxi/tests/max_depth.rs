@@ -0,0 +1,16 @@
+use std::process::Command;
+
+#[test]
+fn rejects_a_depth_past_the_limit() {
+    let out = Command::new(env!("CARGO_BIN_EXE_roxi"))
+        .arg("--max-depth=100000000")
+        .arg("missing.lox")
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(64));
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    assert_eq!(
+        stderr,
+        "Invalid max depth 100000000, expected a number up to 100000\n"
+    );
+}